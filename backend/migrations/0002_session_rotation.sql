@@ -0,0 +1,4 @@
+ALTER TABLE UserSessions ADD COLUMN IF NOT EXISTS issued_at VARCHAR(255);
+ALTER TABLE UserSessions ADD COLUMN IF NOT EXISTS replaced_by uuid;
+
+UPDATE UserSessions SET issued_at = created_at WHERE issued_at IS NULL;
@@ -0,0 +1,30 @@
+CREATE TABLE IF NOT EXISTS UserTotp (
+    user_uuid uuid PRIMARY KEY NOT NULL,
+    secret VARCHAR(255) NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    last_used_step BIGINT,
+    created_at VARCHAR(255) DEFAULT CURRENT_TIMESTAMP,
+    updated_at VARCHAR(255) DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT FK_user_totp FOREIGN KEY(user_uuid)
+        REFERENCES Users(uuid)
+);
+
+CREATE TABLE IF NOT EXISTS RecoveryCodes (
+    uuid uuid PRIMARY KEY NOT NULL,
+    user_uuid uuid NOT NULL,
+    code_hash VARCHAR(255) NOT NULL,
+    used_at VARCHAR(255),
+    created_at VARCHAR(255) DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT FK_user_recovery_code FOREIGN KEY(user_uuid)
+        REFERENCES Users(uuid)
+);
+
+CREATE TABLE IF NOT EXISTS LoginChallenges (
+    uuid uuid PRIMARY KEY NOT NULL,
+    user_uuid uuid NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    created_at VARCHAR(255) DEFAULT CURRENT_TIMESTAMP,
+    expires_at VARCHAR(255) NOT NULL,
+    CONSTRAINT FK_user_login_challenge FOREIGN KEY(user_uuid)
+        REFERENCES Users(uuid)
+);
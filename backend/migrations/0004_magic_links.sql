@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS MagicLinks (
+    uuid uuid PRIMARY KEY NOT NULL,
+    user_uuid uuid NOT NULL,
+    created_at VARCHAR(255) DEFAULT CURRENT_TIMESTAMP,
+    expires_at VARCHAR(255) NOT NULL,
+    used_at VARCHAR(255),
+    CONSTRAINT FK_user_magic_link FOREIGN KEY(user_uuid)
+        REFERENCES Users(uuid)
+);
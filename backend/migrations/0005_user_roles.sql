@@ -0,0 +1,3 @@
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS role VARCHAR(32) NOT NULL DEFAULT 'user'
+    CONSTRAINT CK_user_role CHECK (role IN ('user', 'admin'));
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS disabled_at VARCHAR(255);
@@ -0,0 +1,44 @@
+CREATE TABLE IF NOT EXISTS Organisations (
+    uuid uuid PRIMARY KEY NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    created_by uuid NOT NULL,
+    created_at VARCHAR(255) DEFAULT CURRENT_TIMESTAMP,
+    updated_at VARCHAR(255) DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT FK_user_organisation FOREIGN KEY(created_by)
+        REFERENCES Users(uuid)
+);
+
+CREATE TABLE IF NOT EXISTS OrganisationMembers (
+    organisation_uuid uuid NOT NULL,
+    user_uuid uuid NOT NULL,
+    role VARCHAR(32) NOT NULL DEFAULT 'member'
+        CONSTRAINT CK_organisation_member_role CHECK (role IN ('owner', 'admin', 'member')),
+    created_at VARCHAR(255) DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (organisation_uuid, user_uuid),
+    CONSTRAINT FK_organisation_member_organisation FOREIGN KEY(organisation_uuid)
+        REFERENCES Organisations(uuid),
+    CONSTRAINT FK_organisation_member_user FOREIGN KEY(user_uuid)
+        REFERENCES Users(uuid)
+);
+
+CREATE TABLE IF NOT EXISTS OrganisationInvites (
+    uuid uuid PRIMARY KEY NOT NULL,
+    organisation_uuid uuid NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    role VARCHAR(32) NOT NULL DEFAULT 'member'
+        CONSTRAINT CK_organisation_invite_role CHECK (role IN ('admin', 'member')),
+    invited_by uuid NOT NULL,
+    created_at VARCHAR(255) DEFAULT CURRENT_TIMESTAMP,
+    expires_at VARCHAR(255) NOT NULL,
+    accepted_at VARCHAR(255),
+    CONSTRAINT FK_organisation_invite_organisation FOREIGN KEY(organisation_uuid)
+        REFERENCES Organisations(uuid),
+    CONSTRAINT FK_organisation_invite_user FOREIGN KEY(invited_by)
+        REFERENCES Users(uuid)
+);
+
+-- Documents in a workspace keep the user that created them in user_uuid
+ALTER TABLE Documents ADD COLUMN IF NOT EXISTS organisation_uuid uuid
+    CONSTRAINT FK_organisation_document REFERENCES Organisations(uuid);
+
+CREATE INDEX IF NOT EXISTS IX_documents_organisation ON Documents(organisation_uuid);
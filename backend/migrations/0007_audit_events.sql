@@ -0,0 +1,34 @@
+CREATE TABLE IF NOT EXISTS audit_events (
+    uuid uuid PRIMARY KEY NOT NULL,
+    -- Who did it, kept without a foreign key so events outlive deleted accounts
+    user_uuid uuid,
+    event_type VARCHAR(64) NOT NULL,
+    target_type VARCHAR(64),
+    target_uuid uuid,
+    organisation_uuid uuid,
+    metadata JSONB NOT NULL DEFAULT '{}'::jsonb,
+    ip_address VARCHAR(64),
+    user_agent VARCHAR(512),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS IX_audit_events_user_created ON audit_events(user_uuid, created_at DESC);
+CREATE INDEX IF NOT EXISTS IX_audit_events_type_created ON audit_events(event_type, created_at DESC);
+CREATE INDEX IF NOT EXISTS IX_audit_events_created ON audit_events(created_at DESC);
+
+-- The audit log is append-only, nothing may change or remove an event
+CREATE OR REPLACE FUNCTION prevent_audit_event_changes() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS TR_audit_events_append_only ON audit_events;
+CREATE TRIGGER TR_audit_events_append_only
+    BEFORE UPDATE OR DELETE ON audit_events
+    FOR EACH ROW EXECUTE FUNCTION prevent_audit_event_changes();
+
+DROP TRIGGER IF EXISTS TR_audit_events_no_truncate ON audit_events;
+CREATE TRIGGER TR_audit_events_no_truncate
+    BEFORE TRUNCATE ON audit_events
+    FOR EACH STATEMENT EXECUTE FUNCTION prevent_audit_event_changes();
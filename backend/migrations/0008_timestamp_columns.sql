@@ -0,0 +1,46 @@
+-- Timestamps were stored as text, either Unix seconds written by the backend or the
+-- CURRENT_TIMESTAMP text of a column default. Both are converted to TIMESTAMPTZ, columns
+-- that were already converted are left alone
+DO $$
+DECLARE
+    col RECORD;
+BEGIN
+    FOR col IN
+        SELECT c.table_name, c.column_name, c.column_default IS NOT NULL AS has_default
+        FROM information_schema.columns AS c
+        WHERE c.table_schema = current_schema()
+            AND c.data_type = 'character varying'
+            AND (c.table_name, c.column_name) IN (
+                ('users', 'created_at'),
+                ('users', 'updated_at'),
+                ('users', 'disabled_at'),
+                ('documents', 'created_at'),
+                ('documents', 'updated_at'),
+                ('usersessions', 'created_at'),
+                ('usersessions', 'expires_at'),
+                ('usersessions', 'issued_at')
+            )
+    LOOP
+        -- The text default can't be cast along with the column
+        EXECUTE format('ALTER TABLE %I ALTER COLUMN %I DROP DEFAULT', col.table_name, col.column_name);
+        EXECUTE format(
+            'ALTER TABLE %1$I ALTER COLUMN %2$I TYPE TIMESTAMPTZ USING CASE
+                WHEN NULLIF(TRIM(%2$I), '''') IS NULL THEN NULL
+                WHEN TRIM(%2$I) ~ ''^[0-9]+$'' THEN TO_TIMESTAMP(TRIM(%2$I)::BIGINT)
+                ELSE TRIM(%2$I)::TIMESTAMPTZ
+            END',
+            col.table_name,
+            col.column_name
+        );
+        IF col.has_default THEN
+            EXECUTE format(
+                'ALTER TABLE %I ALTER COLUMN %I SET DEFAULT CURRENT_TIMESTAMP',
+                col.table_name,
+                col.column_name
+            );
+        END IF;
+    END LOOP;
+END $$;
+
+-- Session lookups and the cleanup task filter on expiry
+CREATE INDEX IF NOT EXISTS IX_user_sessions_expires_at ON UserSessions(expires_at);
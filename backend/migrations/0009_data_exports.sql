@@ -0,0 +1,18 @@
+-- Archives users build of their own data. The archive is kept in the row until the download
+-- link expires, so any server instance can hand it out
+CREATE TABLE IF NOT EXISTS DataExports (
+    uuid uuid PRIMARY KEY NOT NULL,
+    user_uuid uuid NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'ready', 'failed')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    completed_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ NOT NULL,
+    size_bytes BIGINT,
+    archive BYTEA,
+    CONSTRAINT FK_user_data_export FOREIGN KEY(user_uuid)
+        REFERENCES Users(uuid) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_data_exports_user ON DataExports(user_uuid);
+CREATE INDEX IF NOT EXISTS IX_data_exports_expires_at ON DataExports(expires_at);
@@ -0,0 +1,6 @@
+-- Accounts whose owner asked for them to be deleted are kept until this time, so the
+-- deletion can still be cancelled
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS deletion_scheduled_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS IX_users_deletion_scheduled_at ON Users(deletion_scheduled_at)
+    WHERE deletion_scheduled_at IS NOT NULL;
@@ -0,0 +1,22 @@
+-- Users can pick how they are shown instead of the name and picture Google gave them
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS display_name VARCHAR(255);
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS avatar_url VARCHAR(2048);
+
+-- Editor settings, kept on the server so they follow the user across devices. A user
+-- without a row gets the defaults
+CREATE TABLE IF NOT EXISTS UserPreferences (
+    user_uuid uuid PRIMARY KEY NOT NULL,
+    theme VARCHAR(16) NOT NULL DEFAULT 'system'
+        CHECK (theme IN ('system', 'light', 'dark')),
+    editor_font_size INTEGER NOT NULL DEFAULT 14
+        CHECK (editor_font_size BETWEEN 8 AND 32),
+    keybinding_mode VARCHAR(16) NOT NULL DEFAULT 'default'
+        CHECK (keybinding_mode IN ('default', 'vim', 'emacs')),
+    default_export_format VARCHAR(16) NOT NULL DEFAULT 'markdown'
+        CHECK (default_export_format IN ('markdown', 'html', 'pdf')),
+    preview_layout VARCHAR(16) NOT NULL DEFAULT 'split'
+        CHECK (preview_layout IN ('split', 'editor', 'preview')),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT FK_user_preferences FOREIGN KEY(user_uuid)
+        REFERENCES Users(uuid) ON DELETE CASCADE
+);
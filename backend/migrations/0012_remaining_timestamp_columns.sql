@@ -0,0 +1,53 @@
+-- The same conversion as 0008 for the tables it left out, so expiries are compared as
+-- times rather than as text
+DO $$
+DECLARE
+    col RECORD;
+BEGIN
+    FOR col IN
+        SELECT c.table_name, c.column_name, c.column_default IS NOT NULL AS has_default
+        FROM information_schema.columns AS c
+        WHERE c.table_schema = current_schema()
+            AND c.data_type = 'character varying'
+            AND (c.table_name, c.column_name) IN (
+                ('magiclinks', 'created_at'),
+                ('magiclinks', 'expires_at'),
+                ('magiclinks', 'used_at'),
+                ('loginchallenges', 'created_at'),
+                ('loginchallenges', 'expires_at'),
+                ('recoverycodes', 'created_at'),
+                ('recoverycodes', 'used_at'),
+                ('usertotp', 'created_at'),
+                ('usertotp', 'updated_at'),
+                ('organisations', 'created_at'),
+                ('organisations', 'updated_at'),
+                ('organisationmembers', 'created_at'),
+                ('organisationinvites', 'created_at'),
+                ('organisationinvites', 'expires_at'),
+                ('organisationinvites', 'accepted_at')
+            )
+    LOOP
+        -- The text default can't be cast along with the column
+        EXECUTE format('ALTER TABLE %I ALTER COLUMN %I DROP DEFAULT', col.table_name, col.column_name);
+        EXECUTE format(
+            'ALTER TABLE %1$I ALTER COLUMN %2$I TYPE TIMESTAMPTZ USING CASE
+                WHEN NULLIF(TRIM(%2$I), '''') IS NULL THEN NULL
+                WHEN TRIM(%2$I) ~ ''^[0-9]+$'' THEN TO_TIMESTAMP(TRIM(%2$I)::BIGINT)
+                ELSE TRIM(%2$I)::TIMESTAMPTZ
+            END',
+            col.table_name,
+            col.column_name
+        );
+        IF col.has_default THEN
+            EXECUTE format(
+                'ALTER TABLE %I ALTER COLUMN %I SET DEFAULT CURRENT_TIMESTAMP',
+                col.table_name,
+                col.column_name
+            );
+        END IF;
+    END LOOP;
+END $$;
+
+-- The cleanup task deletes by expiry
+CREATE INDEX IF NOT EXISTS IX_magic_links_expires_at ON MagicLinks(expires_at);
+CREATE INDEX IF NOT EXISTS IX_login_challenges_expires_at ON LoginChallenges(expires_at);
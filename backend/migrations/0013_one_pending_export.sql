@@ -0,0 +1,13 @@
+-- One export is built at a time per user. Users that already have more than one being
+-- built keep the newest
+UPDATE DataExports SET status = 'failed'
+WHERE status = 'pending'
+    AND uuid NOT IN (
+        SELECT DISTINCT ON (user_uuid) uuid
+        FROM DataExports
+        WHERE status = 'pending'
+        ORDER BY user_uuid, created_at DESC
+    );
+
+CREATE UNIQUE INDEX IF NOT EXISTS UX_data_exports_pending ON DataExports(user_uuid)
+    WHERE status = 'pending';
@@ -0,0 +1,35 @@
+-- The SQLite schema only holds what the repositories need. Timestamps are RFC 3339 text in
+-- UTC and UUIDs are 16 byte blobs, which is how sqlx encodes them for SQLite
+CREATE TABLE IF NOT EXISTS Users (
+    uuid BLOB PRIMARY KEY NOT NULL,
+    username TEXT NOT NULL,
+    email TEXT NOT NULL,
+    created_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    role TEXT NOT NULL DEFAULT 'user' CHECK (role IN ('user', 'admin')),
+    disabled_at TEXT
+);
+
+CREATE TABLE IF NOT EXISTS Documents (
+    uuid BLOB PRIMARY KEY NOT NULL,
+    user_uuid BLOB NOT NULL REFERENCES Users(uuid),
+    title TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    -- Organisations need PostgreSQL, the column is kept so documents look the same
+    organisation_uuid BLOB
+);
+
+CREATE INDEX IF NOT EXISTS IX_documents_user ON Documents(user_uuid);
+
+CREATE TABLE IF NOT EXISTS UserSessions (
+    uuid BLOB PRIMARY KEY NOT NULL,
+    user_uuid BLOB NOT NULL REFERENCES Users(uuid),
+    created_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    expires_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    issued_at TEXT,
+    replaced_by BLOB
+);
+
+CREATE INDEX IF NOT EXISTS IX_user_sessions_expires_at ON UserSessions(expires_at);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS DataExports (
+    uuid BLOB PRIMARY KEY NOT NULL,
+    user_uuid BLOB NOT NULL REFERENCES Users(uuid) ON DELETE CASCADE,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'ready', 'failed')),
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    completed_at TEXT,
+    expires_at TEXT NOT NULL,
+    size_bytes INTEGER,
+    archive BLOB
+);
+
+CREATE INDEX IF NOT EXISTS IX_data_exports_user ON DataExports(user_uuid);
+CREATE INDEX IF NOT EXISTS IX_data_exports_expires_at ON DataExports(expires_at);
@@ -0,0 +1,3 @@
+-- Accounts whose owner asked for them to be deleted are kept until this time, so the
+-- deletion can still be cancelled
+ALTER TABLE Users ADD COLUMN deletion_scheduled_at TEXT;
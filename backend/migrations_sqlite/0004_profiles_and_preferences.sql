@@ -0,0 +1,18 @@
+-- Users can pick how they are shown instead of the name and picture Google gave them
+ALTER TABLE Users ADD COLUMN display_name TEXT;
+ALTER TABLE Users ADD COLUMN avatar_url TEXT;
+
+-- Editor settings, kept on the server so they follow the user across devices. A user
+-- without a row gets the defaults
+CREATE TABLE IF NOT EXISTS UserPreferences (
+    user_uuid BLOB PRIMARY KEY NOT NULL REFERENCES Users(uuid) ON DELETE CASCADE,
+    theme TEXT NOT NULL DEFAULT 'system' CHECK (theme IN ('system', 'light', 'dark')),
+    editor_font_size INTEGER NOT NULL DEFAULT 14 CHECK (editor_font_size BETWEEN 8 AND 32),
+    keybinding_mode TEXT NOT NULL DEFAULT 'default'
+        CHECK (keybinding_mode IN ('default', 'vim', 'emacs')),
+    default_export_format TEXT NOT NULL DEFAULT 'markdown'
+        CHECK (default_export_format IN ('markdown', 'html', 'pdf')),
+    preview_layout TEXT NOT NULL DEFAULT 'split'
+        CHECK (preview_layout IN ('split', 'editor', 'preview')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
@@ -0,0 +1,13 @@
+-- One export is built at a time per user. Users that already have more than one being
+-- built keep the newest
+UPDATE DataExports SET status = 'failed'
+WHERE status = 'pending'
+    AND EXISTS (
+        SELECT 1 FROM DataExports AS newer
+        WHERE newer.user_uuid = DataExports.user_uuid
+            AND newer.status = 'pending'
+            AND julianday(newer.created_at) > julianday(DataExports.created_at)
+    );
+
+CREATE UNIQUE INDEX IF NOT EXISTS UX_data_exports_pending ON DataExports(user_uuid)
+    WHERE status = 'pending';
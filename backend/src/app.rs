@@ -0,0 +1,100 @@
+use axum::http::header::{CONTENT_TYPE, RETRY_AFTER};
+use axum::http::{HeaderName, Method};
+use axum::{middleware, Router};
+use tower_http::{
+    cors::CorsLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    services::{ServeDir, ServeFile},
+    trace::TraceLayer,
+};
+
+use crate::routes::admin::admin_routes;
+use crate::routes::auth::google_auth_router;
+use crate::routes::documents::document_routes;
+use crate::routes::health::health_routes;
+use crate::routes::metrics::metrics_routes;
+use crate::routes::openapi::openapi_routes;
+use crate::routes::organisations::organisation_routes;
+use crate::routes::two_factor::two_factor_routes;
+use crate::routes::users::users_routes;
+use crate::state::AppState;
+use crate::utils::auth::require_user;
+use crate::utils::constants::{HEADER_REQUEST_ID, HEADER_WORKSPACE};
+use crate::utils::monitoring;
+use crate::utils::rate_limit;
+use crate::utils::session::refresh_session;
+use crate::utils::telemetry;
+
+// Puts every router together into the application main.rs serves
+pub fn router(state: AppState) -> Router {
+    let request_id_header = HeaderName::from_static(HEADER_REQUEST_ID);
+    let cors_middleware = CorsLayer::new()
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::DELETE,
+            Method::PUT,
+            Method::PATCH,
+        ])
+        .allow_origin(state.config.cors_origins.clone())
+        .allow_headers([CONTENT_TYPE, HeaderName::from_static(HEADER_WORKSPACE)])
+        .expose_headers([request_id_header.clone(), RETRY_AFTER])
+        .allow_credentials(true);
+
+    // Everything in here needs a signed in user. The check runs before any handler, so new
+    // endpoints are covered without having to remember it
+    let protected_router = Router::new()
+        .nest("/users", users_routes())
+        .nest("/users/me/2fa", two_factor_routes())
+        .nest("/documents", document_routes())
+        .nest("/organisations", organisation_routes())
+        .nest("/admin", admin_routes(state.clone()))
+        // keep the session alive while the user is active. Route layers run outside in, so
+        // this only runs once require_user accepted the session
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            refresh_session,
+        ))
+        // runs after require_user, so each user has a budget of their own
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::limit_requests,
+        ))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_user));
+
+    let api_router = Router::new()
+        .nest(
+            "/auth",
+            google_auth_router().route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                rate_limit::limit_auth,
+            )),
+        )
+        .merge(protected_router)
+        .layer(cors_middleware);
+
+    let dist_dir = state.config.dist_dir.clone();
+
+    Router::new()
+        .nest_service(
+            "/",
+            // Unknown paths are client side routes, so let the frontend handle them
+            ServeDir::new(&dist_dir).fallback(ServeFile::new(dist_dir.join("index.html"))),
+        )
+        .merge(api_router)
+        .merge(health_routes())
+        .merge(openapi_routes())
+        .merge(metrics_routes())
+        .with_state(state)
+        .layer(middleware::from_fn(monitoring::track_http_metrics))
+        // Every request gets an identifier, echoed back in X-Request-Id and logged in its span
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(telemetry::request_span)
+                .on_request(())
+                .on_response(telemetry::record_response)
+                .on_failure(()),
+        )
+        .layer(PropagateRequestIdLayer::new(request_id_header.clone()))
+        .layer(SetRequestIdLayer::new(request_id_header, MakeRequestUuid))
+}
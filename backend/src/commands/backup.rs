@@ -0,0 +1,513 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::db::connection::{Database, MIGRATOR};
+use crate::db::repository::BackupData;
+
+// A backup is a zip archive holding a manifest and one JSON record per line for each table.
+// It doesn't depend on the database it came from, so a PostgreSQL backup restores into
+// another PostgreSQL version or into SQLite
+const BACKUP_FORMAT: &str = "markdown-edit-backup";
+// Bumped whenever the layout of the archive or its records changes. Version 1 only held
+// users, their personal documents and sessions, and still restores
+const BACKUP_VERSION: u32 = 2;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const USERS_FILE: &str = "users.jsonl";
+const PREFERENCES_FILE: &str = "preferences.jsonl";
+const SESSIONS_FILE: &str = "sessions.jsonl";
+const TOTP_FILE: &str = "totp.jsonl";
+const RECOVERY_CODES_FILE: &str = "recovery_codes.jsonl";
+const ORGANISATIONS_FILE: &str = "organisations.jsonl";
+const ORGANISATION_MEMBERS_FILE: &str = "organisation_members.jsonl";
+const ORGANISATION_INVITES_FILE: &str = "organisation_invites.jsonl";
+const DOCUMENTS_FILE: &str = "documents.jsonl";
+
+// Records per file. Version 1 manifests don't have the counts added since
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    format: String,
+    version: u32,
+    // The newest migration in backend/migrations the data was taken at
+    schema_version: i64,
+    created_at: DateTime<Utc>,
+    users: usize,
+    #[serde(default)]
+    preferences: usize,
+    sessions: usize,
+    #[serde(default)]
+    totp: usize,
+    #[serde(default)]
+    recovery_codes: usize,
+    #[serde(default)]
+    organisations: usize,
+    #[serde(default)]
+    organisation_members: usize,
+    #[serde(default)]
+    organisation_invites: usize,
+    documents: usize,
+}
+
+pub async fn backup(database: &Database, output: &Path) -> Result<(), anyhow::Error> {
+    let backup = database
+        .backups()
+        .fetch_backup()
+        .await
+        .context("Failed to read the database")?;
+
+    let manifest = Manifest {
+        format: BACKUP_FORMAT.to_string(),
+        version: BACKUP_VERSION,
+        // Commands only run on an up to date schema, SQLite's included, so the data has the
+        // shape the newest migration gives it
+        schema_version: latest_schema_version(),
+        created_at: Utc::now(),
+        users: backup.users.len(),
+        preferences: backup.preferences.len(),
+        sessions: backup.sessions.len(),
+        totp: backup.totp.len(),
+        recovery_codes: backup.recovery_codes.len(),
+        organisations: backup.organisations.len(),
+        organisation_members: backup.organisation_members.len(),
+        organisation_invites: backup.organisation_invites.len(),
+        documents: backup.documents.len(),
+    };
+
+    // Written next to the destination first, so a failed backup never replaces a good one
+    let partial = output.with_extension("partial");
+    write_archive(&partial, &manifest, &backup)
+        .with_context(|| format!("Failed to write {}", partial.display()))?;
+    fs::rename(&partial, output)
+        .with_context(|| format!("Failed to move the backup to {}", output.display()))?;
+
+    println!(
+        "Backed up {} user(s), {} document(s), {} session(s) and {} organisation(s) to {}",
+        manifest.users,
+        manifest.documents,
+        manifest.sessions,
+        manifest.organisations,
+        output.display()
+    );
+
+    Ok(())
+}
+
+pub async fn restore(database: &Database, file: &Path) -> Result<(), anyhow::Error> {
+    let (manifest, backup) =
+        read_archive(file).with_context(|| format!("Failed to read {}", file.display()))?;
+
+    // SQLite has no tables for these, restoring without them would lose them for good
+    if database.postgres().is_none()
+        && !(backup.organisations.is_empty()
+            && backup.totp.is_empty()
+            && backup.recovery_codes.is_empty())
+    {
+        bail!(
+            "The backup holds organisations or two-factor settings, which need DATABASE_BACKEND=postgres"
+        );
+    }
+
+    let backups = database.backups();
+    let users = backups
+        .count_users()
+        .await
+        .context("Failed to check the database is empty")?;
+    if users > 0 {
+        bail!(
+            "The database already has {users} user(s), backups only restore into an empty database"
+        );
+    }
+
+    backups
+        .restore_backup(&backup)
+        .await
+        .context("Failed to restore the backup, nothing was written")?;
+
+    println!(
+        "Restored {} user(s), {} document(s), {} session(s) and {} organisation(s) backed up at {}",
+        manifest.users,
+        manifest.documents,
+        manifest.sessions,
+        manifest.organisations,
+        manifest.created_at
+    );
+
+    Ok(())
+}
+
+fn latest_schema_version() -> i64 {
+    MIGRATOR
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or_default()
+}
+
+fn write_archive(
+    path: &Path,
+    manifest: &Manifest,
+    backup: &BackupData,
+) -> Result<(), anyhow::Error> {
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(path)?));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file(MANIFEST_FILE, options)?;
+    serde_json::to_writer_pretty(&mut zip, manifest)?;
+
+    write_records(&mut zip, options, USERS_FILE, &backup.users)?;
+    write_records(&mut zip, options, PREFERENCES_FILE, &backup.preferences)?;
+    write_records(&mut zip, options, SESSIONS_FILE, &backup.sessions)?;
+    write_records(&mut zip, options, TOTP_FILE, &backup.totp)?;
+    write_records(
+        &mut zip,
+        options,
+        RECOVERY_CODES_FILE,
+        &backup.recovery_codes,
+    )?;
+    write_records(&mut zip, options, ORGANISATIONS_FILE, &backup.organisations)?;
+    write_records(
+        &mut zip,
+        options,
+        ORGANISATION_MEMBERS_FILE,
+        &backup.organisation_members,
+    )?;
+    write_records(
+        &mut zip,
+        options,
+        ORGANISATION_INVITES_FILE,
+        &backup.organisation_invites,
+    )?;
+    write_records(&mut zip, options, DOCUMENTS_FILE, &backup.documents)?;
+
+    zip.finish()?.flush()?;
+
+    Ok(())
+}
+
+fn write_records<W: Write + Seek, T: Serialize>(
+    zip: &mut ZipWriter<W>,
+    options: SimpleFileOptions,
+    name: &str,
+    records: &[T],
+) -> Result<(), anyhow::Error> {
+    zip.start_file(name, options)?;
+    for record in records {
+        serde_json::to_writer(&mut *zip, record)?;
+        zip.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+fn read_archive(path: &Path) -> Result<(Manifest, BackupData), anyhow::Error> {
+    let mut zip = ZipArchive::new(BufReader::new(File::open(path)?))
+        .context("The file isn't a backup archive")?;
+
+    let manifest: Manifest = serde_json::from_reader(
+        zip.by_name(MANIFEST_FILE)
+            .context("The archive has no manifest")?,
+    )
+    .context("The manifest isn't valid")?;
+    check_manifest(&manifest)?;
+
+    let mut backup = BackupData {
+        users: read_records(&mut zip, USERS_FILE, manifest.users)?,
+        sessions: read_records(&mut zip, SESSIONS_FILE, manifest.sessions)?,
+        documents: read_records(&mut zip, DOCUMENTS_FILE, manifest.documents)?,
+        ..BackupData::default()
+    };
+    if manifest.version >= 2 {
+        backup.preferences = read_records(&mut zip, PREFERENCES_FILE, manifest.preferences)?;
+        backup.totp = read_records(&mut zip, TOTP_FILE, manifest.totp)?;
+        backup.recovery_codes =
+            read_records(&mut zip, RECOVERY_CODES_FILE, manifest.recovery_codes)?;
+        backup.organisations = read_records(&mut zip, ORGANISATIONS_FILE, manifest.organisations)?;
+        backup.organisation_members = read_records(
+            &mut zip,
+            ORGANISATION_MEMBERS_FILE,
+            manifest.organisation_members,
+        )?;
+        backup.organisation_invites = read_records(
+            &mut zip,
+            ORGANISATION_INVITES_FILE,
+            manifest.organisation_invites,
+        )?;
+    }
+
+    Ok((manifest, backup))
+}
+
+// The archive has to be one this version can read, taken at a schema it knows
+fn check_manifest(manifest: &Manifest) -> Result<(), anyhow::Error> {
+    if manifest.format != BACKUP_FORMAT {
+        bail!("The archive isn't a markdown-edit backup");
+    }
+    if !(1..=BACKUP_VERSION).contains(&manifest.version) {
+        bail!(
+            "The backup is in format version {}, this version of markdown-edit reads up to version {}",
+            manifest.version,
+            BACKUP_VERSION
+        );
+    }
+
+    let latest = latest_schema_version();
+    if manifest.schema_version > latest {
+        bail!(
+            "The backup was taken at schema version {}, newer than the {} this version of markdown-edit knows, upgrade it first",
+            manifest.schema_version,
+            latest
+        );
+    }
+    if !MIGRATOR
+        .iter()
+        .any(|migration| migration.version == manifest.schema_version)
+    {
+        bail!(
+            "The backup was taken at schema version {}, which isn't one of the migrations in this version of markdown-edit",
+            manifest.schema_version
+        );
+    }
+
+    Ok(())
+}
+
+fn read_records<R: Read + Seek, T: DeserializeOwned>(
+    zip: &mut ZipArchive<R>,
+    name: &str,
+    expected: usize,
+) -> Result<Vec<T>, anyhow::Error> {
+    let file = zip
+        .by_name(name)
+        .with_context(|| format!("The archive has no {name}"))?;
+
+    let mut records = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("Failed to read {name}"))?;
+        if line.is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .with_context(|| format!("Line {} of {name} isn't valid", index + 1))?;
+        records.push(record);
+    }
+
+    // A truncated archive would otherwise restore only part of the data
+    if records.len() != expected {
+        bail!(
+            "{name} has {} record(s), the manifest lists {expected}",
+            records.len()
+        );
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::db::{organisation_queries, test_support, two_factor_queries};
+    use crate::models::user_preferences::UserPreferences;
+
+    fn temp_archive() -> PathBuf {
+        std::env::temp_dir().join(format!("markdown-edit-backup-{}.zip", Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn backups_restore_with_their_uuids() {
+        let source = test_support::sqlite().await;
+        let user = source
+            .users()
+            .create_user(Uuid::new_v4(), "alice", "alice@example.com")
+            .await
+            .unwrap();
+        let session = source
+            .users()
+            .create_user_session(user.uuid, Duration::from_secs(60))
+            .await
+            .unwrap();
+        let document = source
+            .documents()
+            .create_document(Uuid::new_v4(), user.uuid, None, "Notes", "# Notes")
+            .await
+            .unwrap();
+        source
+            .users()
+            .update_user_preferences(&UserPreferences {
+                theme: "dark".to_string(),
+                ..UserPreferences::defaults(user.uuid)
+            })
+            .await
+            .unwrap();
+
+        let archive = temp_archive();
+        backup(&source, &archive).await.unwrap();
+        let target = test_support::sqlite().await;
+        restore(&target, &archive).await.unwrap();
+
+        let restored = target.users().fetch_user_by_uuid(user.uuid).await.unwrap();
+        assert_eq!(restored.email, user.email);
+        assert_eq!(restored.created_at, user.created_at);
+        assert!(target
+            .users()
+            .fetch_active_user_session(session.uuid)
+            .await
+            .unwrap()
+            .is_some());
+        let restored = target
+            .documents()
+            .fetch_document_by_uuid(document.uuid.unwrap(), user.uuid, None)
+            .await
+            .unwrap();
+        assert_eq!(restored.content.as_deref(), Some("# Notes"));
+        let preferences = target
+            .users()
+            .fetch_user_preferences(user.uuid)
+            .await
+            .unwrap();
+        assert_eq!(preferences.theme, "dark");
+
+        // Restoring twice would duplicate everything, so only an empty database is accepted
+        assert!(restore(&target, &archive).await.is_err());
+
+        fs::remove_file(archive).unwrap();
+    }
+
+    #[tokio::test]
+    async fn postgres_backups_keep_organisations_and_two_factor_settings() {
+        let Some(source) = test_support::PostgresSchema::create().await else {
+            return;
+        };
+        let Some(target) = test_support::PostgresSchema::create().await else {
+            return;
+        };
+        let pool = source.database.postgres().unwrap();
+        let user = source
+            .database
+            .users()
+            .create_user(Uuid::new_v4(), "alice", "alice@example.com")
+            .await
+            .unwrap();
+        let organisation =
+            organisation_queries::create_organisation(pool, Uuid::new_v4(), "Acme", user.uuid)
+                .await
+                .unwrap();
+        organisation_queries::create_invite(
+            pool,
+            organisation.uuid,
+            "bob@example.com",
+            "member",
+            user.uuid,
+            Duration::from_secs(60),
+        )
+        .await
+        .unwrap();
+        let document = source
+            .database
+            .documents()
+            .create_document(
+                Uuid::new_v4(),
+                user.uuid,
+                Some(organisation.uuid),
+                "Roadmap",
+                "# Plans",
+            )
+            .await
+            .unwrap();
+        two_factor_queries::upsert_pending_user_totp(pool, user.uuid, "SECRET")
+            .await
+            .unwrap();
+        two_factor_queries::enable_user_totp(pool, user.uuid, 1, &["hash".to_string()])
+            .await
+            .unwrap();
+
+        let archive = temp_archive();
+        backup(&source.database, &archive).await.unwrap();
+        restore(&target.database, &archive).await.unwrap();
+
+        let pool = target.database.postgres().unwrap();
+        let totp = two_factor_queries::fetch_user_totp(pool, user.uuid)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(totp.secret, "SECRET");
+        assert!(totp.enabled);
+        assert_eq!(
+            two_factor_queries::count_unused_recovery_codes(pool, user.uuid)
+                .await
+                .unwrap(),
+            1
+        );
+        let members = organisation_queries::fetch_members(pool, organisation.uuid)
+            .await
+            .unwrap();
+        assert_eq!(members.len(), 1);
+        let invites = organisation_queries::fetch_pending_invites(pool, organisation.uuid)
+            .await
+            .unwrap();
+        assert_eq!(invites[0].email, "bob@example.com");
+        let restored = target
+            .database
+            .documents()
+            .fetch_document_by_uuid(document.uuid.unwrap(), user.uuid, Some(organisation.uuid))
+            .await
+            .unwrap();
+        assert_eq!(restored.content.as_deref(), Some("# Plans"));
+
+        // SQLite has nowhere to put them
+        assert!(restore(&test_support::sqlite().await, &archive)
+            .await
+            .is_err());
+
+        fs::remove_file(archive).unwrap();
+        source.drop().await;
+        target.drop().await;
+    }
+
+    #[test]
+    fn manifests_from_a_newer_schema_are_rejected() {
+        let manifest = |schema_version| Manifest {
+            format: BACKUP_FORMAT.to_string(),
+            version: BACKUP_VERSION,
+            schema_version,
+            created_at: Utc::now(),
+            users: 0,
+            preferences: 0,
+            sessions: 0,
+            totp: 0,
+            recovery_codes: 0,
+            organisations: 0,
+            organisation_members: 0,
+            organisation_invites: 0,
+            documents: 0,
+        };
+
+        assert!(check_manifest(&manifest(latest_schema_version())).is_ok());
+        assert!(check_manifest(&manifest(latest_schema_version() + 1)).is_err());
+        assert!(check_manifest(&manifest(0)).is_err());
+
+        // Older formats still restore, newer ones don't
+        let older = Manifest {
+            version: 1,
+            ..manifest(latest_schema_version())
+        };
+        assert!(check_manifest(&older).is_ok());
+        let newer = Manifest {
+            version: BACKUP_VERSION + 1,
+            ..manifest(latest_schema_version())
+        };
+        assert!(check_manifest(&newer).is_err());
+    }
+}
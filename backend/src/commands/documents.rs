@@ -0,0 +1,200 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::find_user;
+use crate::db::connection::Database;
+use crate::utils::constants::{DOCUMENT_CONTENT_MAX_BYTES, DOCUMENT_TITLE_MAX_LENGTH};
+
+// Bumped whenever the file format changes in a way older versions can't read
+const DOCUMENT_EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Subcommand)]
+pub enum DocCommand {
+    /// Write a user's personal documents to a JSON file
+    Export {
+        /// Email address or uuid of the account
+        #[arg(long)]
+        user: String,
+        /// File to write to [default: standard output]
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Add the documents from an exported JSON file to an account
+    Import {
+        /// File written by `doc export`
+        file: PathBuf,
+        /// Email address or uuid of the account [default: the account the file was exported from]
+        #[arg(long)]
+        user: Option<String>,
+        /// Give the documents new uuids, to copy them next to the ones they were exported from
+        #[arg(long)]
+        new_ids: bool,
+    },
+}
+
+// What `doc export` writes. Documents keep their uuid, so importing the same file twice
+// doesn't duplicate anything
+#[derive(Debug, Serialize, Deserialize)]
+struct DocumentExport {
+    version: u32,
+    exported_at: DateTime<Utc>,
+    // The account the documents came from
+    email: String,
+    documents: Vec<ExportedDocument>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedDocument {
+    uuid: Uuid,
+    title: String,
+    content: String,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+pub async fn run(command: DocCommand, database: &Database) -> Result<(), anyhow::Error> {
+    match command {
+        DocCommand::Export { user, output } => export(database, &user, output).await,
+        DocCommand::Import {
+            file,
+            user,
+            new_ids,
+        } => import(database, file, user, new_ids).await,
+    }
+}
+
+async fn export(
+    database: &Database,
+    user: &str,
+    output: Option<PathBuf>,
+) -> Result<(), anyhow::Error> {
+    let user = find_user(database.users().as_ref(), user).await?;
+    let documents = database
+        .documents()
+        .fetch_all_documents_for_user(user.uuid, None)
+        .await
+        .context("Failed to read the documents")?;
+
+    let export = DocumentExport {
+        version: DOCUMENT_EXPORT_VERSION,
+        exported_at: Utc::now(),
+        email: user.email.clone(),
+        documents: documents
+            .into_iter()
+            .filter_map(|document| {
+                Some(ExportedDocument {
+                    uuid: document.uuid?,
+                    title: document.title.unwrap_or_default(),
+                    content: document.content.unwrap_or_default(),
+                    created_at: document.created_at,
+                    updated_at: document.updated_at,
+                })
+            })
+            .collect(),
+    };
+    let json = serde_json::to_string_pretty(&export)?;
+
+    match output {
+        Some(path) => {
+            fs::write(&path, json)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!(
+                "Exported {} document(s) of {} to {}",
+                export.documents.len(),
+                user.email,
+                path.display()
+            );
+        }
+        // Written rather than printed, so a closed pipe is an error instead of a panic
+        None => writeln!(io::stdout().lock(), "{json}").context("Failed to write the export")?,
+    }
+
+    Ok(())
+}
+
+async fn import(
+    database: &Database,
+    file: PathBuf,
+    user: Option<String>,
+    new_ids: bool,
+) -> Result<(), anyhow::Error> {
+    let contents =
+        fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let export: DocumentExport = serde_json::from_str(&contents)
+        .with_context(|| format!("{} isn't a document export", file.display()))?;
+    if export.version != DOCUMENT_EXPORT_VERSION {
+        bail!(
+            "{} is in export format version {}, this version of markdown-edit reads version {}",
+            file.display(),
+            export.version,
+            DOCUMENT_EXPORT_VERSION
+        );
+    }
+
+    // Check every document before importing any, so a bad file doesn't leave half of it behind
+    for document in &export.documents {
+        if document.title.is_empty()
+            || document.title.chars().count() as u64 > DOCUMENT_TITLE_MAX_LENGTH
+        {
+            bail!(
+                "Document {} needs a title of 1 to {} characters",
+                document.uuid,
+                DOCUMENT_TITLE_MAX_LENGTH
+            );
+        }
+        if document.content.len() > DOCUMENT_CONTENT_MAX_BYTES {
+            bail!(
+                "Document {} is larger than {} bytes",
+                document.uuid,
+                DOCUMENT_CONTENT_MAX_BYTES
+            );
+        }
+    }
+
+    let user = find_user(
+        database.users().as_ref(),
+        user.as_deref().unwrap_or(&export.email),
+    )
+    .await?;
+    let documents = database.documents();
+
+    let mut imported = 0;
+    let mut skipped = 0;
+    for document in &export.documents {
+        let uuid = match new_ids {
+            true => Uuid::new_v4(),
+            false => document.uuid,
+        };
+        let result = documents
+            .create_document(uuid, user.uuid, None, &document.title, &document.content)
+            .await;
+
+        match result {
+            Ok(_) => imported += 1,
+            // Imported before, or the uuid is taken by another document
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => skipped += 1,
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!(
+                        "Failed to import document {} after importing {imported}",
+                        document.uuid
+                    )
+                })
+            }
+        }
+    }
+
+    println!(
+        "Imported {imported} document(s) for {}, skipped {skipped} that already exist",
+        user.email
+    );
+
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use clap::Subcommand;
+use uuid::Uuid;
+
+use crate::config::DatabaseConfig;
+use crate::db::connection::Database;
+use crate::db::repository::UserRepository;
+use crate::models::user::User;
+
+mod backup;
+mod documents;
+mod sessions;
+mod users;
+
+// Administration commands, run as `markdown-edit <command>` with the same database settings
+// as the server. They print what they did and exit non-zero when something fails
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Apply the database migrations this binary ships with
+    Migrate,
+    /// Write everything but the audit log to a compressed archive
+    Backup {
+        /// Archive to write, e.g. markdown-edit-backup.zip
+        output: PathBuf,
+    },
+    /// Load an archive written by `backup` into an empty database
+    Restore {
+        /// Archive written by `backup`
+        file: PathBuf,
+    },
+    /// List, disable and delete user accounts
+    #[command(subcommand)]
+    User(users::UserCommand),
+    /// Remove sign in sessions
+    #[command(subcommand)]
+    Session(sessions::SessionCommand),
+    /// Export and import a user's documents
+    #[command(subcommand)]
+    Doc(documents::DocCommand),
+}
+
+pub async fn run(command: Command, config: &DatabaseConfig) -> Result<(), anyhow::Error> {
+    let database = Database::open(config)
+        .await
+        .context("Failed to connect to the database")?;
+
+    let result = run_command(command, &database).await;
+    database.close().await;
+
+    result
+}
+
+async fn run_command(command: Command, database: &Database) -> Result<(), anyhow::Error> {
+    if !matches!(command, Command::Migrate) {
+        check_schema(database).await?;
+    }
+
+    match command {
+        Command::Migrate => migrate(database).await,
+        Command::Backup { output } => backup::backup(database, &output).await,
+        Command::Restore { file } => backup::restore(database, &file).await,
+        Command::User(command) => users::run(command, database).await,
+        Command::Session(command) => sessions::run(command, database).await,
+        Command::Doc(command) => documents::run(command, database).await,
+    }
+}
+
+async fn migrate(database: &Database) -> Result<(), anyhow::Error> {
+    // A database that was never migrated has no migrations table yet
+    let pending = match database.pending_migrations().await {
+        Ok(pending) => pending,
+        Err(_) => database
+            .migrator()
+            .iter()
+            .map(|migration| migration.version)
+            .collect(),
+    };
+
+    database
+        .migrate()
+        .await
+        .context("Failed to apply the migrations")?;
+
+    if pending.is_empty() {
+        println!("The database is up to date");
+    }
+    for migration in database.migrator().iter() {
+        if pending.contains(&migration.version) {
+            println!(
+                "Applied migration {} {}",
+                migration.version, migration.description
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// The server migrates on start, but commands don't, so they refuse to run against a schema
+// they don't know
+async fn check_schema(database: &Database) -> Result<(), anyhow::Error> {
+    let pending = database
+        .pending_migrations()
+        .await
+        .context("The database hasn't been set up, run `markdown-edit migrate` first")?;
+
+    if !pending.is_empty() {
+        bail!(
+            "The database is missing {} migration(s), run `markdown-edit migrate` first",
+            pending.len()
+        );
+    }
+
+    Ok(())
+}
+
+// Finds a user by their email address or uuid
+async fn find_user(users: &dyn UserRepository, user: &str) -> Result<User, anyhow::Error> {
+    let result = match Uuid::parse_str(user) {
+        Ok(uuid) => users.fetch_user_by_uuid(uuid).await,
+        Err(_) => users.fetch_user_by_email(user).await,
+    };
+
+    match result {
+        Ok(user) => Ok(user),
+        Err(sqlx::Error::RowNotFound) => bail!("There is no user with the email or uuid {user}"),
+        Err(err) => Err(err).context("Failed to look up the user"),
+    }
+}
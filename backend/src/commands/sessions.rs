@@ -0,0 +1,53 @@
+use anyhow::Context;
+use clap::Subcommand;
+use serde_json::json;
+
+use crate::commands::find_user;
+use crate::db::connection::Database;
+use crate::models::audit_event::AuditEventType;
+use crate::utils::audit::{self, AuditContext, AuditRecord};
+
+#[derive(Debug, Subcommand)]
+pub enum SessionCommand {
+    /// Delete expired sessions, or every session of one account with --user
+    Purge {
+        /// Email address or uuid of an account to sign out everywhere
+        #[arg(long)]
+        user: Option<String>,
+    },
+}
+
+pub async fn run(command: SessionCommand, database: &Database) -> Result<(), anyhow::Error> {
+    let users = database.users();
+
+    match command {
+        SessionCommand::Purge { user: None } => {
+            let deleted = users
+                .delete_expired_sessions()
+                .await
+                .context("Failed to delete the expired sessions")?;
+
+            println!("Deleted {deleted} expired session(s)");
+        }
+        SessionCommand::Purge { user: Some(user) } => {
+            let user = find_user(users.as_ref(), &user).await?;
+            let deleted = users
+                .delete_user_sessions(user.uuid)
+                .await
+                .context("Failed to delete the user's sessions")?;
+
+            audit::record(
+                database.postgres(),
+                &AuditContext::default(),
+                AuditRecord::new(AuditEventType::AdminSessionsRevoked, None)
+                    .target("user", user.uuid)
+                    .metadata(json!({ "source": "cli", "sessions_deleted": deleted })),
+            )
+            .await;
+
+            println!("Deleted {deleted} session(s) of {}", user.email);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use anyhow::{bail, Context};
+use clap::Subcommand;
+use serde_json::json;
+
+use crate::commands::find_user;
+use crate::db::connection::Database;
+use crate::models::audit_event::AuditEventType;
+use crate::utils::audit::{self, AuditContext, AuditRecord};
+
+#[derive(Debug, Subcommand)]
+pub enum UserCommand {
+    /// List every account with its document and session counts
+    List,
+    /// Block an account from signing in and end its sessions
+    Disable {
+        /// Email address or uuid of the account
+        user: String,
+    },
+    /// Let a disabled account sign in again
+    Enable {
+        /// Email address or uuid of the account
+        user: String,
+    },
+    /// Delete an account along with its documents
+    Delete {
+        /// Email address or uuid of the account
+        user: String,
+        /// Confirm the deletion, it can't be undone
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+pub async fn run(command: UserCommand, database: &Database) -> Result<(), anyhow::Error> {
+    let users = database.users();
+    // Changes made here show up in the audit log without anyone behind them
+    let audit_context = AuditContext::default();
+    let source = json!({ "source": "cli" });
+
+    match command {
+        UserCommand::List => {
+            let overviews = users
+                .fetch_user_overviews()
+                .await
+                .context("Failed to list the users")?;
+
+            println!(
+                "{:<36}  {:<32}  {:<6}  {:<8}  {:>9}  {:>8}",
+                "UUID", "EMAIL", "ROLE", "STATUS", "DOCUMENTS", "SESSIONS"
+            );
+            for user in overviews {
+                let status = match user.disabled_at {
+                    Some(_) => "disabled",
+                    None => "active",
+                };
+                println!(
+                    "{:<36}  {:<32}  {:<6}  {:<8}  {:>9}  {:>8}",
+                    user.uuid,
+                    user.email,
+                    user.role,
+                    status,
+                    user.document_count,
+                    user.active_sessions
+                );
+            }
+        }
+        UserCommand::Disable { user } => {
+            let user = find_user(users.as_ref(), &user).await?;
+
+            users
+                .set_user_disabled(user.uuid, true)
+                .await
+                .context("Failed to disable the user")?;
+            let sessions_deleted = users
+                .delete_user_sessions(user.uuid)
+                .await
+                .context("Failed to end the user's sessions")?;
+
+            audit::record(
+                database.postgres(),
+                &audit_context,
+                AuditRecord::new(AuditEventType::AdminUserDisabled, None)
+                    .target("user", user.uuid)
+                    .metadata(json!({ "source": "cli", "sessions_deleted": sessions_deleted })),
+            )
+            .await;
+
+            println!(
+                "Disabled {} and ended {sessions_deleted} session(s)",
+                user.email
+            );
+        }
+        UserCommand::Enable { user } => {
+            let user = find_user(users.as_ref(), &user).await?;
+
+            users
+                .set_user_disabled(user.uuid, false)
+                .await
+                .context("Failed to enable the user")?;
+
+            audit::record(
+                database.postgres(),
+                &audit_context,
+                AuditRecord::new(AuditEventType::AdminUserEnabled, None)
+                    .target("user", user.uuid)
+                    .metadata(source),
+            )
+            .await;
+
+            println!("Enabled {}", user.email);
+        }
+        UserCommand::Delete { user, yes } => {
+            let user = find_user(users.as_ref(), &user).await?;
+            if !yes {
+                bail!(
+                    "Deleting {} can't be undone, run the command again with --yes to go ahead",
+                    user.email
+                );
+            }
+
+            users
+                .delete_user(user.uuid)
+                .await
+                .context("Failed to delete the user")?;
+
+            audit::record(
+                database.postgres(),
+                &audit_context,
+                AuditRecord::new(AuditEventType::AccountDeleted, None)
+                    .target("user", user.uuid)
+                    .metadata(source),
+            )
+            .await;
+
+            println!("Deleted {}", user.email);
+        }
+    }
+
+    Ok(())
+}
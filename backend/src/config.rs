@@ -0,0 +1,659 @@
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use clap::{Parser, ValueEnum};
+use http::HeaderValue;
+use serde::Deserialize;
+use tracing::Level;
+
+use crate::commands::Command;
+use crate::utils::constants::{
+    DEFAULT_ACCOUNT_DELETION_GRACE_DAYS, DEFAULT_BIND_ADDRESS, DEFAULT_CONFIG_FILE,
+    DEFAULT_DATABASE_MAX_CONNECTIONS, DEFAULT_PORT, DEFAULT_QUOTA_MAX_DOCUMENTS,
+    DEFAULT_QUOTA_MAX_DOCUMENT_BYTES, DEFAULT_QUOTA_MAX_STORAGE_BYTES,
+    DEFAULT_RATE_LIMIT_AUTH_PER_MINUTE, DEFAULT_RATE_LIMIT_READ_PER_MINUTE,
+    DEFAULT_RATE_LIMIT_UNAUTHENTICATED_PER_MINUTE, DEFAULT_RATE_LIMIT_WRITE_PER_MINUTE,
+    DEFAULT_SESSION_IDLE_TIMEOUT, DEFAULT_SESSION_MAX_LIFETIME, DEFAULT_SESSION_ROTATION_INTERVAL,
+    DEFAULT_SHUTDOWN_READINESS_DELAY, DEFAULT_SHUTDOWN_TIMEOUT, DEFAULT_TRACING_LEVEL,
+    MAX_ACCOUNT_DELETION_GRACE_DAYS,
+};
+use crate::utils::quota::QuotaConfig;
+use crate::utils::rate_limit::RateLimitConfig;
+use crate::utils::session::SessionConfig;
+
+// How log lines are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    // Human readable lines for development
+    #[default]
+    Pretty,
+    // One JSON object per line for log collectors
+    Json,
+}
+
+// Where users and documents are stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseBackend {
+    // Every feature is available
+    #[default]
+    Postgres,
+    // A single file, for one person self-hosting. Features that need PostgreSQL are off
+    Sqlite,
+}
+
+// Command line flags. Every flag can also be set through the environment variable next to
+// it, a flag given on the command line wins over the environment
+#[derive(Debug, Default, Parser)]
+#[command(version, about = "MarkdownEdit server")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path to a TOML config file [default: markdown-edit.toml when it exists]
+    #[arg(long, env = "CONFIG_FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Address the server listens on [default: 0.0.0.0]
+    #[arg(long, env = "BIND_ADDRESS")]
+    pub bind_address: Option<IpAddr>,
+
+    /// Port the server listens on [default: 8080]
+    #[arg(long, env = "PORT")]
+    pub port: Option<u16>,
+
+    /// Database the server stores its data in [default: postgres]
+    #[arg(long, env = "DATABASE_BACKEND", value_enum)]
+    pub database_backend: Option<DatabaseBackend>,
+
+    /// Database connection string, e.g. postgres://... or sqlite://markdown-edit.db
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    pub database_url: Option<String>,
+
+    /// Maximum number of pooled database connections [default: 5]
+    #[arg(long, env = "DATABASE_MAX_CONNECTIONS")]
+    pub database_max_connections: Option<u32>,
+
+    /// Public URL of the frontend, users are sent back here after signing in
+    #[arg(long, env = "CLIENT_URL")]
+    pub client_url: Option<String>,
+
+    /// Public URL of this server, used to build OAuth callbacks and emailed links
+    #[arg(long, env = "BASE_URL")]
+    pub base_url: Option<String>,
+
+    /// Comma separated origins allowed to make credentialed requests [default: the client URL]
+    #[arg(long, env = "CORS_ORIGINS", value_delimiter = ',')]
+    pub cors_origins: Option<Vec<String>>,
+
+    /// Directory holding the built frontend
+    #[arg(long, env = "DIST_DIR")]
+    pub dist_dir: Option<PathBuf>,
+
+    /// Take client addresses from X-Forwarded-For, only behind a proxy that sets it [default: false]
+    #[arg(long, env = "TRUST_FORWARDED_FOR")]
+    pub trust_forwarded_for: Option<bool>,
+
+    /// Seconds a session lives without any activity [default: 86400]
+    #[arg(long, env = "SESSION_IDLE_TIMEOUT_SECS")]
+    pub session_idle_timeout_secs: Option<u64>,
+
+    /// Seconds after login a session can't be extended past [default: 2592000]
+    #[arg(long, env = "SESSION_MAX_LIFETIME_SECS")]
+    pub session_max_lifetime_secs: Option<u64>,
+
+    /// Seconds a session identifier is used before it's rotated [default: 3600]
+    #[arg(long, env = "SESSION_ROTATION_INTERVAL_SECS")]
+    pub session_rotation_interval_secs: Option<u64>,
+
+    /// Seconds readiness fails before the server stops taking requests on shutdown [default: 5]
+    #[arg(long, env = "SHUTDOWN_READINESS_DELAY_SECS")]
+    pub shutdown_readiness_delay_secs: Option<u64>,
+
+    /// Seconds in-flight requests get to finish on shutdown [default: 30]
+    #[arg(long, env = "SHUTDOWN_TIMEOUT_SECS")]
+    pub shutdown_timeout_secs: Option<u64>,
+
+    /// Log level, one of TRACE, DEBUG, INFO, WARN or ERROR [default: INFO]
+    #[arg(long, env = "TRACING_LEVEL")]
+    pub tracing_level: Option<Level>,
+
+    /// Log output format [default: pretty]
+    #[arg(long, env = "LOG_FORMAT", value_enum)]
+    pub log_format: Option<LogFormat>,
+
+    /// Days a deleted account is kept so the deletion can be cancelled, 0 deletes it right away,
+    /// at most 365 [default: 0]
+    #[arg(long, env = "ACCOUNT_DELETION_GRACE_DAYS")]
+    pub account_deletion_grace_days: Option<u64>,
+
+    /// Comma separated emails of the users given the admin role, at startup or when they sign in
+    #[arg(long, env = "ADMIN_EMAILS", value_delimiter = ',')]
+    pub admin_emails: Option<Vec<String>>,
+
+    /// Most documents a user can have [default: 1000]
+    #[arg(long, env = "QUOTA_MAX_DOCUMENTS")]
+    pub quota_max_documents: Option<u64>,
+
+    /// Most bytes of content a user can store across their documents [default: 104857600]
+    #[arg(long, env = "QUOTA_MAX_STORAGE_BYTES")]
+    pub quota_max_storage_bytes: Option<u64>,
+
+    /// Largest document a user can store, in bytes [default: 1048576]
+    #[arg(long, env = "QUOTA_MAX_DOCUMENT_BYTES")]
+    pub quota_max_document_bytes: Option<u64>,
+
+    /// Sign in requests a minute from one address, 0 turns the limit off [default: 10]
+    #[arg(long, env = "RATE_LIMIT_AUTH_PER_MINUTE")]
+    pub rate_limit_auth_per_minute: Option<u32>,
+
+    /// Requests without a valid session a minute from one address, 0 turns the limit off
+    /// [default: 30]
+    #[arg(long, env = "RATE_LIMIT_UNAUTHENTICATED_PER_MINUTE")]
+    pub rate_limit_unauthenticated_per_minute: Option<u32>,
+
+    /// Read requests a minute from one user or address, 0 turns the limit off [default: 300]
+    #[arg(long, env = "RATE_LIMIT_READ_PER_MINUTE")]
+    pub rate_limit_read_per_minute: Option<u32>,
+
+    /// Write requests a minute from one user or address, 0 turns the limit off [default: 60]
+    #[arg(long, env = "RATE_LIMIT_WRITE_PER_MINUTE")]
+    pub rate_limit_write_per_minute: Option<u32>,
+
+    /// Bearer token required to scrape /metrics [default: no token]
+    #[arg(long, env = "METRICS_TOKEN", hide_env_values = true)]
+    pub metrics_token: Option<String>,
+}
+
+// The config file mirrors the flags, grouped into sections
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    #[serde(default)]
+    server: ServerSection,
+    #[serde(default)]
+    database: DatabaseSection,
+    #[serde(default)]
+    session: SessionSection,
+    #[serde(default)]
+    tracing: TracingSection,
+    #[serde(default)]
+    accounts: AccountsSection,
+    #[serde(default)]
+    quotas: QuotasSection,
+    #[serde(default)]
+    rate_limit: RateLimitSection,
+    #[serde(default)]
+    metrics: MetricsSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ServerSection {
+    bind_address: Option<IpAddr>,
+    port: Option<u16>,
+    client_url: Option<String>,
+    base_url: Option<String>,
+    cors_origins: Option<Vec<String>>,
+    dist_dir: Option<PathBuf>,
+    trust_forwarded_for: Option<bool>,
+    shutdown_readiness_delay_secs: Option<u64>,
+    shutdown_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DatabaseSection {
+    backend: Option<DatabaseBackend>,
+    url: Option<String>,
+    max_connections: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SessionSection {
+    idle_timeout_secs: Option<u64>,
+    max_lifetime_secs: Option<u64>,
+    rotation_interval_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TracingSection {
+    #[serde(default, deserialize_with = "deserialize_level")]
+    level: Option<Level>,
+    format: Option<LogFormat>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountsSection {
+    deletion_grace_days: Option<u64>,
+    admin_emails: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuotasSection {
+    max_documents: Option<u64>,
+    max_storage_bytes: Option<u64>,
+    max_document_bytes: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RateLimitSection {
+    auth_per_minute: Option<u32>,
+    unauthenticated_per_minute: Option<u32>,
+    read_per_minute: Option<u32>,
+    write_per_minute: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MetricsSection {
+    token: Option<String>,
+}
+
+// What the process was started to do
+pub enum Invocation {
+    // Serve the application, when no command is given
+    Serve(Box<Config>),
+    // Run one administration command against the database and exit
+    Admin(Command, DatabaseConfig),
+}
+
+// The settings the server runs with once flags, environment and config file are merged
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub bind_address: IpAddr,
+    pub port: u16,
+    pub database: DatabaseConfig,
+    pub client_url: String,
+    pub base_url: String,
+    pub cors_origins: Vec<HeaderValue>,
+    pub dist_dir: PathBuf,
+    // Behind a reverse proxy every request comes from the proxy, the client address is then
+    // the last one it added to X-Forwarded-For. Without a proxy the header can be forged
+    pub trust_forwarded_for: bool,
+    pub session: SessionConfig,
+    pub shutdown_readiness_delay: Duration,
+    pub shutdown_timeout: Duration,
+    pub tracing_level: Level,
+    pub log_format: LogFormat,
+    // Zero when deleted accounts go right away
+    pub account_deletion_grace_period: Duration,
+    // Users with these emails are made admins, so there is a way to bootstrap the first one
+    pub admin_emails: Vec<String>,
+    pub quotas: QuotaConfig,
+    pub rate_limit: RateLimitConfig,
+    pub metrics_token: Option<String>,
+}
+
+// The database settings on their own, which is all the administration commands need
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    pub backend: DatabaseBackend,
+    pub url: String,
+    pub max_connections: u32,
+}
+
+pub type SharedConfig = Arc<Config>;
+
+impl Invocation {
+    // Reads the command line and environment, then fills whatever they leave out from the
+    // config file and finally the defaults
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let mut cli = Cli::parse();
+
+        let file = match &cli.config {
+            Some(path) => read_file_config(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                read_file_config(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => FileConfig::default(),
+        };
+
+        // Commands don't serve anything, so they don't need the rest of the settings
+        match cli.command.take() {
+            Some(command) => Ok(Invocation::Admin(
+                command,
+                DatabaseConfig::merge(&cli, &file.database)?,
+            )),
+            None => Ok(Invocation::Serve(Box::new(Config::merge(cli, file)?))),
+        }
+    }
+}
+
+impl Config {
+    // Same as load, but with the given arguments instead of the process' own and without a
+    // config file
+    #[cfg(test)]
+    pub fn from_args(args: &[&str]) -> Result<Self, anyhow::Error> {
+        let cli =
+            Cli::try_parse_from(std::iter::once("markdown-edit").chain(args.iter().copied()))?;
+
+        Self::merge(cli, FileConfig::default())
+    }
+
+    fn merge(cli: Cli, file: FileConfig) -> Result<Self, anyhow::Error> {
+        let bind_address = match cli.bind_address.or(file.server.bind_address) {
+            Some(bind_address) => bind_address,
+            None => DEFAULT_BIND_ADDRESS.parse()?,
+        };
+
+        let database = DatabaseConfig::merge(&cli, &file.database)?;
+
+        let client_url = cli.client_url.or(file.server.client_url).context(
+            "Missing the client URL, set --client-url, CLIENT_URL or server.client_url in the config file",
+        )?;
+        let client_url = normalize_url("client URL", &client_url)?;
+
+        let base_url = cli.base_url.or(file.server.base_url).context(
+            "Missing the base URL, set --base-url, BASE_URL or server.base_url in the config file",
+        )?;
+        let base_url = normalize_url("base URL", &base_url)?;
+
+        // Without an explicit list only the frontend may call the API
+        let cors_origins = cli
+            .cors_origins
+            .or(file.server.cors_origins)
+            .unwrap_or_else(|| vec![client_url.clone()])
+            .iter()
+            .map(|origin| origin.trim())
+            .filter(|origin| !origin.is_empty())
+            .map(|origin| {
+                HeaderValue::from_str(origin.trim_end_matches('/'))
+                    .with_context(|| format!("Invalid CORS origin {origin:?}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let dist_dir = cli
+            .dist_dir
+            .or(file.server.dist_dir)
+            .unwrap_or_else(default_dist_dir);
+
+        let session = SessionConfig::new(
+            session_duration(
+                "session idle timeout",
+                cli.session_idle_timeout_secs
+                    .or(file.session.idle_timeout_secs),
+                DEFAULT_SESSION_IDLE_TIMEOUT,
+            )?,
+            session_duration(
+                "session max lifetime",
+                cli.session_max_lifetime_secs
+                    .or(file.session.max_lifetime_secs),
+                DEFAULT_SESSION_MAX_LIFETIME,
+            )?,
+            session_duration(
+                "session rotation interval",
+                cli.session_rotation_interval_secs
+                    .or(file.session.rotation_interval_secs),
+                DEFAULT_SESSION_ROTATION_INTERVAL,
+            )?,
+        )?;
+
+        let tracing_level = cli
+            .tracing_level
+            .or(file.tracing.level)
+            .unwrap_or(DEFAULT_TRACING_LEVEL);
+        let log_format = cli.log_format.or(file.tracing.format).unwrap_or_default();
+
+        let admin_emails = cli
+            .admin_emails
+            .or(file.accounts.admin_emails)
+            .unwrap_or_default()
+            .iter()
+            .map(|email| email.trim())
+            .filter(|email| !email.is_empty())
+            .map(String::from)
+            .collect();
+
+        let quotas = QuotaConfig::new(
+            cli.quota_max_documents
+                .or(file.quotas.max_documents)
+                .unwrap_or(DEFAULT_QUOTA_MAX_DOCUMENTS),
+            cli.quota_max_storage_bytes
+                .or(file.quotas.max_storage_bytes)
+                .unwrap_or(DEFAULT_QUOTA_MAX_STORAGE_BYTES),
+            cli.quota_max_document_bytes
+                .or(file.quotas.max_document_bytes)
+                .unwrap_or(DEFAULT_QUOTA_MAX_DOCUMENT_BYTES),
+        )?;
+
+        Ok(Config {
+            bind_address,
+            port: cli.port.or(file.server.port).unwrap_or(DEFAULT_PORT),
+            database,
+            client_url,
+            base_url,
+            cors_origins,
+            dist_dir,
+            trust_forwarded_for: cli
+                .trust_forwarded_for
+                .or(file.server.trust_forwarded_for)
+                .unwrap_or_default(),
+            session,
+            shutdown_readiness_delay: cli
+                .shutdown_readiness_delay_secs
+                .or(file.server.shutdown_readiness_delay_secs)
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_SHUTDOWN_READINESS_DELAY),
+            shutdown_timeout: cli
+                .shutdown_timeout_secs
+                .or(file.server.shutdown_timeout_secs)
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT),
+            tracing_level,
+            log_format,
+            account_deletion_grace_period: deletion_grace_period(
+                cli.account_deletion_grace_days
+                    .or(file.accounts.deletion_grace_days)
+                    .unwrap_or(DEFAULT_ACCOUNT_DELETION_GRACE_DAYS),
+            )?,
+            admin_emails,
+            quotas,
+            rate_limit: RateLimitConfig {
+                auth_per_minute: cli
+                    .rate_limit_auth_per_minute
+                    .or(file.rate_limit.auth_per_minute)
+                    .unwrap_or(DEFAULT_RATE_LIMIT_AUTH_PER_MINUTE),
+                unauthenticated_per_minute: cli
+                    .rate_limit_unauthenticated_per_minute
+                    .or(file.rate_limit.unauthenticated_per_minute)
+                    .unwrap_or(DEFAULT_RATE_LIMIT_UNAUTHENTICATED_PER_MINUTE),
+                read_per_minute: cli
+                    .rate_limit_read_per_minute
+                    .or(file.rate_limit.read_per_minute)
+                    .unwrap_or(DEFAULT_RATE_LIMIT_READ_PER_MINUTE),
+                write_per_minute: cli
+                    .rate_limit_write_per_minute
+                    .or(file.rate_limit.write_per_minute)
+                    .unwrap_or(DEFAULT_RATE_LIMIT_WRITE_PER_MINUTE),
+            },
+            metrics_token: cli
+                .metrics_token
+                .or(file.metrics.token)
+                .filter(|token| !token.is_empty()),
+        })
+    }
+}
+
+impl DatabaseConfig {
+    fn merge(cli: &Cli, file: &DatabaseSection) -> Result<Self, anyhow::Error> {
+        let backend = cli.database_backend.or(file.backend).unwrap_or_default();
+        let url = cli.database_url.clone().or(file.url.clone()).context(
+            "Missing the database URL, set --database-url, DATABASE_URL or database.url in the config file",
+        )?;
+        // Catch a URL meant for the other backend before trying to connect with it
+        match backend {
+            DatabaseBackend::Postgres
+                if !url.starts_with("postgres://") && !url.starts_with("postgresql://") =>
+            {
+                bail!("The PostgreSQL backend needs a postgres:// database URL")
+            }
+            DatabaseBackend::Sqlite if !url.starts_with("sqlite:") => {
+                bail!("The SQLite backend needs a sqlite: database URL")
+            }
+            _ => {}
+        }
+
+        let max_connections = cli
+            .database_max_connections
+            .or(file.max_connections)
+            .unwrap_or(DEFAULT_DATABASE_MAX_CONNECTIONS);
+        if max_connections == 0 {
+            bail!("The database pool needs at least one connection");
+        }
+
+        Ok(DatabaseConfig {
+            backend,
+            url,
+            max_connections,
+        })
+    }
+}
+
+fn read_file_config(path: &Path) -> Result<FileConfig, anyhow::Error> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the config file {}", path.display()))?;
+
+    toml::from_str(&contents).with_context(|| format!("Invalid config file {}", path.display()))
+}
+
+// Development builds run from the backend directory next to the frontend
+fn default_dist_dir() -> PathBuf {
+    if cfg!(debug_assertions) {
+        PathBuf::from("../frontend/dist/")
+    } else {
+        PathBuf::from("./dist")
+    }
+}
+
+// URLs are joined with paths later on, so they need a scheme and no trailing slash
+fn normalize_url(name: &str, url: &str) -> Result<String, anyhow::Error> {
+    let url = url.trim().trim_end_matches('/');
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        bail!("The {name} must start with http:// or https://, got {url:?}");
+    }
+
+    Ok(url.to_string())
+}
+
+fn session_duration(
+    name: &str,
+    secs: Option<u64>,
+    default: Duration,
+) -> Result<Duration, anyhow::Error> {
+    match secs {
+        Some(0) => bail!("The {name} must be greater than zero"),
+        Some(secs) => Ok(Duration::from_secs(secs)),
+        None => Ok(default),
+    }
+}
+
+fn deletion_grace_period(days: u64) -> Result<Duration, anyhow::Error> {
+    if days > MAX_ACCOUNT_DELETION_GRACE_DAYS {
+        let max_days = MAX_ACCOUNT_DELETION_GRACE_DAYS;
+        bail!("The account deletion grace period can't be more than {max_days} days");
+    }
+    let secs = days
+        .checked_mul(60 * 60 * 24)
+        .context("The account deletion grace period is too long")?;
+
+    Ok(Duration::from_secs(secs))
+}
+
+fn deserialize_level<'de, D>(deserializer: D) -> Result<Option<Level>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let level = Option::<String>::deserialize(deserializer)?;
+
+    level
+        .map(|level| level.parse::<Level>().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with(args: &[&str]) -> Result<Config, anyhow::Error> {
+        config_from(args, "")
+    }
+
+    // Like load, with the given arguments and config file contents
+    fn config_from(args: &[&str], file: &str) -> Result<Config, anyhow::Error> {
+        let cli = Cli::try_parse_from(
+            [
+                "markdown-edit",
+                "--database-url",
+                "postgres://localhost/markdown_edit",
+                "--client-url",
+                "http://localhost:5173",
+                "--base-url",
+                "http://localhost:8080",
+            ]
+            .into_iter()
+            .chain(args.iter().copied()),
+        )?;
+
+        Config::merge(cli, toml::from_str(file)?)
+    }
+
+    #[test]
+    fn deletion_grace_period_is_capped() {
+        let config = config_with(&["--account-deletion-grace-days", "30"]).unwrap();
+        assert_eq!(
+            config.account_deletion_grace_period,
+            Duration::from_secs(30 * 24 * 60 * 60)
+        );
+
+        assert!(config_with(&["--account-deletion-grace-days", "366"]).is_err());
+        assert!(config_with(&["--account-deletion-grace-days", &u64::MAX.to_string()]).is_err());
+    }
+
+    #[test]
+    fn flags_win_over_the_environment_then_the_file_then_defaults() {
+        // Only read on shutdown, so other tests parsing the environment aren't affected
+        std::env::set_var("SHUTDOWN_TIMEOUT_SECS", "20");
+        std::env::set_var("SHUTDOWN_READINESS_DELAY_SECS", "2");
+        let config = config_from(
+            &["--shutdown-timeout-secs", "30"],
+            r#"
+            [server]
+            port = 9000
+            shutdown_timeout_secs = 10
+            shutdown_readiness_delay_secs = 1
+
+            [tracing]
+            format = "json"
+            "#,
+        );
+        std::env::remove_var("SHUTDOWN_TIMEOUT_SECS");
+        std::env::remove_var("SHUTDOWN_READINESS_DELAY_SECS");
+        let config = config.unwrap();
+
+        assert_eq!(config.shutdown_timeout, Duration::from_secs(30));
+        assert_eq!(config.shutdown_readiness_delay, Duration::from_secs(2));
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(config.tracing_level, DEFAULT_TRACING_LEVEL);
+        assert_eq!(config.bind_address.to_string(), DEFAULT_BIND_ADDRESS);
+    }
+
+    #[test]
+    fn mistyped_file_keys_are_rejected() {
+        let err = config_from(&[], "[server]\nprot = 9000\n").unwrap_err();
+        assert!(err.to_string().contains("unknown field `prot`"), "{err}");
+
+        assert!(config_from(&[], "[sessions]\nidle_timeout_secs = 60\n").is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::user::User;
+use crate::models::user_overview::{StorageUsage, UserOverview};
+
+pub async fn fetch_user_overviews(pool: &PgPool) -> Result<Vec<UserOverview>, sqlx::Error> {
+    let users = sqlx::query_as!(
+        UserOverview,
+        r#"
+        SELECT
+            u.uuid, u.username, u.email, u.role, u.disabled_at, u.created_at,
+            (SELECT COUNT(*) FROM documents AS d WHERE d.user_uuid = u.uuid) AS "document_count!",
+            (
+                SELECT COALESCE(SUM(OCTET_LENGTH(d.content)), 0)::BIGINT
+                FROM documents AS d WHERE d.user_uuid = u.uuid
+            ) AS "storage_bytes!",
+            (
+                SELECT COUNT(*) FROM UserSessions AS s
+                WHERE s.user_uuid = u.uuid AND s.expires_at > NOW()
+            ) AS "active_sessions!"
+        FROM users AS u
+        ORDER BY u.created_at
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(users)
+}
+
+pub async fn fetch_storage_usage(pool: &PgPool) -> Result<StorageUsage, sqlx::Error> {
+    let storage_usage = sqlx::query_as!(
+        StorageUsage,
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM users) AS "user_count!",
+            COUNT(d.uuid) AS "document_count!",
+            COALESCE(SUM(OCTET_LENGTH(d.content)), 0)::BIGINT AS "storage_bytes!"
+        FROM documents AS d
+        "#
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(storage_usage)
+}
+
+pub async fn set_user_disabled(
+    pool: &PgPool,
+    uuid: Uuid,
+    disabled: bool,
+) -> Result<User, sqlx::Error> {
+    let user = sqlx::query_as!(
+        User,
+        "
+        UPDATE users
+        SET disabled_at = CASE WHEN $1 THEN COALESCE(disabled_at, CURRENT_TIMESTAMP) ELSE NULL END,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE uuid = $2
+        RETURNING *
+        ",
+        disabled,
+        uuid
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(user)
+}
+
+pub async fn set_user_role(pool: &PgPool, uuid: Uuid, role: &str) -> Result<User, sqlx::Error> {
+    let user = sqlx::query_as!(
+        User,
+        "
+        UPDATE users
+        SET role = $1, updated_at = CURRENT_TIMESTAMP
+        WHERE uuid = $2
+        RETURNING *
+        ",
+        role,
+        uuid
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(user)
+}
+
+// Gives the admin role to every existing user with one of the given emails
+pub async fn promote_admins(pool: &PgPool, emails: &[String]) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "
+        UPDATE users
+        SET role = 'admin', updated_at = CURRENT_TIMESTAMP
+        WHERE email = ANY($1) AND role <> 'admin'
+        ",
+        emails
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
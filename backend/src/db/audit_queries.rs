@@ -0,0 +1,74 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::audit_event::{AuditEvent, AuditEventFilter};
+use crate::utils::constants::{AUDIT_EVENTS_DEFAULT_LIMIT, AUDIT_EVENTS_MAX_LIMIT};
+
+#[allow(clippy::too_many_arguments)]
+pub async fn insert_audit_event(
+    pool: &PgPool,
+    user_uuid: Option<Uuid>,
+    event_type: &str,
+    target_type: Option<&str>,
+    target_uuid: Option<Uuid>,
+    organisation_uuid: Option<Uuid>,
+    metadata: &serde_json::Value,
+    ip_address: Option<&str>,
+    user_agent: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        INSERT INTO audit_events (
+            uuid, user_uuid, event_type, target_type, target_uuid, organisation_uuid,
+            metadata, ip_address, user_agent, created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, DEFAULT)
+        ",
+        Uuid::new_v4(),
+        user_uuid,
+        event_type,
+        target_type,
+        target_uuid,
+        organisation_uuid,
+        metadata,
+        ip_address,
+        user_agent
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Lists the newest events first, narrowed down by whichever filters are set
+pub async fn fetch_audit_events(
+    pool: &PgPool,
+    filter: &AuditEventFilter,
+) -> Result<Vec<AuditEvent>, sqlx::Error> {
+    let limit = filter
+        .limit
+        .unwrap_or(AUDIT_EVENTS_DEFAULT_LIMIT)
+        .clamp(1, AUDIT_EVENTS_MAX_LIMIT);
+
+    let events = sqlx::query_as!(
+        AuditEvent,
+        "
+        SELECT * FROM audit_events
+        WHERE ($1::uuid IS NULL OR user_uuid = $1)
+            AND ($2::text IS NULL OR event_type = $2)
+            AND ($3::timestamptz IS NULL OR created_at >= $3)
+            AND ($4::timestamptz IS NULL OR created_at < $4)
+        ORDER BY created_at DESC
+        LIMIT $5
+        ",
+        filter.user_uuid,
+        filter.event_type,
+        filter.from,
+        filter.to,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(events)
+}
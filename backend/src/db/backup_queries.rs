@@ -0,0 +1,316 @@
+use sqlx::PgPool;
+
+use crate::db::repository::BackupData;
+use crate::models::backup::{OrganisationMemberRecord, RecoveryCodeRecord, UserTotpRecord};
+use crate::models::document::Document;
+use crate::models::organisation::{Organisation, OrganisationInvite};
+use crate::models::user::User;
+use crate::models::user_preferences::UserPreferences;
+use crate::models::user_session::UserSession;
+
+// Reads everything a backup holds
+pub async fn fetch_backup(pool: &PgPool) -> Result<BackupData, sqlx::Error> {
+    // One snapshot, so a document can't show up without the user it belongs to
+    let mut tx = pool.begin().await?;
+    sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+
+    let users = sqlx::query_as!(
+        User,
+        "
+        SELECT * FROM users
+        ORDER BY created_at, uuid
+        "
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let preferences = sqlx::query_as!(
+        UserPreferences,
+        r#"
+        SELECT
+            user_uuid, theme, editor_font_size, keybinding_mode, default_export_format,
+            preview_layout, updated_at AS "updated_at?"
+        FROM UserPreferences
+        ORDER BY user_uuid
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let sessions = sqlx::query_as!(
+        UserSession,
+        "
+        SELECT * FROM UserSessions
+        ORDER BY created_at, uuid
+        "
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let totp = sqlx::query_as!(
+        UserTotpRecord,
+        "
+        SELECT * FROM UserTotp
+        ORDER BY user_uuid
+        "
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let recovery_codes = sqlx::query_as!(
+        RecoveryCodeRecord,
+        "
+        SELECT * FROM RecoveryCodes
+        ORDER BY created_at, uuid
+        "
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let organisations = sqlx::query_as!(
+        Organisation,
+        "
+        SELECT * FROM Organisations
+        ORDER BY created_at, uuid
+        "
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let organisation_members = sqlx::query_as!(
+        OrganisationMemberRecord,
+        "
+        SELECT * FROM OrganisationMembers
+        ORDER BY created_at, organisation_uuid, user_uuid
+        "
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let organisation_invites = sqlx::query_as!(
+        OrganisationInvite,
+        "
+        SELECT * FROM OrganisationInvites
+        ORDER BY created_at, uuid
+        "
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let documents = sqlx::query_as!(
+        Document,
+        "
+        SELECT * FROM documents
+        ORDER BY created_at, uuid
+        "
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(BackupData {
+        users,
+        preferences,
+        sessions,
+        totp,
+        recovery_codes,
+        organisations,
+        organisation_members,
+        organisation_invites,
+        documents,
+    })
+}
+
+pub async fn count_users(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    let count = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!" FROM users
+        "#
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
+
+// Writes a backup back with its uuids and timestamps as they were. Either all of it is
+// restored or none of it
+pub async fn restore_backup(pool: &PgPool, backup: &BackupData) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    for user in &backup.users {
+        sqlx::query!(
+            "
+            INSERT INTO users (
+                uuid, username, email, created_at, updated_at, role, disabled_at,
+                deletion_scheduled_at, display_name, avatar_url
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ",
+            user.uuid,
+            user.username,
+            user.email,
+            user.created_at,
+            user.updated_at,
+            user.role,
+            user.disabled_at,
+            user.deletion_scheduled_at,
+            user.display_name,
+            user.avatar_url
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for session in &backup.sessions {
+        sqlx::query!(
+            "
+            INSERT INTO UserSessions (uuid, user_uuid, created_at, expires_at, issued_at, replaced_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ",
+            session.uuid,
+            session.user_uuid,
+            session.created_at,
+            session.expires_at,
+            session.issued_at,
+            session.replaced_by
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for preferences in &backup.preferences {
+        sqlx::query!(
+            "
+            INSERT INTO UserPreferences (
+                user_uuid, theme, editor_font_size, keybinding_mode, default_export_format,
+                preview_layout, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, COALESCE($7, NOW()))
+            ",
+            preferences.user_uuid,
+            preferences.theme,
+            preferences.editor_font_size,
+            preferences.keybinding_mode,
+            preferences.default_export_format,
+            preferences.preview_layout,
+            preferences.updated_at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for totp in &backup.totp {
+        sqlx::query!(
+            "
+            INSERT INTO UserTotp (user_uuid, secret, enabled, last_used_step, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ",
+            totp.user_uuid,
+            totp.secret,
+            totp.enabled,
+            totp.last_used_step,
+            totp.created_at,
+            totp.updated_at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for code in &backup.recovery_codes {
+        sqlx::query!(
+            "
+            INSERT INTO RecoveryCodes (uuid, user_uuid, code_hash, used_at, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ",
+            code.uuid,
+            code.user_uuid,
+            code.code_hash,
+            code.used_at,
+            code.created_at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for organisation in &backup.organisations {
+        sqlx::query!(
+            "
+            INSERT INTO Organisations (uuid, name, created_by, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ",
+            organisation.uuid,
+            organisation.name,
+            organisation.created_by,
+            organisation.created_at,
+            organisation.updated_at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for member in &backup.organisation_members {
+        sqlx::query!(
+            "
+            INSERT INTO OrganisationMembers (organisation_uuid, user_uuid, role, created_at)
+            VALUES ($1, $2, $3, $4)
+            ",
+            member.organisation_uuid,
+            member.user_uuid,
+            member.role,
+            member.created_at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for invite in &backup.organisation_invites {
+        sqlx::query!(
+            "
+            INSERT INTO OrganisationInvites (
+                uuid, organisation_uuid, email, role, invited_by, created_at, expires_at,
+                accepted_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ",
+            invite.uuid,
+            invite.organisation_uuid,
+            invite.email,
+            invite.role,
+            invite.invited_by,
+            invite.created_at,
+            invite.expires_at,
+            invite.accepted_at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // Last, since organisation documents need their organisation
+    for document in &backup.documents {
+        sqlx::query!(
+            "
+            INSERT INTO documents (
+                uuid, user_uuid, organisation_uuid, title, content, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ",
+            document.uuid,
+            document.user_uuid,
+            document.organisation_uuid,
+            document.title,
+            document.content,
+            document.created_at,
+            document.updated_at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
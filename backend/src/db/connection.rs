@@ -1,22 +1,168 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use sqlx::migrate::Migrator;
 use sqlx::postgres::PgPoolOptions;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use sqlx::{PgPool, SqlitePool};
+
+use crate::config::{DatabaseBackend, DatabaseConfig};
+use crate::db::postgres::PgRepository;
+use crate::db::repository::{
+    SharedBackupRepository, SharedDocumentRepository, SharedExportRepository, SharedUserRepository,
+};
+use crate::db::sqlite::SqliteRepository;
+
+// Every migration shipped with this binary
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+// The SQLite schema is its own, so it has its own migrations
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations_sqlite");
+
+// The database the server runs on. Users and documents work on both, everything else
+// (organisations, two-factor authentication, magic links, administration and the audit log)
+// needs PostgreSQL
+#[derive(Debug, Clone)]
+pub enum Database {
+    Postgres(PgPool),
+    Sqlite(SqlitePool),
+}
+
+impl Database {
+    // Connects to the database and brings its schema up to date
+    pub async fn connect(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        let database = Self::open(config).await?;
+        database.migrate().await?;
 
-pub async fn connect(db_url: &str) -> Result<sqlx::PgPool, sqlx::Error> {
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(db_url)
-        .await?;
+        Ok(database)
+    }
+
+    // Connects without touching the schema, for the administration commands
+    pub async fn open(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        match config.backend {
+            DatabaseBackend::Postgres => {
+                let pool = PgPoolOptions::new()
+                    .max_connections(config.max_connections)
+                    .connect(&config.url)
+                    .await?;
+
+                Ok(Database::Postgres(pool))
+            }
+            DatabaseBackend::Sqlite => {
+                // The file is created on first start, WAL lets reads carry on during a write
+                let options = SqliteConnectOptions::from_str(&config.url)?
+                    .create_if_missing(true)
+                    .journal_mode(SqliteJournalMode::Wal);
+                let pool = SqlitePoolOptions::new()
+                    .max_connections(config.max_connections)
+                    .connect_with(options)
+                    .await?;
+
+                Ok(Database::Sqlite(pool))
+            }
+        }
+    }
+
+    // Applies the migrations shipped with this binary that the database doesn't have yet
+    pub async fn migrate(&self) -> Result<(), sqlx::migrate::MigrateError> {
+        match self {
+            Database::Postgres(pool) => MIGRATOR.run(pool).await,
+            Database::Sqlite(pool) => SQLITE_MIGRATOR.run(pool).await,
+        }
+    }
+
+    // Every migration shipped with this binary for this backend
+    pub fn migrator(&self) -> &'static Migrator {
+        match self {
+            Database::Postgres(_) => &MIGRATOR,
+            Database::Sqlite(_) => &SQLITE_MIGRATOR,
+        }
+    }
+
+    // The PostgreSQL pool, for the features that only exist on PostgreSQL
+    pub fn postgres(&self) -> Option<&PgPool> {
+        match self {
+            Database::Postgres(pool) => Some(pool),
+            Database::Sqlite(_) => None,
+        }
+    }
 
-    match {
-        let pool = &pool;
-        async move {
-            let mut conn = pool.acquire().await?;
-            sqlx::migrate!("./migrations").run(&mut *conn).await?;
-            Ok(())
+    pub fn users(&self) -> SharedUserRepository {
+        match self {
+            Database::Postgres(pool) => Arc::new(PgRepository::new(pool.clone())),
+            Database::Sqlite(pool) => Arc::new(SqliteRepository::new(pool.clone())),
         }
     }
-    .await
-    {
-        Ok(_) => Ok(pool),
-        Err(err) => Err(err),
+
+    pub fn documents(&self) -> SharedDocumentRepository {
+        match self {
+            Database::Postgres(pool) => Arc::new(PgRepository::new(pool.clone())),
+            Database::Sqlite(pool) => Arc::new(SqliteRepository::new(pool.clone())),
+        }
+    }
+
+    pub fn backups(&self) -> SharedBackupRepository {
+        match self {
+            Database::Postgres(pool) => Arc::new(PgRepository::new(pool.clone())),
+            Database::Sqlite(pool) => Arc::new(SqliteRepository::new(pool.clone())),
+        }
+    }
+
+    pub fn exports(&self) -> SharedExportRepository {
+        match self {
+            Database::Postgres(pool) => Arc::new(PgRepository::new(pool.clone())),
+            Database::Sqlite(pool) => Arc::new(SqliteRepository::new(pool.clone())),
+        }
+    }
+
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        match self {
+            Database::Postgres(pool) => sqlx::query("SELECT 1").execute(pool).await.map(drop),
+            Database::Sqlite(pool) => sqlx::query("SELECT 1").execute(pool).await.map(drop),
+        }
+    }
+
+    // Returns the versions of the migrations shipped with this binary that haven't been
+    // applied to the database yet
+    pub async fn pending_migrations(&self) -> Result<Vec<i64>, sqlx::Error> {
+        // The table belongs to sqlx rather than our schema, so this query isn't checked at
+        // compile time
+        let query = "
+            SELECT version FROM _sqlx_migrations
+            WHERE success
+            ";
+        let applied: Vec<i64> = match self {
+            Database::Postgres(pool) => sqlx::query_scalar(query).fetch_all(pool).await?,
+            Database::Sqlite(pool) => sqlx::query_scalar(query).fetch_all(pool).await?,
+        };
+
+        Ok(self
+            .migrator()
+            .iter()
+            .map(|migration| migration.version)
+            .filter(|version| !applied.contains(version))
+            .collect())
+    }
+
+    // Pool sizes for the metrics endpoint: open connections, idle ones and the maximum
+    pub fn pool_status(&self) -> (u32, usize, u32) {
+        match self {
+            Database::Postgres(pool) => (
+                pool.size(),
+                pool.num_idle(),
+                pool.options().get_max_connections(),
+            ),
+            Database::Sqlite(pool) => (
+                pool.size(),
+                pool.num_idle(),
+                pool.options().get_max_connections(),
+            ),
+        }
+    }
+
+    pub async fn close(&self) {
+        match self {
+            Database::Postgres(pool) => pool.close().await,
+            Database::Sqlite(pool) => pool.close().await,
+        }
     }
 }
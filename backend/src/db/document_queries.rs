@@ -1,12 +1,19 @@
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use uuid::Uuid;
 
 use crate::models::document::Document;
+use crate::models::user_usage::UserUsage;
+use crate::utils::quota::{QuotaConfig, QuotaError};
+
+// Every query is scoped to a workspace. Without an organisation_uuid that is the user's
+// personal documents, with one it is the organisation's documents. Callers must check that
+// the user is a member of the organisation first
 
 pub async fn fetch_document_by_uuid(
-    pool: &PgPool,
+    executor: impl PgExecutor<'_>,
     uuid: Uuid,
     user_uuid: Uuid,
+    organisation_uuid: Option<Uuid>,
 ) -> Result<Document, sqlx::Error> {
     let document = sqlx::query_as!(
         Document,
@@ -14,12 +21,16 @@ pub async fn fetch_document_by_uuid(
         SELECT d.*
         FROM documents AS d
         LEFT JOIN users AS u ON d.user_uuid = u.uuid
-        WHERE d.uuid = $1 AND d.user_uuid = $2
+        WHERE d.uuid = $1 AND (
+            ($3::uuid IS NULL AND d.organisation_uuid IS NULL AND d.user_uuid = $2)
+            OR d.organisation_uuid = $3
+        )
         ",
         uuid,
-        user_uuid
+        user_uuid,
+        organisation_uuid
     )
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await?;
 
     Ok(document)
@@ -28,105 +39,225 @@ pub async fn fetch_document_by_uuid(
 pub async fn fetch_all_documents_for_user(
     pool: &PgPool,
     user_uuid: Uuid,
+    organisation_uuid: Option<Uuid>,
 ) -> Result<Vec<Document>, sqlx::Error> {
     let documents = sqlx::query_as!(
         Document,
         "
-        SELECT d.uuid, d.user_uuid, d.title, d.content, d.created_at, d.updated_at
+        SELECT d.uuid, d.user_uuid, d.title, d.content, d.created_at, d.updated_at,
+            d.organisation_uuid
         FROM documents AS d
         LEFT JOIN users AS u ON d.user_uuid = u.uuid
-        WHERE d.user_uuid = $1
+        WHERE ($2::uuid IS NULL AND d.organisation_uuid IS NULL AND d.user_uuid = $1)
+            OR d.organisation_uuid = $2
         ",
-        user_uuid
+        user_uuid,
+        organisation_uuid
     )
     .fetch_all(pool)
     .await?;
 
-    let mut result = Vec::new();
-    for document in documents {
-        result.push(Document {
-            uuid: document.uuid,
-            user_uuid: document.user_uuid,
-            title: document.title,
-            content: document.content,
-            created_at: Some(document.created_at.unwrap().to_string()),
-            updated_at: Some(document.updated_at.unwrap().to_string()),
-        });
-    }
+    Ok(documents)
+}
+
+pub async fn fetch_documents_by_author(
+    pool: &PgPool,
+    user_uuid: Uuid,
+) -> Result<Vec<Document>, sqlx::Error> {
+    let documents = sqlx::query_as!(
+        Document,
+        "
+        SELECT uuid, user_uuid, title, content, created_at, updated_at, organisation_uuid
+        FROM documents
+        WHERE user_uuid = $1
+        ORDER BY created_at
+        ",
+        user_uuid
+    )
+    .fetch_all(pool)
+    .await?;
 
-    Ok(result)
+    Ok(documents)
 }
 
 pub async fn create_document(
-    pool: &PgPool,
+    executor: impl PgExecutor<'_>,
     uuid: Uuid,
     user_uuid: Uuid,
+    organisation_uuid: Option<Uuid>,
     title: &str,
     content: &str,
 ) -> Result<Document, sqlx::Error> {
     let document = sqlx::query_as!(
         Document,
         "
-        INSERT INTO documents (uuid, user_uuid, title, content, created_at, updated_at)
-        VALUES ($1, $2, $3, $4, DEFAULT, DEFAULT)
+        INSERT INTO documents
+            (uuid, user_uuid, organisation_uuid, title, content, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, DEFAULT, DEFAULT)
         RETURNING *
         ",
         uuid,
         user_uuid,
+        organisation_uuid,
         title,
         content
     )
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await?;
 
     Ok(document)
 }
 
 pub async fn update_document(
-    pool: &PgPool,
+    executor: impl PgExecutor<'_>,
     uuid: Uuid,
     user_uuid: Uuid,
+    organisation_uuid: Option<Uuid>,
     title: &str,
     content: &str,
-    updated_at: &str,
 ) -> Result<Document, sqlx::Error> {
     let document = sqlx::query_as!(
         Document,
         "
         UPDATE documents
-        SET title = $1, content = $2, updated_at = $3
-        WHERE uuid = $4 AND user_uuid = $5
+        SET title = $1, content = $2, updated_at = CURRENT_TIMESTAMP
+        WHERE uuid = $3 AND (
+            ($5::uuid IS NULL AND organisation_uuid IS NULL AND user_uuid = $4)
+            OR organisation_uuid = $5
+        )
         RETURNING *
         ",
         title,
         content,
-        updated_at,
         uuid,
-        user_uuid
+        user_uuid,
+        organisation_uuid
     )
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await?;
 
     Ok(document)
 }
 
+// The author's row stays locked until the transaction ends, so their other quota checked
+// writes wait for this one instead of counting the same room twice
+pub async fn create_document_within_quota(
+    pool: &PgPool,
+    uuid: Uuid,
+    user_uuid: Uuid,
+    organisation_uuid: Option<Uuid>,
+    title: &str,
+    content: &str,
+    quotas: &QuotaConfig,
+) -> Result<Result<Document, QuotaError>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "SELECT uuid FROM users WHERE uuid = $1 FOR UPDATE",
+        user_uuid
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let usage = fetch_usage(&mut *tx, user_uuid).await?;
+    if let Err(err) = quotas.check(&usage, true, 0, content.len() as u64) {
+        return Ok(Err(err));
+    }
+
+    let document =
+        create_document(&mut *tx, uuid, user_uuid, organisation_uuid, title, content).await?;
+    tx.commit().await?;
+
+    Ok(Ok(document))
+}
+
+pub async fn update_document_within_quota(
+    pool: &PgPool,
+    uuid: Uuid,
+    user_uuid: Uuid,
+    organisation_uuid: Option<Uuid>,
+    title: &str,
+    content: &str,
+    quotas: &QuotaConfig,
+) -> Result<Result<Document, QuotaError>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    // The author is locked before the document is read, so its size can't change under us
+    sqlx::query!(
+        "
+        SELECT u.uuid
+        FROM users AS u
+        JOIN documents AS d ON d.user_uuid = u.uuid
+        WHERE d.uuid = $1
+        FOR UPDATE OF u
+        ",
+        uuid
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let existing = fetch_document_by_uuid(&mut *tx, uuid, user_uuid, organisation_uuid).await?;
+    let usage = fetch_usage(&mut *tx, existing.user_uuid.unwrap_or(user_uuid)).await?;
+    let replaced_bytes = existing.content.as_deref().unwrap_or_default().len() as u64;
+    if let Err(err) = quotas.check(&usage, false, replaced_bytes, content.len() as u64) {
+        return Ok(Err(err));
+    }
+
+    let document =
+        update_document(&mut *tx, uuid, user_uuid, organisation_uuid, title, content).await?;
+    tx.commit().await?;
+
+    Ok(Ok(document))
+}
+
+// In an organisation, only the author or someone allowed to manage every document
+// (can_manage) may delete a document
 pub async fn delete_document(
     pool: &PgPool,
     uuid: Uuid,
     user_uuid: Uuid,
+    organisation_uuid: Option<Uuid>,
+    can_manage: bool,
 ) -> Result<Document, sqlx::Error> {
     let document = sqlx::query_as!(
         Document,
         "
         DELETE FROM documents
-        WHERE uuid = $1 AND user_uuid = $2
+        WHERE uuid = $1 AND (
+            ($3::uuid IS NULL AND organisation_uuid IS NULL AND user_uuid = $2)
+            OR (organisation_uuid = $3 AND ($4 OR user_uuid = $2))
+        )
         RETURNING *
         ",
         uuid,
-        user_uuid
+        user_uuid,
+        organisation_uuid,
+        can_manage
     )
     .fetch_one(pool)
     .await?;
 
     Ok(document)
 }
+
+pub async fn fetch_usage(
+    executor: impl PgExecutor<'_>,
+    user_uuid: Uuid,
+) -> Result<UserUsage, sqlx::Error> {
+    let usage = sqlx::query_as!(
+        UserUsage,
+        r#"
+        SELECT
+            COUNT(*) AS "document_count!",
+            COALESCE(SUM(OCTET_LENGTH(content)), 0)::BIGINT AS "storage_bytes!",
+            COALESCE(MAX(OCTET_LENGTH(content)), 0)::BIGINT AS "largest_document_bytes!"
+        FROM documents
+        WHERE user_uuid = $1
+        "#,
+        user_uuid
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(usage)
+}
@@ -0,0 +1,144 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::data_export::DataExport;
+use crate::utils::constants::{EXPORT_STATUS_FAILED, EXPORT_STATUS_PENDING, EXPORT_STATUS_READY};
+
+// Only creates the export when the user has none being built, which UX_data_exports_pending
+// enforces. A build that outlived its expiry never finished and stops counting
+pub async fn create_export(
+    pool: &PgPool,
+    uuid: Uuid,
+    user_uuid: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<Option<DataExport>, sqlx::Error> {
+    sqlx::query!(
+        "
+        UPDATE DataExports
+        SET status = $2
+        WHERE user_uuid = $1 AND status = $3 AND expires_at <= NOW()
+        ",
+        user_uuid,
+        EXPORT_STATUS_FAILED,
+        EXPORT_STATUS_PENDING
+    )
+    .execute(pool)
+    .await?;
+
+    let result = sqlx::query_as!(
+        DataExport,
+        "
+        INSERT INTO DataExports (uuid, user_uuid, expires_at)
+        VALUES ($1, $2, $3)
+        RETURNING uuid, user_uuid, status, created_at, completed_at, expires_at, size_bytes
+        ",
+        uuid,
+        user_uuid,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await;
+
+    match result {
+        Ok(export) => Ok(Some(export)),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn fetch_export(
+    pool: &PgPool,
+    uuid: Uuid,
+    user_uuid: Uuid,
+) -> Result<DataExport, sqlx::Error> {
+    let export = sqlx::query_as!(
+        DataExport,
+        "
+        SELECT uuid, user_uuid, status, created_at, completed_at, expires_at, size_bytes
+        FROM DataExports
+        WHERE uuid = $1 AND user_uuid = $2 AND expires_at > NOW()
+        ",
+        uuid,
+        user_uuid
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(export)
+}
+
+// Only ready exports have an archive to hand out
+pub async fn fetch_export_archive(
+    pool: &PgPool,
+    uuid: Uuid,
+    user_uuid: Uuid,
+) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    let archive = sqlx::query_scalar!(
+        "
+        SELECT archive FROM DataExports
+        WHERE uuid = $1 AND user_uuid = $2 AND status = $3 AND expires_at > NOW()
+        ",
+        uuid,
+        user_uuid,
+        EXPORT_STATUS_READY
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(archive.flatten())
+}
+
+pub async fn complete_export(
+    pool: &PgPool,
+    uuid: Uuid,
+    archive: &[u8],
+    expires_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        UPDATE DataExports
+        SET status = $1, archive = $2, size_bytes = $3, completed_at = NOW(), expires_at = $4
+        WHERE uuid = $5
+        ",
+        EXPORT_STATUS_READY,
+        archive,
+        archive.len() as i64,
+        expires_at,
+        uuid
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn fail_export(pool: &PgPool, uuid: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        UPDATE DataExports
+        SET status = $1, completed_at = NOW()
+        WHERE uuid = $2
+        ",
+        EXPORT_STATUS_FAILED,
+        uuid
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Returns how many exports were removed
+pub async fn delete_expired_exports(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "
+        DELETE FROM DataExports
+        WHERE expires_at < NOW()
+        "
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,65 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::models::magic_link::MagicLink;
+
+pub async fn create_magic_link(
+    pool: &PgPool,
+    user_uuid: Uuid,
+    link_duration: Duration,
+) -> Result<MagicLink, sqlx::Error> {
+    let expires_at = Utc::now() + link_duration;
+
+    let magic_link = sqlx::query_as!(
+        MagicLink,
+        "
+        INSERT INTO MagicLinks (uuid, user_uuid, created_at, expires_at)
+        VALUES ($1, $2, DEFAULT, $3)
+        RETURNING *
+        ",
+        Uuid::new_v4(),
+        user_uuid,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(magic_link)
+}
+
+// Marks a link as used if it is still valid. Returns None if it expired or was already
+// used, so each link can sign in exactly once
+pub async fn consume_magic_link(
+    pool: &PgPool,
+    uuid: Uuid,
+) -> Result<Option<MagicLink>, sqlx::Error> {
+    let magic_link = sqlx::query_as!(
+        MagicLink,
+        "
+        UPDATE MagicLinks
+        SET used_at = NOW()
+        WHERE uuid = $1 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING *
+        ",
+        uuid
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(magic_link)
+}
+
+pub async fn delete_expired_magic_links(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        DELETE FROM MagicLinks
+        WHERE expires_at < NOW()
+        "
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -1,3 +1,16 @@
+pub mod admin_queries;
+pub mod audit_queries;
+pub mod backup_queries;
 pub mod connection;
 pub mod document_queries;
+pub mod export_queries;
+pub mod magic_link_queries;
+pub mod organisation_queries;
+pub mod postgres;
+pub mod repository;
+pub mod sqlite;
+pub mod two_factor_queries;
 pub mod user_queries;
+
+#[cfg(test)]
+pub mod test_support;
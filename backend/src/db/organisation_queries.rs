@@ -0,0 +1,382 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::models::organisation::{
+    Organisation, OrganisationInvite, OrganisationMember, OrganisationMembership,
+};
+
+// Creates an organisation with its creator as the owner
+pub async fn create_organisation(
+    pool: &PgPool,
+    uuid: Uuid,
+    name: &str,
+    owner_uuid: Uuid,
+) -> Result<Organisation, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let organisation = sqlx::query_as!(
+        Organisation,
+        "
+        INSERT INTO Organisations (uuid, name, created_by, created_at, updated_at)
+        VALUES ($1, $2, $3, DEFAULT, DEFAULT)
+        RETURNING *
+        ",
+        uuid,
+        name,
+        owner_uuid
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        INSERT INTO OrganisationMembers (organisation_uuid, user_uuid, role)
+        VALUES ($1, $2, 'owner')
+        ",
+        uuid,
+        owner_uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(organisation)
+}
+
+pub async fn fetch_organisation_by_uuid(
+    pool: &PgPool,
+    uuid: Uuid,
+) -> Result<Organisation, sqlx::Error> {
+    let organisation = sqlx::query_as!(
+        Organisation,
+        "
+        SELECT * FROM Organisations
+        WHERE uuid = $1
+        ",
+        uuid
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(organisation)
+}
+
+pub async fn fetch_organisations_for_user(
+    pool: &PgPool,
+    user_uuid: Uuid,
+) -> Result<Vec<OrganisationMembership>, sqlx::Error> {
+    let organisations = sqlx::query_as!(
+        OrganisationMembership,
+        "
+        SELECT o.uuid, o.name, m.role, o.created_at
+        FROM Organisations AS o
+        INNER JOIN OrganisationMembers AS m ON o.uuid = m.organisation_uuid
+        WHERE m.user_uuid = $1
+        ORDER BY o.name
+        ",
+        user_uuid
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(organisations)
+}
+
+pub async fn update_organisation_name(
+    pool: &PgPool,
+    uuid: Uuid,
+    name: &str,
+) -> Result<Organisation, sqlx::Error> {
+    let organisation = sqlx::query_as!(
+        Organisation,
+        "
+        UPDATE Organisations
+        SET name = $1, updated_at = CURRENT_TIMESTAMP
+        WHERE uuid = $2
+        RETURNING *
+        ",
+        name,
+        uuid
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(organisation)
+}
+
+// Deletes an organisation along with its documents, members and invites
+pub async fn delete_organisation(pool: &PgPool, uuid: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM documents
+        WHERE organisation_uuid = $1
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM OrganisationInvites
+        WHERE organisation_uuid = $1
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM OrganisationMembers
+        WHERE organisation_uuid = $1
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM Organisations
+        WHERE uuid = $1
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Returns the user's role in the organisation, or None if they aren't a member
+pub async fn fetch_member_role(
+    pool: &PgPool,
+    organisation_uuid: Uuid,
+    user_uuid: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let role = sqlx::query_scalar!(
+        "
+        SELECT role FROM OrganisationMembers
+        WHERE organisation_uuid = $1 AND user_uuid = $2
+        ",
+        organisation_uuid,
+        user_uuid
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(role)
+}
+
+pub async fn fetch_members(
+    pool: &PgPool,
+    organisation_uuid: Uuid,
+) -> Result<Vec<OrganisationMember>, sqlx::Error> {
+    let members = sqlx::query_as!(
+        OrganisationMember,
+        "
+        SELECT m.user_uuid, u.username, u.email, m.role, m.created_at
+        FROM OrganisationMembers AS m
+        INNER JOIN users AS u ON m.user_uuid = u.uuid
+        WHERE m.organisation_uuid = $1
+        ORDER BY u.username
+        ",
+        organisation_uuid
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(members)
+}
+
+pub async fn count_owners(pool: &PgPool, organisation_uuid: Uuid) -> Result<i64, sqlx::Error> {
+    let count = sqlx::query_scalar!(
+        "
+        SELECT COUNT(*) FROM OrganisationMembers
+        WHERE organisation_uuid = $1 AND role = 'owner'
+        ",
+        organisation_uuid
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count.unwrap_or(0))
+}
+
+pub async fn update_member_role(
+    pool: &PgPool,
+    organisation_uuid: Uuid,
+    user_uuid: Uuid,
+    role: &str,
+) -> Result<(), sqlx::Error> {
+    let result = sqlx::query!(
+        "
+        UPDATE OrganisationMembers
+        SET role = $1
+        WHERE organisation_uuid = $2 AND user_uuid = $3
+        ",
+        role,
+        organisation_uuid,
+        user_uuid
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    Ok(())
+}
+
+pub async fn delete_member(
+    pool: &PgPool,
+    organisation_uuid: Uuid,
+    user_uuid: Uuid,
+) -> Result<(), sqlx::Error> {
+    let result = sqlx::query!(
+        "
+        DELETE FROM OrganisationMembers
+        WHERE organisation_uuid = $1 AND user_uuid = $2
+        ",
+        organisation_uuid,
+        user_uuid
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    Ok(())
+}
+
+pub async fn create_invite(
+    pool: &PgPool,
+    organisation_uuid: Uuid,
+    email: &str,
+    role: &str,
+    invited_by: Uuid,
+    invite_duration: Duration,
+) -> Result<OrganisationInvite, sqlx::Error> {
+    let expires_at = Utc::now() + invite_duration;
+
+    let invite = sqlx::query_as!(
+        OrganisationInvite,
+        "
+        INSERT INTO OrganisationInvites
+            (uuid, organisation_uuid, email, role, invited_by, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5, DEFAULT, $6)
+        RETURNING *
+        ",
+        Uuid::new_v4(),
+        organisation_uuid,
+        email,
+        role,
+        invited_by,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(invite)
+}
+
+pub async fn fetch_pending_invites(
+    pool: &PgPool,
+    organisation_uuid: Uuid,
+) -> Result<Vec<OrganisationInvite>, sqlx::Error> {
+    let invites = sqlx::query_as!(
+        OrganisationInvite,
+        "
+        SELECT * FROM OrganisationInvites
+        WHERE organisation_uuid = $1 AND accepted_at IS NULL AND expires_at > NOW()
+        ORDER BY created_at
+        ",
+        organisation_uuid
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(invites)
+}
+
+pub async fn delete_invite(
+    pool: &PgPool,
+    organisation_uuid: Uuid,
+    uuid: Uuid,
+) -> Result<(), sqlx::Error> {
+    let result = sqlx::query!(
+        "
+        DELETE FROM OrganisationInvites
+        WHERE organisation_uuid = $1 AND uuid = $2
+        ",
+        organisation_uuid,
+        uuid
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    Ok(())
+}
+
+// Accepts a pending invite addressed to the given email and adds the user to the
+// organisation. Returns None if the invite doesn't exist, expired, was already accepted or
+// belongs to someone else
+pub async fn accept_invite(
+    pool: &PgPool,
+    uuid: Uuid,
+    user_uuid: Uuid,
+    email: &str,
+) -> Result<Option<OrganisationInvite>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let invite = sqlx::query_as!(
+        OrganisationInvite,
+        "
+        UPDATE OrganisationInvites
+        SET accepted_at = NOW()
+        WHERE uuid = $1 AND LOWER(email) = LOWER($2) AND accepted_at IS NULL AND expires_at > NOW()
+        RETURNING *
+        ",
+        uuid,
+        email
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(invite) = invite else {
+        return Ok(None);
+    };
+
+    // Existing members keep their current role
+    sqlx::query!(
+        "
+        INSERT INTO OrganisationMembers (organisation_uuid, user_uuid, role)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (organisation_uuid, user_uuid) DO NOTHING
+        ",
+        invite.organisation_uuid,
+        user_uuid,
+        invite.role
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(invite))
+}
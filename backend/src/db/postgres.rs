@@ -0,0 +1,363 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::repository::{
+    BackupData, BackupRepository, DocumentRepository, ExportRepository, UserRepository,
+};
+use crate::db::{admin_queries, backup_queries, document_queries, export_queries, user_queries};
+use crate::models::data_export::DataExport;
+use crate::models::document::Document;
+use crate::models::user::User;
+use crate::models::user_overview::{StorageUsage, UserOverview};
+use crate::models::user_preferences::UserPreferences;
+use crate::models::user_session::UserSession;
+use crate::models::user_usage::UserUsage;
+use crate::utils::quota::{QuotaConfig, QuotaError};
+
+// The repositories on PostgreSQL, backed by the compile time checked queries in
+// user_queries and document_queries
+#[derive(Clone)]
+pub struct PgRepository {
+    pool: PgPool,
+}
+
+impl PgRepository {
+    pub fn new(pool: PgPool) -> Self {
+        PgRepository { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl UserRepository for PgRepository {
+    async fn fetch_user_by_email(&self, email: &str) -> Result<User, sqlx::Error> {
+        user_queries::fetch_user_by_email(&self.pool, email).await
+    }
+
+    async fn fetch_user_by_uuid(&self, uuid: Uuid) -> Result<User, sqlx::Error> {
+        user_queries::fetch_user_by_uuid(&self.pool, uuid).await
+    }
+
+    async fn fetch_user_by_session_uuid(&self, session_uuid: Uuid) -> Result<User, sqlx::Error> {
+        user_queries::fetch_user_by_session_uuid(&self.pool, session_uuid).await
+    }
+
+    async fn fetch_active_user_session(
+        &self,
+        session_uuid: Uuid,
+    ) -> Result<Option<UserSession>, sqlx::Error> {
+        user_queries::fetch_active_user_session(&self.pool, session_uuid).await
+    }
+
+    async fn fetch_active_user_sessions(
+        &self,
+        user_uuid: Uuid,
+    ) -> Result<Vec<UserSession>, sqlx::Error> {
+        user_queries::fetch_active_user_sessions(&self.pool, user_uuid).await
+    }
+
+    async fn create_user(
+        &self,
+        uuid: Uuid,
+        username: &str,
+        email: &str,
+    ) -> Result<User, sqlx::Error> {
+        user_queries::create_user(&self.pool, uuid, username, email).await
+    }
+
+    async fn create_user_session(
+        &self,
+        user_uuid: Uuid,
+        session_duration: Duration,
+    ) -> Result<UserSession, sqlx::Error> {
+        user_queries::create_user_session(&self.pool, user_uuid, session_duration).await
+    }
+
+    async fn extend_user_session(
+        &self,
+        session_uuid: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<UserSession, sqlx::Error> {
+        user_queries::extend_user_session(&self.pool, session_uuid, expires_at).await
+    }
+
+    async fn rotate_user_session(
+        &self,
+        session_uuid: Uuid,
+        issued_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+        grace_expires_at: DateTime<Utc>,
+    ) -> Result<UserSession, sqlx::Error> {
+        user_queries::rotate_user_session(
+            &self.pool,
+            session_uuid,
+            issued_at,
+            expires_at,
+            grace_expires_at,
+        )
+        .await
+    }
+
+    async fn update_user_profile(
+        &self,
+        uuid: Uuid,
+        display_name: Option<&str>,
+        avatar_url: Option<&str>,
+    ) -> Result<User, sqlx::Error> {
+        user_queries::update_user_profile(&self.pool, uuid, display_name, avatar_url).await
+    }
+
+    async fn fetch_user_preferences(
+        &self,
+        user_uuid: Uuid,
+    ) -> Result<UserPreferences, sqlx::Error> {
+        user_queries::fetch_user_preferences(&self.pool, user_uuid).await
+    }
+
+    async fn update_user_preferences(
+        &self,
+        preferences: &UserPreferences,
+    ) -> Result<UserPreferences, sqlx::Error> {
+        user_queries::update_user_preferences(&self.pool, preferences).await
+    }
+
+    async fn delete_user(&self, uuid: Uuid) -> Result<(), sqlx::Error> {
+        user_queries::delete_user(&self.pool, uuid).await
+    }
+
+    async fn schedule_user_deletion(
+        &self,
+        uuid: Uuid,
+        deletion_scheduled_at: Option<DateTime<Utc>>,
+    ) -> Result<User, sqlx::Error> {
+        user_queries::schedule_user_deletion(&self.pool, uuid, deletion_scheduled_at).await
+    }
+
+    async fn fetch_users_due_for_deletion(&self) -> Result<Vec<Uuid>, sqlx::Error> {
+        user_queries::fetch_users_due_for_deletion(&self.pool).await
+    }
+
+    async fn fetch_user_overviews(&self) -> Result<Vec<UserOverview>, sqlx::Error> {
+        admin_queries::fetch_user_overviews(&self.pool).await
+    }
+
+    async fn set_user_disabled(&self, uuid: Uuid, disabled: bool) -> Result<User, sqlx::Error> {
+        admin_queries::set_user_disabled(&self.pool, uuid, disabled).await
+    }
+
+    async fn set_user_role(&self, uuid: Uuid, role: &str) -> Result<User, sqlx::Error> {
+        admin_queries::set_user_role(&self.pool, uuid, role).await
+    }
+
+    async fn promote_admins(&self, emails: &[String]) -> Result<u64, sqlx::Error> {
+        admin_queries::promote_admins(&self.pool, emails).await
+    }
+
+    async fn fetch_storage_usage(&self) -> Result<StorageUsage, sqlx::Error> {
+        admin_queries::fetch_storage_usage(&self.pool).await
+    }
+
+    async fn delete_user_session(&self, session_uuid: Uuid) -> Result<(), sqlx::Error> {
+        user_queries::delete_user_session(&self.pool, session_uuid).await
+    }
+
+    async fn delete_user_sessions(&self, user_uuid: Uuid) -> Result<u64, sqlx::Error> {
+        user_queries::delete_user_sessions(&self.pool, user_uuid).await
+    }
+
+    async fn count_active_sessions(&self) -> Result<i64, sqlx::Error> {
+        user_queries::count_active_sessions(&self.pool).await
+    }
+
+    async fn delete_expired_sessions(&self) -> Result<u64, sqlx::Error> {
+        user_queries::delete_expired_sessions(&self.pool).await
+    }
+}
+
+#[async_trait::async_trait]
+impl DocumentRepository for PgRepository {
+    async fn fetch_document_by_uuid(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+    ) -> Result<Document, sqlx::Error> {
+        document_queries::fetch_document_by_uuid(&self.pool, uuid, user_uuid, organisation_uuid)
+            .await
+    }
+
+    async fn fetch_all_documents_for_user(
+        &self,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+    ) -> Result<Vec<Document>, sqlx::Error> {
+        document_queries::fetch_all_documents_for_user(&self.pool, user_uuid, organisation_uuid)
+            .await
+    }
+
+    async fn fetch_documents_by_author(
+        &self,
+        user_uuid: Uuid,
+    ) -> Result<Vec<Document>, sqlx::Error> {
+        document_queries::fetch_documents_by_author(&self.pool, user_uuid).await
+    }
+
+    async fn create_document(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        title: &str,
+        content: &str,
+    ) -> Result<Document, sqlx::Error> {
+        document_queries::create_document(
+            &self.pool,
+            uuid,
+            user_uuid,
+            organisation_uuid,
+            title,
+            content,
+        )
+        .await
+    }
+
+    async fn update_document(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        title: &str,
+        content: &str,
+    ) -> Result<Document, sqlx::Error> {
+        document_queries::update_document(
+            &self.pool,
+            uuid,
+            user_uuid,
+            organisation_uuid,
+            title,
+            content,
+        )
+        .await
+    }
+
+    async fn create_document_within_quota(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        title: &str,
+        content: &str,
+        quotas: &QuotaConfig,
+    ) -> Result<Result<Document, QuotaError>, sqlx::Error> {
+        document_queries::create_document_within_quota(
+            &self.pool,
+            uuid,
+            user_uuid,
+            organisation_uuid,
+            title,
+            content,
+            quotas,
+        )
+        .await
+    }
+
+    async fn update_document_within_quota(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        title: &str,
+        content: &str,
+        quotas: &QuotaConfig,
+    ) -> Result<Result<Document, QuotaError>, sqlx::Error> {
+        document_queries::update_document_within_quota(
+            &self.pool,
+            uuid,
+            user_uuid,
+            organisation_uuid,
+            title,
+            content,
+            quotas,
+        )
+        .await
+    }
+
+    async fn fetch_usage(&self, user_uuid: Uuid) -> Result<UserUsage, sqlx::Error> {
+        document_queries::fetch_usage(&self.pool, user_uuid).await
+    }
+
+    async fn delete_document(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        can_manage: bool,
+    ) -> Result<Document, sqlx::Error> {
+        document_queries::delete_document(
+            &self.pool,
+            uuid,
+            user_uuid,
+            organisation_uuid,
+            can_manage,
+        )
+        .await
+    }
+}
+
+#[async_trait::async_trait]
+impl BackupRepository for PgRepository {
+    async fn fetch_backup(&self) -> Result<BackupData, sqlx::Error> {
+        backup_queries::fetch_backup(&self.pool).await
+    }
+
+    async fn count_users(&self) -> Result<i64, sqlx::Error> {
+        backup_queries::count_users(&self.pool).await
+    }
+
+    async fn restore_backup(&self, backup: &BackupData) -> Result<(), sqlx::Error> {
+        backup_queries::restore_backup(&self.pool, backup).await
+    }
+}
+
+#[async_trait::async_trait]
+impl ExportRepository for PgRepository {
+    async fn create_export(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<DataExport>, sqlx::Error> {
+        export_queries::create_export(&self.pool, uuid, user_uuid, expires_at).await
+    }
+
+    async fn fetch_export(&self, uuid: Uuid, user_uuid: Uuid) -> Result<DataExport, sqlx::Error> {
+        export_queries::fetch_export(&self.pool, uuid, user_uuid).await
+    }
+
+    async fn fetch_export_archive(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+    ) -> Result<Option<Vec<u8>>, sqlx::Error> {
+        export_queries::fetch_export_archive(&self.pool, uuid, user_uuid).await
+    }
+
+    async fn complete_export(
+        &self,
+        uuid: Uuid,
+        archive: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        export_queries::complete_export(&self.pool, uuid, archive, expires_at).await
+    }
+
+    async fn fail_export(&self, uuid: Uuid) -> Result<(), sqlx::Error> {
+        export_queries::fail_export(&self.pool, uuid).await
+    }
+
+    async fn delete_expired_exports(&self) -> Result<u64, sqlx::Error> {
+        export_queries::delete_expired_exports(&self.pool).await
+    }
+}
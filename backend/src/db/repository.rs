@@ -0,0 +1,938 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::models::backup::{OrganisationMemberRecord, RecoveryCodeRecord, UserTotpRecord};
+use crate::models::data_export::DataExport;
+use crate::models::document::Document;
+use crate::models::organisation::{Organisation, OrganisationInvite};
+use crate::models::user::User;
+use crate::models::user_overview::{StorageUsage, UserOverview};
+use crate::models::user_preferences::UserPreferences;
+use crate::models::user_session::UserSession;
+use crate::models::user_usage::UserUsage;
+use crate::utils::quota::{QuotaConfig, QuotaError};
+
+// Users and their sessions, whichever database they are stored in. Lookups that find
+// nothing fail with sqlx::Error::RowNotFound like the queries behind them
+#[async_trait::async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn fetch_user_by_email(&self, email: &str) -> Result<User, sqlx::Error>;
+
+    async fn fetch_user_by_uuid(&self, uuid: Uuid) -> Result<User, sqlx::Error>;
+
+    // The user owning a session that hasn't expired yet
+    async fn fetch_user_by_session_uuid(&self, session_uuid: Uuid) -> Result<User, sqlx::Error>;
+
+    async fn fetch_active_user_session(
+        &self,
+        session_uuid: Uuid,
+    ) -> Result<Option<UserSession>, sqlx::Error>;
+
+    // Every session the user could still sign in with, oldest first
+    async fn fetch_active_user_sessions(
+        &self,
+        user_uuid: Uuid,
+    ) -> Result<Vec<UserSession>, sqlx::Error>;
+
+    async fn create_user(
+        &self,
+        uuid: Uuid,
+        username: &str,
+        email: &str,
+    ) -> Result<User, sqlx::Error>;
+
+    async fn create_user_session(
+        &self,
+        user_uuid: Uuid,
+        session_duration: Duration,
+    ) -> Result<UserSession, sqlx::Error>;
+
+    async fn extend_user_session(
+        &self,
+        session_uuid: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<UserSession, sqlx::Error>;
+
+    // Replaces a session with a new identifier that keeps the original login time. The old
+    // identifier stays valid until grace_expires_at so requests already in flight still
+    // succeed. A session that was already rotated returns the session that replaced it
+    async fn rotate_user_session(
+        &self,
+        session_uuid: Uuid,
+        issued_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+        grace_expires_at: DateTime<Utc>,
+    ) -> Result<UserSession, sqlx::Error>;
+
+    // Sets the name and picture the user picked, None falls back to the ones from Google
+    async fn update_user_profile(
+        &self,
+        uuid: Uuid,
+        display_name: Option<&str>,
+        avatar_url: Option<&str>,
+    ) -> Result<User, sqlx::Error>;
+
+    // The defaults when the user never saved any
+    async fn fetch_user_preferences(&self, user_uuid: Uuid)
+        -> Result<UserPreferences, sqlx::Error>;
+
+    async fn update_user_preferences(
+        &self,
+        preferences: &UserPreferences,
+    ) -> Result<UserPreferences, sqlx::Error>;
+
+    // Deletes the user along with their sessions and documents, all or nothing
+    async fn delete_user(&self, uuid: Uuid) -> Result<(), sqlx::Error>;
+
+    // Marks the account to be deleted once deletion_scheduled_at has passed, None cancels
+    // a scheduled deletion
+    async fn schedule_user_deletion(
+        &self,
+        uuid: Uuid,
+        deletion_scheduled_at: Option<DateTime<Utc>>,
+    ) -> Result<User, sqlx::Error>;
+
+    // Accounts whose grace period is over
+    async fn fetch_users_due_for_deletion(&self) -> Result<Vec<Uuid>, sqlx::Error>;
+
+    // Every account with what it stores and how many sessions it has, oldest first
+    async fn fetch_user_overviews(&self) -> Result<Vec<UserOverview>, sqlx::Error>;
+
+    // Disabled accounts can't sign in, disabling one twice keeps the original time
+    async fn set_user_disabled(&self, uuid: Uuid, disabled: bool) -> Result<User, sqlx::Error>;
+
+    async fn set_user_role(&self, uuid: Uuid, role: &str) -> Result<User, sqlx::Error>;
+
+    // Gives the admin role to every existing user with one of the emails. Returns how many
+    // users were promoted
+    async fn promote_admins(&self, emails: &[String]) -> Result<u64, sqlx::Error>;
+
+    // Totals across every account
+    async fn fetch_storage_usage(&self) -> Result<StorageUsage, sqlx::Error>;
+
+    async fn delete_user_session(&self, session_uuid: Uuid) -> Result<(), sqlx::Error>;
+
+    // Logs a user out everywhere. Returns how many sessions were removed
+    async fn delete_user_sessions(&self, user_uuid: Uuid) -> Result<u64, sqlx::Error>;
+
+    // Sessions that can still be used, rotated identifiers in their grace period are left out
+    async fn count_active_sessions(&self) -> Result<i64, sqlx::Error>;
+
+    // Returns how many sessions were removed
+    async fn delete_expired_sessions(&self) -> Result<u64, sqlx::Error>;
+}
+
+// Documents, scoped to a workspace. Without an organisation_uuid that is the user's personal
+// documents, with one it is the organisation's documents. Callers must check that the user
+// is a member of the organisation first
+#[async_trait::async_trait]
+pub trait DocumentRepository: Send + Sync {
+    async fn fetch_document_by_uuid(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+    ) -> Result<Document, sqlx::Error>;
+
+    async fn fetch_all_documents_for_user(
+        &self,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+    ) -> Result<Vec<Document>, sqlx::Error>;
+
+    // Every document the user wrote, personal ones and those in organisations alike
+    async fn fetch_documents_by_author(
+        &self,
+        user_uuid: Uuid,
+    ) -> Result<Vec<Document>, sqlx::Error>;
+
+    async fn create_document(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        title: &str,
+        content: &str,
+    ) -> Result<Document, sqlx::Error>;
+
+    async fn update_document(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        title: &str,
+        content: &str,
+    ) -> Result<Document, sqlx::Error>;
+
+    // Like create_document, but the inner error says the author's quota has no room for it.
+    // Usage is read and the document written in one transaction that holds the author, so
+    // concurrent writes can't add up to more than the quota
+    async fn create_document_within_quota(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        title: &str,
+        content: &str,
+        quotas: &QuotaConfig,
+    ) -> Result<Result<Document, QuotaError>, sqlx::Error>;
+
+    // Like update_document, counted against the quota of the document's author, who may be
+    // someone else in the organisation
+    async fn update_document_within_quota(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        title: &str,
+        content: &str,
+        quotas: &QuotaConfig,
+    ) -> Result<Result<Document, QuotaError>, sqlx::Error>;
+
+    // What the user stores across every workspace
+    async fn fetch_usage(&self, user_uuid: Uuid) -> Result<UserUsage, sqlx::Error>;
+
+    // In an organisation, only the author or someone allowed to manage every document
+    // (can_manage) may delete a document
+    async fn delete_document(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        can_manage: bool,
+    ) -> Result<Document, sqlx::Error>;
+}
+
+// Everything a backup holds, which is every table but the audit log and short lived sign in
+// state. Organisations and two-factor authentication only exist on PostgreSQL, so SQLite
+// leaves those empty
+#[derive(Debug, Default)]
+pub struct BackupData {
+    pub users: Vec<User>,
+    pub preferences: Vec<UserPreferences>,
+    pub sessions: Vec<UserSession>,
+    pub totp: Vec<UserTotpRecord>,
+    pub recovery_codes: Vec<RecoveryCodeRecord>,
+    pub organisations: Vec<Organisation>,
+    pub organisation_members: Vec<OrganisationMemberRecord>,
+    pub organisation_invites: Vec<OrganisationInvite>,
+    pub documents: Vec<Document>,
+}
+
+// Whole tables at once, for the backup and restore commands
+#[async_trait::async_trait]
+pub trait BackupRepository: Send + Sync {
+    // A consistent snapshot of everything a backup holds
+    async fn fetch_backup(&self) -> Result<BackupData, sqlx::Error>;
+
+    async fn count_users(&self) -> Result<i64, sqlx::Error>;
+
+    // Inserts the rows as they are, uuids and timestamps included, in one transaction
+    async fn restore_backup(&self, backup: &BackupData) -> Result<(), sqlx::Error>;
+}
+
+// Archives of a user's own data. Every lookup is scoped to the user and skips expired exports
+#[async_trait::async_trait]
+pub trait ExportRepository: Send + Sync {
+    // None when the user already has an export being built
+    async fn create_export(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<DataExport>, sqlx::Error>;
+
+    async fn fetch_export(&self, uuid: Uuid, user_uuid: Uuid) -> Result<DataExport, sqlx::Error>;
+
+    // None until the export is ready
+    async fn fetch_export_archive(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+    ) -> Result<Option<Vec<u8>>, sqlx::Error>;
+
+    async fn complete_export(
+        &self,
+        uuid: Uuid,
+        archive: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn fail_export(&self, uuid: Uuid) -> Result<(), sqlx::Error>;
+
+    // Returns how many exports were removed
+    async fn delete_expired_exports(&self) -> Result<u64, sqlx::Error>;
+}
+
+pub type SharedUserRepository = Arc<dyn UserRepository>;
+pub type SharedDocumentRepository = Arc<dyn DocumentRepository>;
+pub type SharedBackupRepository = Arc<dyn BackupRepository>;
+pub type SharedExportRepository = Arc<dyn ExportRepository>;
+
+// The suite every backend has to pass. Each case runs once on SQLite and, when
+// TEST_DATABASE_URL is set, once on PostgreSQL
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration as TimeDelta, Utc};
+
+    use super::*;
+    use crate::db::connection::Database;
+
+    const SESSION_DURATION: Duration = Duration::from_secs(3600);
+
+    macro_rules! repository_tests {
+        ($($case:ident),* $(,)?) => {
+            mod sqlite {
+                $(
+                    #[tokio::test]
+                    async fn $case() {
+                        super::$case(crate::db::test_support::sqlite().await).await;
+                    }
+                )*
+            }
+
+            mod postgres {
+                $(
+                    #[tokio::test]
+                    async fn $case() {
+                        let Some(schema) = crate::db::test_support::PostgresSchema::create().await
+                        else {
+                            return;
+                        };
+                        super::$case(schema.database.clone()).await;
+                        schema.drop().await;
+                    }
+                )*
+            }
+        };
+    }
+
+    repository_tests!(
+        creates_and_finds_users,
+        sessions_authenticate_until_they_expire,
+        rotated_sessions_live_out_their_grace_period,
+        deletes_sessions,
+        deletes_expired_sessions_only,
+        documents_round_trip,
+        documents_are_scoped_to_their_owner,
+        deletes_users,
+        schedules_user_deletion,
+        preferences_round_trip,
+        counts_usage_in_bytes,
+        concurrent_writes_stay_within_the_quota,
+        disables_users_and_lists_them,
+        promotes_admins_and_counts_storage,
+        builds_one_export_at_a_time,
+    );
+
+    async fn create_user(users: &dyn UserRepository, name: &str) -> User {
+        users
+            .create_user(Uuid::new_v4(), name, &format!("{name}@example.com"))
+            .await
+            .unwrap()
+    }
+
+    fn is_row_not_found<T: std::fmt::Debug>(result: Result<T, sqlx::Error>) -> bool {
+        matches!(result, Err(sqlx::Error::RowNotFound))
+    }
+
+    async fn creates_and_finds_users(database: Database) {
+        let users = database.users();
+
+        let user = create_user(users.as_ref(), "alice").await;
+        assert_eq!(user.role, "user");
+        assert!(user.created_at.is_some());
+        assert!(!user.is_disabled());
+
+        let by_email = users
+            .fetch_user_by_email("alice@example.com")
+            .await
+            .unwrap();
+        assert_eq!(by_email.uuid, user.uuid);
+        let by_uuid = users.fetch_user_by_uuid(user.uuid).await.unwrap();
+        assert_eq!(by_uuid.email, user.email);
+
+        assert!(is_row_not_found(
+            users.fetch_user_by_email("nobody@example.com").await
+        ));
+        assert!(is_row_not_found(
+            users.fetch_user_by_uuid(Uuid::new_v4()).await
+        ));
+    }
+
+    async fn sessions_authenticate_until_they_expire(database: Database) {
+        let users = database.users();
+        let user = create_user(users.as_ref(), "alice").await;
+
+        let session = users
+            .create_user_session(user.uuid, SESSION_DURATION)
+            .await
+            .unwrap();
+        assert_eq!(session.user_uuid, user.uuid);
+        assert_eq!(session.issued_at, session.created_at);
+        assert!(session.expires_at.unwrap() > Utc::now());
+
+        let signed_in = users
+            .fetch_user_by_session_uuid(session.uuid)
+            .await
+            .unwrap();
+        assert_eq!(signed_in.uuid, user.uuid);
+        assert!(users
+            .fetch_active_user_session(session.uuid)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(users.count_active_sessions().await.unwrap(), 1);
+
+        let expired = users
+            .extend_user_session(session.uuid, Utc::now() - TimeDelta::minutes(1))
+            .await
+            .unwrap();
+        assert!(expired.expires_at.unwrap() < Utc::now());
+
+        assert!(is_row_not_found(
+            users.fetch_user_by_session_uuid(session.uuid).await
+        ));
+        assert!(users
+            .fetch_active_user_session(session.uuid)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(users.count_active_sessions().await.unwrap(), 0);
+    }
+
+    async fn rotated_sessions_live_out_their_grace_period(database: Database) {
+        let users = database.users();
+        let user = create_user(users.as_ref(), "alice").await;
+        let session = users
+            .create_user_session(user.uuid, SESSION_DURATION)
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let rotated = users
+            .rotate_user_session(
+                session.uuid,
+                now,
+                now + TimeDelta::hours(1),
+                now + TimeDelta::minutes(1),
+            )
+            .await
+            .unwrap();
+        assert_ne!(rotated.uuid, session.uuid);
+        assert_eq!(rotated.user_uuid, user.uuid);
+        // The login time carries over, only the identifier is new
+        assert_eq!(rotated.created_at, session.created_at);
+        assert!(rotated.replaced_by.is_none());
+
+        let old = users
+            .fetch_active_user_session(session.uuid)
+            .await
+            .unwrap()
+            .expect("the old identifier still works during the grace period");
+        assert_eq!(old.replaced_by, Some(rotated.uuid));
+        assert!(old.expires_at.unwrap() < rotated.expires_at.unwrap());
+
+        // A second rotation, e.g. from a concurrent request, doesn't split the session
+        let again = users
+            .rotate_user_session(
+                session.uuid,
+                now,
+                now + TimeDelta::hours(1),
+                now + TimeDelta::minutes(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(again.uuid, rotated.uuid);
+
+        // Rotated identifiers in their grace period aren't counted as sessions of their own
+        assert_eq!(users.count_active_sessions().await.unwrap(), 1);
+    }
+
+    async fn deletes_sessions(database: Database) {
+        let users = database.users();
+        let alice = create_user(users.as_ref(), "alice").await;
+        let bob = create_user(users.as_ref(), "bob").await;
+
+        let first = users
+            .create_user_session(alice.uuid, SESSION_DURATION)
+            .await
+            .unwrap();
+        users
+            .create_user_session(alice.uuid, SESSION_DURATION)
+            .await
+            .unwrap();
+        let bobs = users
+            .create_user_session(bob.uuid, SESSION_DURATION)
+            .await
+            .unwrap();
+
+        users.delete_user_session(first.uuid).await.unwrap();
+        assert!(is_row_not_found(
+            users.fetch_user_by_session_uuid(first.uuid).await
+        ));
+        assert_eq!(users.count_active_sessions().await.unwrap(), 2);
+
+        assert_eq!(users.delete_user_sessions(alice.uuid).await.unwrap(), 1);
+        assert_eq!(users.delete_user_sessions(alice.uuid).await.unwrap(), 0);
+        assert!(users.fetch_user_by_session_uuid(bobs.uuid).await.is_ok());
+    }
+
+    async fn deletes_expired_sessions_only(database: Database) {
+        let users = database.users();
+        let user = create_user(users.as_ref(), "alice").await;
+
+        let active = users
+            .create_user_session(user.uuid, SESSION_DURATION)
+            .await
+            .unwrap();
+        // Expiries far apart, which broke when they were compared as text
+        let expired_recently = users
+            .create_user_session(user.uuid, SESSION_DURATION)
+            .await
+            .unwrap();
+        users
+            .extend_user_session(expired_recently.uuid, Utc::now() - TimeDelta::seconds(5))
+            .await
+            .unwrap();
+        let expired_long_ago = users
+            .create_user_session(user.uuid, SESSION_DURATION)
+            .await
+            .unwrap();
+        users
+            .extend_user_session(expired_long_ago.uuid, Utc::now() - TimeDelta::days(10_000))
+            .await
+            .unwrap();
+
+        assert_eq!(users.delete_expired_sessions().await.unwrap(), 2);
+
+        assert!(users
+            .fetch_active_user_session(active.uuid)
+            .await
+            .unwrap()
+            .is_some());
+        // Only the active session is left to log out
+        assert_eq!(users.delete_user_sessions(user.uuid).await.unwrap(), 1);
+    }
+
+    async fn documents_round_trip(database: Database) {
+        let users = database.users();
+        let documents = database.documents();
+        let user = create_user(users.as_ref(), "alice").await;
+
+        let uuid = Uuid::new_v4();
+        let created = documents
+            .create_document(uuid, user.uuid, None, "Notes", "# Hello")
+            .await
+            .unwrap();
+        assert_eq!(created.uuid, Some(uuid));
+        assert_eq!(created.user_uuid, Some(user.uuid));
+        assert_eq!(created.title.as_deref(), Some("Notes"));
+        assert_eq!(created.content.as_deref(), Some("# Hello"));
+        assert_eq!(created.organisation_uuid, None);
+        assert!(created.created_at.is_some());
+
+        let fetched = documents
+            .fetch_document_by_uuid(uuid, user.uuid, None)
+            .await
+            .unwrap();
+        assert_eq!(fetched.content, created.content);
+
+        let updated = documents
+            .update_document(uuid, user.uuid, None, "Renamed", "# Changed")
+            .await
+            .unwrap();
+        assert_eq!(updated.title.as_deref(), Some("Renamed"));
+        assert_eq!(updated.content.as_deref(), Some("# Changed"));
+        assert_eq!(updated.created_at, created.created_at);
+        assert!(updated.updated_at >= created.updated_at);
+
+        let all = documents
+            .fetch_all_documents_for_user(user.uuid, None)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].title.as_deref(), Some("Renamed"));
+
+        let deleted = documents
+            .delete_document(uuid, user.uuid, None, true)
+            .await
+            .unwrap();
+        assert_eq!(deleted.uuid, Some(uuid));
+        assert!(is_row_not_found(
+            documents
+                .fetch_document_by_uuid(uuid, user.uuid, None)
+                .await
+        ));
+        assert!(is_row_not_found(
+            documents.delete_document(uuid, user.uuid, None, true).await
+        ));
+    }
+
+    async fn documents_are_scoped_to_their_owner(database: Database) {
+        let users = database.users();
+        let documents = database.documents();
+        let alice = create_user(users.as_ref(), "alice").await;
+        let bob = create_user(users.as_ref(), "bob").await;
+
+        let uuid = Uuid::new_v4();
+        documents
+            .create_document(uuid, alice.uuid, None, "Private", "Alice only")
+            .await
+            .unwrap();
+
+        assert!(is_row_not_found(
+            documents.fetch_document_by_uuid(uuid, bob.uuid, None).await
+        ));
+        assert!(documents
+            .fetch_all_documents_for_user(bob.uuid, None)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(is_row_not_found(
+            documents
+                .update_document(uuid, bob.uuid, None, "Taken", "Bob was here")
+                .await
+        ));
+        assert!(is_row_not_found(
+            documents.delete_document(uuid, bob.uuid, None, true).await
+        ));
+
+        let untouched = documents
+            .fetch_document_by_uuid(uuid, alice.uuid, None)
+            .await
+            .unwrap();
+        assert_eq!(untouched.content.as_deref(), Some("Alice only"));
+    }
+
+    async fn deletes_users(database: Database) {
+        let users = database.users();
+        let user = create_user(users.as_ref(), "alice").await;
+        let bob = create_user(users.as_ref(), "bob").await;
+        let session = users
+            .create_user_session(user.uuid, SESSION_DURATION)
+            .await
+            .unwrap();
+        for owner in [&user, &bob] {
+            database
+                .documents()
+                .create_document(Uuid::new_v4(), owner.uuid, None, "Notes", "")
+                .await
+                .unwrap();
+        }
+
+        users.delete_user(user.uuid).await.unwrap();
+
+        assert!(is_row_not_found(users.fetch_user_by_uuid(user.uuid).await));
+        assert!(users
+            .fetch_active_user_session(session.uuid)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(database
+            .documents()
+            .fetch_all_documents_for_user(user.uuid, None)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            database
+                .documents()
+                .fetch_all_documents_for_user(bob.uuid, None)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    async fn schedules_user_deletion(database: Database) {
+        let users = database.users();
+        let user = create_user(users.as_ref(), "alice").await;
+        let later = create_user(users.as_ref(), "bob").await;
+
+        let due = users
+            .schedule_user_deletion(user.uuid, Some(Utc::now() - TimeDelta::minutes(1)))
+            .await
+            .unwrap();
+        assert!(due.deletion_scheduled_at.is_some());
+        users
+            .schedule_user_deletion(later.uuid, Some(Utc::now() + TimeDelta::days(1)))
+            .await
+            .unwrap();
+        assert_eq!(
+            users.fetch_users_due_for_deletion().await.unwrap(),
+            [user.uuid]
+        );
+
+        let kept = users.schedule_user_deletion(user.uuid, None).await.unwrap();
+        assert!(kept.deletion_scheduled_at.is_none());
+        assert!(users
+            .fetch_users_due_for_deletion()
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    async fn preferences_round_trip(database: Database) {
+        let users = database.users();
+        let user = create_user(users.as_ref(), "alice").await;
+
+        let defaults = users.fetch_user_preferences(user.uuid).await.unwrap();
+        assert_eq!(defaults.theme, "system");
+        assert!(defaults.updated_at.is_none());
+
+        let saved = users
+            .update_user_preferences(&UserPreferences {
+                theme: "dark".to_string(),
+                editor_font_size: 18,
+                ..defaults
+            })
+            .await
+            .unwrap();
+        assert!(saved.updated_at.is_some());
+
+        // Saving again replaces the row rather than adding another
+        users
+            .update_user_preferences(&UserPreferences {
+                keybinding_mode: "vim".to_string(),
+                ..saved
+            })
+            .await
+            .unwrap();
+        let fetched = users.fetch_user_preferences(user.uuid).await.unwrap();
+        assert_eq!(
+            (
+                fetched.theme.as_str(),
+                fetched.editor_font_size,
+                fetched.keybinding_mode.as_str()
+            ),
+            ("dark", 18, "vim")
+        );
+
+        let user = users
+            .update_user_profile(user.uuid, Some("Alice"), None)
+            .await
+            .unwrap();
+        assert_eq!(user.display_name.as_deref(), Some("Alice"));
+    }
+
+    async fn counts_usage_in_bytes(database: Database) {
+        let users = database.users();
+        let documents = database.documents();
+        let user = create_user(users.as_ref(), "alice").await;
+
+        assert_eq!(
+            documents
+                .fetch_usage(user.uuid)
+                .await
+                .unwrap()
+                .document_count,
+            0
+        );
+
+        // Two bytes per character, sizes are counted the way quotas measure them
+        for content in ["éé", "abc"] {
+            documents
+                .create_document(Uuid::new_v4(), user.uuid, None, "Notes", content)
+                .await
+                .unwrap();
+        }
+
+        let usage = documents.fetch_usage(user.uuid).await.unwrap();
+        assert_eq!(
+            (
+                usage.document_count,
+                usage.storage_bytes,
+                usage.largest_document_bytes
+            ),
+            (2, 7, 4)
+        );
+    }
+
+    async fn concurrent_writes_stay_within_the_quota(database: Database) {
+        let users = database.users();
+        let documents = database.documents();
+        let user = create_user(users.as_ref(), "alice").await;
+        let quotas = QuotaConfig::new(3, 13, 10).unwrap();
+
+        let writes = (0..8).map(|_| {
+            let documents = documents.clone();
+            tokio::spawn(async move {
+                documents
+                    .create_document_within_quota(
+                        Uuid::new_v4(),
+                        user.uuid,
+                        None,
+                        "Notes",
+                        "abc",
+                        &quotas,
+                    )
+                    .await
+                    .unwrap()
+            })
+        });
+        let mut created = Vec::new();
+        for write in writes.collect::<Vec<_>>() {
+            if let Ok(document) = write.await.unwrap() {
+                created.push(document);
+            }
+        }
+        assert_eq!(created.len(), 3);
+
+        // Growing every document at once only lets as many through as the storage has room for
+        let writes = created.iter().map(|document| {
+            let documents = documents.clone();
+            let uuid = document.uuid.unwrap();
+            tokio::spawn(async move {
+                documents
+                    .update_document_within_quota(uuid, user.uuid, None, "Notes", "abcde", &quotas)
+                    .await
+                    .unwrap()
+            })
+        });
+        let mut refused = 0;
+        for write in writes.collect::<Vec<_>>() {
+            if let Err(err) = write.await.unwrap() {
+                assert_eq!(
+                    err,
+                    QuotaError::StorageFull {
+                        max_storage_bytes: 13
+                    }
+                );
+                refused += 1;
+            }
+        }
+        assert_eq!(refused, 1);
+
+        let usage = documents.fetch_usage(user.uuid).await.unwrap();
+        assert_eq!((usage.document_count, usage.storage_bytes), (3, 13));
+    }
+
+    async fn disables_users_and_lists_them(database: Database) {
+        let users = database.users();
+        let alice = create_user(users.as_ref(), "alice").await;
+        let bob = create_user(users.as_ref(), "bob").await;
+        database
+            .documents()
+            .create_document(Uuid::new_v4(), alice.uuid, None, "Notes", "éé")
+            .await
+            .unwrap();
+        users
+            .create_user_session(alice.uuid, SESSION_DURATION)
+            .await
+            .unwrap();
+
+        let disabled = users.set_user_disabled(bob.uuid, true).await.unwrap();
+        assert!(disabled.is_disabled());
+        // Disabling again keeps when it first happened
+        let again = users.set_user_disabled(bob.uuid, true).await.unwrap();
+        assert_eq!(again.disabled_at, disabled.disabled_at);
+
+        let overviews = users.fetch_user_overviews().await.unwrap();
+        let counts: Vec<_> = overviews
+            .iter()
+            .map(|user| {
+                (
+                    user.email.as_str(),
+                    user.disabled_at.is_some(),
+                    user.document_count,
+                    user.storage_bytes,
+                    user.active_sessions,
+                )
+            })
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("alice@example.com", false, 1, 4, 1),
+                ("bob@example.com", true, 0, 0, 0)
+            ]
+        );
+
+        let enabled = users.set_user_disabled(bob.uuid, false).await.unwrap();
+        assert!(!enabled.is_disabled());
+        assert!(is_row_not_found(
+            users.set_user_disabled(Uuid::new_v4(), true).await
+        ));
+    }
+
+    async fn promotes_admins_and_counts_storage(database: Database) {
+        let users = database.users();
+        let alice = create_user(users.as_ref(), "alice").await;
+        let bob = create_user(users.as_ref(), "bob").await;
+        database
+            .documents()
+            .create_document(Uuid::new_v4(), alice.uuid, None, "Notes", "éé")
+            .await
+            .unwrap();
+
+        let emails = [
+            "alice@example.com".to_string(),
+            "nobody@example.com".to_string(),
+        ];
+        assert_eq!(users.promote_admins(&emails).await.unwrap(), 1);
+        // Admins already are, so nobody is promoted twice
+        assert_eq!(users.promote_admins(&emails).await.unwrap(), 0);
+        assert!(users
+            .fetch_user_by_uuid(alice.uuid)
+            .await
+            .unwrap()
+            .is_admin());
+
+        let bob = users.set_user_role(bob.uuid, "admin").await.unwrap();
+        assert!(bob.is_admin());
+        let bob = users.set_user_role(bob.uuid, "user").await.unwrap();
+        assert!(!bob.is_admin());
+
+        let storage_usage = users.fetch_storage_usage().await.unwrap();
+        assert_eq!(
+            (
+                storage_usage.user_count,
+                storage_usage.document_count,
+                storage_usage.storage_bytes
+            ),
+            (2, 1, 4)
+        );
+    }
+
+    async fn builds_one_export_at_a_time(database: Database) {
+        let users = database.users();
+        let exports = database.exports();
+        let user = create_user(users.as_ref(), "alice").await;
+        let expires_at = Utc::now() + TimeDelta::hours(1);
+
+        // Both requests pass any check made before inserting, the index turns one away
+        let (first, second) = tokio::join!(
+            exports.create_export(Uuid::new_v4(), user.uuid, expires_at),
+            exports.create_export(Uuid::new_v4(), user.uuid, expires_at),
+        );
+        let created: Vec<DataExport> = [first.unwrap(), second.unwrap()]
+            .into_iter()
+            .flatten()
+            .collect();
+        assert_eq!(created.len(), 1);
+
+        exports.fail_export(created[0].uuid).await.unwrap();
+        let stale = exports
+            .create_export(
+                Uuid::new_v4(),
+                user.uuid,
+                Utc::now() - TimeDelta::minutes(1),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stale.status, "pending");
+
+        // A build that outlived its expiry never finished and doesn't block the next one
+        assert!(exports
+            .create_export(Uuid::new_v4(), user.uuid, expires_at)
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
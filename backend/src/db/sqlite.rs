@@ -0,0 +1,1074 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::{Sqlite, SqliteExecutor, SqlitePool, Transaction};
+use uuid::Uuid;
+
+use crate::db::repository::{
+    BackupData, BackupRepository, DocumentRepository, ExportRepository, UserRepository,
+};
+use crate::models::data_export::DataExport;
+use crate::models::document::Document;
+use crate::models::user::User;
+use crate::models::user_overview::{StorageUsage, UserOverview};
+use crate::models::user_preferences::UserPreferences;
+use crate::models::user_session::UserSession;
+use crate::models::user_usage::UserUsage;
+use crate::utils::constants::{EXPORT_STATUS_FAILED, EXPORT_STATUS_PENDING, EXPORT_STATUS_READY};
+use crate::utils::quota::{QuotaConfig, QuotaError};
+
+// The repositories on a single SQLite file, for self-hosting without a PostgreSQL server.
+// The query macros only check against PostgreSQL, so these are checked by the shared
+// repository tests instead. Timestamps are compared with julianday since the text they are
+// stored as doesn't sort reliably
+#[derive(Clone)]
+pub struct SqliteRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        SqliteRepository { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl UserRepository for SqliteRepository {
+    async fn fetch_user_by_email(&self, email: &str) -> Result<User, sqlx::Error> {
+        sqlx::query_as::<_, User>(
+            "
+            SELECT * FROM users
+            WHERE email = ?1
+            ",
+        )
+        .bind(email)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn fetch_user_by_uuid(&self, uuid: Uuid) -> Result<User, sqlx::Error> {
+        sqlx::query_as::<_, User>(
+            "
+            SELECT * FROM users
+            WHERE uuid = ?1
+            ",
+        )
+        .bind(uuid)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn fetch_user_by_session_uuid(&self, session_uuid: Uuid) -> Result<User, sqlx::Error> {
+        sqlx::query_as::<_, User>(
+            "
+            SELECT u.uuid, u.username, u.email, u.created_at, u.updated_at, u.role, u.disabled_at,
+                u.deletion_scheduled_at, u.display_name, u.avatar_url
+            FROM users AS u
+            JOIN UserSessions AS s ON u.uuid = s.user_uuid
+            WHERE s.uuid = ?1 AND julianday(s.expires_at) > julianday('now')
+            ",
+        )
+        .bind(session_uuid)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn fetch_active_user_session(
+        &self,
+        session_uuid: Uuid,
+    ) -> Result<Option<UserSession>, sqlx::Error> {
+        sqlx::query_as::<_, UserSession>(
+            "
+            SELECT * FROM UserSessions
+            WHERE uuid = ?1 AND julianday(expires_at) > julianday('now')
+            ",
+        )
+        .bind(session_uuid)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn fetch_active_user_sessions(
+        &self,
+        user_uuid: Uuid,
+    ) -> Result<Vec<UserSession>, sqlx::Error> {
+        sqlx::query_as::<_, UserSession>(
+            "
+            SELECT * FROM UserSessions
+            WHERE user_uuid = ?1 AND julianday(expires_at) > julianday('now')
+                AND replaced_by IS NULL
+            ORDER BY julianday(created_at)
+            ",
+        )
+        .bind(user_uuid)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn create_user(
+        &self,
+        uuid: Uuid,
+        username: &str,
+        email: &str,
+    ) -> Result<User, sqlx::Error> {
+        let now = Utc::now();
+
+        sqlx::query_as::<_, User>(
+            "
+            INSERT INTO users (uuid, username, email, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?4)
+            RETURNING *
+            ",
+        )
+        .bind(uuid)
+        .bind(username)
+        .bind(email)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn create_user_session(
+        &self,
+        user_uuid: Uuid,
+        session_duration: Duration,
+    ) -> Result<UserSession, sqlx::Error> {
+        let created_at = Utc::now();
+        let expires_at = created_at + session_duration;
+
+        sqlx::query_as::<_, UserSession>(
+            "
+            INSERT INTO UserSessions (uuid, user_uuid, created_at, expires_at, issued_at)
+            VALUES (?1, ?2, ?3, ?4, ?3)
+            RETURNING *
+            ",
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_uuid)
+        .bind(created_at)
+        .bind(expires_at)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn extend_user_session(
+        &self,
+        session_uuid: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<UserSession, sqlx::Error> {
+        sqlx::query_as::<_, UserSession>(
+            "
+            UPDATE UserSessions
+            SET expires_at = ?1
+            WHERE uuid = ?2
+            RETURNING *
+            ",
+        )
+        .bind(expires_at)
+        .bind(session_uuid)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn rotate_user_session(
+        &self,
+        session_uuid: Uuid,
+        issued_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+        grace_expires_at: DateTime<Utc>,
+    ) -> Result<UserSession, sqlx::Error> {
+        let new_uuid = Uuid::new_v4();
+        let mut tx = self.pool.begin().await?;
+
+        let replaced = sqlx::query(
+            "
+            UPDATE UserSessions
+            SET replaced_by = ?1, expires_at = ?2
+            WHERE uuid = ?3 AND replaced_by IS NULL
+            ",
+        )
+        .bind(new_uuid)
+        .bind(grace_expires_at)
+        .bind(session_uuid)
+        .execute(&mut *tx)
+        .await?;
+
+        // Another request rotated it first, hand out the session that one created
+        if replaced.rows_affected() == 0 {
+            let user_session = sqlx::query_as::<_, UserSession>(
+                "
+                SELECT s.* FROM UserSessions AS s
+                JOIN UserSessions AS replaced ON replaced.replaced_by = s.uuid
+                WHERE replaced.uuid = ?1
+                ",
+            )
+            .bind(session_uuid)
+            .fetch_one(&mut *tx)
+            .await?;
+            tx.commit().await?;
+
+            return Ok(user_session);
+        }
+
+        let user_session = sqlx::query_as::<_, UserSession>(
+            "
+            INSERT INTO UserSessions (uuid, user_uuid, created_at, expires_at, issued_at)
+            SELECT ?1, user_uuid, created_at, ?2, ?3
+            FROM UserSessions
+            WHERE uuid = ?4
+            RETURNING *
+            ",
+        )
+        .bind(new_uuid)
+        .bind(expires_at)
+        .bind(issued_at)
+        .bind(session_uuid)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(user_session)
+    }
+
+    async fn update_user_profile(
+        &self,
+        uuid: Uuid,
+        display_name: Option<&str>,
+        avatar_url: Option<&str>,
+    ) -> Result<User, sqlx::Error> {
+        sqlx::query_as::<_, User>(
+            "
+            UPDATE users
+            SET display_name = ?1, avatar_url = ?2, updated_at = ?3
+            WHERE uuid = ?4
+            RETURNING *
+            ",
+        )
+        .bind(display_name)
+        .bind(avatar_url)
+        .bind(Utc::now())
+        .bind(uuid)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn fetch_user_preferences(
+        &self,
+        user_uuid: Uuid,
+    ) -> Result<UserPreferences, sqlx::Error> {
+        let preferences = sqlx::query_as::<_, UserPreferences>(
+            "
+            SELECT * FROM UserPreferences
+            WHERE user_uuid = ?1
+            ",
+        )
+        .bind(user_uuid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(preferences.unwrap_or_else(|| UserPreferences::defaults(user_uuid)))
+    }
+
+    async fn update_user_preferences(
+        &self,
+        preferences: &UserPreferences,
+    ) -> Result<UserPreferences, sqlx::Error> {
+        sqlx::query_as::<_, UserPreferences>(
+            "
+            INSERT INTO UserPreferences (
+                user_uuid, theme, editor_font_size, keybinding_mode, default_export_format,
+                preview_layout, updated_at
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT (user_uuid) DO UPDATE
+            SET theme = excluded.theme,
+                editor_font_size = excluded.editor_font_size,
+                keybinding_mode = excluded.keybinding_mode,
+                default_export_format = excluded.default_export_format,
+                preview_layout = excluded.preview_layout,
+                updated_at = excluded.updated_at
+            RETURNING *
+            ",
+        )
+        .bind(preferences.user_uuid)
+        .bind(&preferences.theme)
+        .bind(preferences.editor_font_size)
+        .bind(&preferences.keybinding_mode)
+        .bind(&preferences.default_export_format)
+        .bind(&preferences.preview_layout)
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn delete_user(&self, uuid: Uuid) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Everything referencing the user goes first, SQLite enforces the foreign keys
+        sqlx::query(
+            "
+            DELETE FROM UserSessions
+            WHERE user_uuid = ?1
+            ",
+        )
+        .bind(uuid)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "
+            DELETE FROM documents
+            WHERE user_uuid = ?1
+            ",
+        )
+        .bind(uuid)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "
+            DELETE FROM users
+            WHERE uuid = ?1
+            ",
+        )
+        .bind(uuid)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn schedule_user_deletion(
+        &self,
+        uuid: Uuid,
+        deletion_scheduled_at: Option<DateTime<Utc>>,
+    ) -> Result<User, sqlx::Error> {
+        sqlx::query_as::<_, User>(
+            "
+            UPDATE users
+            SET deletion_scheduled_at = ?1, updated_at = ?2
+            WHERE uuid = ?3
+            RETURNING *
+            ",
+        )
+        .bind(deletion_scheduled_at)
+        .bind(Utc::now())
+        .bind(uuid)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn fetch_users_due_for_deletion(&self) -> Result<Vec<Uuid>, sqlx::Error> {
+        sqlx::query_scalar(
+            "
+            SELECT uuid FROM users
+            WHERE julianday(deletion_scheduled_at) <= julianday('now')
+            ORDER BY julianday(deletion_scheduled_at)
+            ",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn fetch_user_overviews(&self) -> Result<Vec<UserOverview>, sqlx::Error> {
+        sqlx::query_as::<_, UserOverview>(
+            "
+            SELECT
+                u.uuid, u.username, u.email, u.role, u.disabled_at, u.created_at,
+                (SELECT COUNT(*) FROM Documents AS d WHERE d.user_uuid = u.uuid) AS document_count,
+                (
+                    SELECT COALESCE(SUM(length(CAST(d.content AS BLOB))), 0)
+                    FROM Documents AS d WHERE d.user_uuid = u.uuid
+                ) AS storage_bytes,
+                (
+                    SELECT COUNT(*) FROM UserSessions AS s
+                    WHERE s.user_uuid = u.uuid AND julianday(s.expires_at) > julianday('now')
+                ) AS active_sessions
+            FROM users AS u
+            ORDER BY julianday(u.created_at)
+            ",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn set_user_disabled(&self, uuid: Uuid, disabled: bool) -> Result<User, sqlx::Error> {
+        sqlx::query_as::<_, User>(
+            "
+            UPDATE users
+            SET disabled_at = CASE WHEN ?1 THEN COALESCE(disabled_at, ?2) ELSE NULL END,
+                updated_at = ?2
+            WHERE uuid = ?3
+            RETURNING *
+            ",
+        )
+        .bind(disabled)
+        .bind(Utc::now())
+        .bind(uuid)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn set_user_role(&self, uuid: Uuid, role: &str) -> Result<User, sqlx::Error> {
+        sqlx::query_as::<_, User>(
+            "
+            UPDATE users
+            SET role = ?1, updated_at = ?2
+            WHERE uuid = ?3
+            RETURNING *
+            ",
+        )
+        .bind(role)
+        .bind(Utc::now())
+        .bind(uuid)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn promote_admins(&self, emails: &[String]) -> Result<u64, sqlx::Error> {
+        // SQLite has no arrays to bind, so the emails go in as a JSON array
+        let result = sqlx::query(
+            "
+            UPDATE users
+            SET role = 'admin', updated_at = ?1
+            WHERE email IN (SELECT value FROM json_each(?2)) AND role <> 'admin'
+            ",
+        )
+        .bind(Utc::now())
+        .bind(serde_json::to_string(emails).expect("a list of strings serializes"))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn fetch_storage_usage(&self) -> Result<StorageUsage, sqlx::Error> {
+        sqlx::query_as::<_, StorageUsage>(
+            "
+            SELECT
+                (SELECT COUNT(*) FROM users) AS user_count,
+                COUNT(d.uuid) AS document_count,
+                COALESCE(SUM(length(CAST(d.content AS BLOB))), 0) AS storage_bytes
+            FROM Documents AS d
+            ",
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn delete_user_session(&self, session_uuid: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "
+            DELETE FROM UserSessions
+            WHERE uuid = ?1
+            ",
+        )
+        .bind(session_uuid)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_user_sessions(&self, user_uuid: Uuid) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "
+            DELETE FROM UserSessions
+            WHERE user_uuid = ?1
+            ",
+        )
+        .bind(user_uuid)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn count_active_sessions(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "
+            SELECT COUNT(*) FROM UserSessions
+            WHERE julianday(expires_at) > julianday('now') AND replaced_by IS NULL
+            ",
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn delete_expired_sessions(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "
+            DELETE FROM UserSessions
+            WHERE julianday(expires_at) < julianday('now')
+            ",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl DocumentRepository for SqliteRepository {
+    async fn fetch_document_by_uuid(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+    ) -> Result<Document, sqlx::Error> {
+        fetch_document_by_uuid(&self.pool, uuid, user_uuid, organisation_uuid).await
+    }
+
+    async fn fetch_all_documents_for_user(
+        &self,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+    ) -> Result<Vec<Document>, sqlx::Error> {
+        sqlx::query_as::<_, Document>(
+            "
+            SELECT * FROM documents
+            WHERE (?2 IS NULL AND organisation_uuid IS NULL AND user_uuid = ?1)
+                OR organisation_uuid = ?2
+            ",
+        )
+        .bind(user_uuid)
+        .bind(organisation_uuid)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn fetch_documents_by_author(
+        &self,
+        user_uuid: Uuid,
+    ) -> Result<Vec<Document>, sqlx::Error> {
+        sqlx::query_as::<_, Document>(
+            "SELECT * FROM documents WHERE user_uuid = ?1 ORDER BY julianday(created_at)",
+        )
+        .bind(user_uuid)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn create_document(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        title: &str,
+        content: &str,
+    ) -> Result<Document, sqlx::Error> {
+        create_document(
+            &self.pool,
+            uuid,
+            user_uuid,
+            organisation_uuid,
+            title,
+            content,
+        )
+        .await
+    }
+
+    async fn update_document(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        title: &str,
+        content: &str,
+    ) -> Result<Document, sqlx::Error> {
+        update_document(
+            &self.pool,
+            uuid,
+            user_uuid,
+            organisation_uuid,
+            title,
+            content,
+        )
+        .await
+    }
+
+    async fn create_document_within_quota(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        title: &str,
+        content: &str,
+        quotas: &QuotaConfig,
+    ) -> Result<Result<Document, QuotaError>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        lock_for_writing(&mut tx).await?;
+
+        let usage = fetch_usage(&mut *tx, user_uuid).await?;
+        if let Err(err) = quotas.check(&usage, true, 0, content.len() as u64) {
+            return Ok(Err(err));
+        }
+
+        let document =
+            create_document(&mut *tx, uuid, user_uuid, organisation_uuid, title, content).await?;
+        tx.commit().await?;
+
+        Ok(Ok(document))
+    }
+
+    async fn update_document_within_quota(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        title: &str,
+        content: &str,
+        quotas: &QuotaConfig,
+    ) -> Result<Result<Document, QuotaError>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        lock_for_writing(&mut tx).await?;
+
+        let existing = fetch_document_by_uuid(&mut *tx, uuid, user_uuid, organisation_uuid).await?;
+        let usage = fetch_usage(&mut *tx, existing.user_uuid.unwrap_or(user_uuid)).await?;
+        let replaced_bytes = existing.content.as_deref().unwrap_or_default().len() as u64;
+        if let Err(err) = quotas.check(&usage, false, replaced_bytes, content.len() as u64) {
+            return Ok(Err(err));
+        }
+
+        let document =
+            update_document(&mut *tx, uuid, user_uuid, organisation_uuid, title, content).await?;
+        tx.commit().await?;
+
+        Ok(Ok(document))
+    }
+
+    async fn fetch_usage(&self, user_uuid: Uuid) -> Result<UserUsage, sqlx::Error> {
+        fetch_usage(&self.pool, user_uuid).await
+    }
+
+    async fn delete_document(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        organisation_uuid: Option<Uuid>,
+        can_manage: bool,
+    ) -> Result<Document, sqlx::Error> {
+        sqlx::query_as::<_, Document>(
+            "
+            DELETE FROM documents
+            WHERE uuid = ?1 AND (
+                (?3 IS NULL AND organisation_uuid IS NULL AND user_uuid = ?2)
+                OR (organisation_uuid = ?3 AND (?4 OR user_uuid = ?2))
+            )
+            RETURNING *
+            ",
+        )
+        .bind(uuid)
+        .bind(user_uuid)
+        .bind(organisation_uuid)
+        .bind(can_manage)
+        .fetch_one(&self.pool)
+        .await
+    }
+}
+
+#[async_trait::async_trait]
+impl BackupRepository for SqliteRepository {
+    async fn fetch_backup(&self) -> Result<BackupData, sqlx::Error> {
+        // Reads inside one transaction see the same snapshot
+        let mut tx = self.pool.begin().await?;
+
+        let users = sqlx::query_as::<_, User>(
+            "
+            SELECT * FROM users
+            ORDER BY julianday(created_at), uuid
+            ",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let preferences = sqlx::query_as::<_, UserPreferences>(
+            "
+            SELECT * FROM UserPreferences
+            ORDER BY user_uuid
+            ",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let sessions = sqlx::query_as::<_, UserSession>(
+            "
+            SELECT * FROM UserSessions
+            ORDER BY julianday(created_at), uuid
+            ",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let documents = sqlx::query_as::<_, Document>(
+            "
+            SELECT * FROM documents
+            ORDER BY julianday(created_at), uuid
+            ",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(BackupData {
+            users,
+            preferences,
+            sessions,
+            documents,
+            ..BackupData::default()
+        })
+    }
+
+    async fn count_users(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "
+            SELECT COUNT(*) FROM users
+            ",
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    // The restore command refuses backups holding organisations or two-factor settings, there
+    // are no tables for them here
+    async fn restore_backup(&self, backup: &BackupData) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        for user in &backup.users {
+            sqlx::query(
+                "
+                INSERT INTO users (
+                    uuid, username, email, created_at, updated_at, role, disabled_at,
+                    deletion_scheduled_at, display_name, avatar_url
+                )
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                ",
+            )
+            .bind(user.uuid)
+            .bind(&user.username)
+            .bind(&user.email)
+            .bind(user.created_at)
+            .bind(user.updated_at)
+            .bind(&user.role)
+            .bind(user.disabled_at)
+            .bind(user.deletion_scheduled_at)
+            .bind(&user.display_name)
+            .bind(&user.avatar_url)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for session in &backup.sessions {
+            sqlx::query(
+                "
+                INSERT INTO UserSessions
+                    (uuid, user_uuid, created_at, expires_at, issued_at, replaced_by)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                ",
+            )
+            .bind(session.uuid)
+            .bind(session.user_uuid)
+            .bind(session.created_at)
+            .bind(session.expires_at)
+            .bind(session.issued_at)
+            .bind(session.replaced_by)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for preferences in &backup.preferences {
+            sqlx::query(
+                "
+                INSERT INTO UserPreferences (
+                    user_uuid, theme, editor_font_size, keybinding_mode, default_export_format,
+                    preview_layout, updated_at
+                )
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, COALESCE(?7, strftime('%Y-%m-%dT%H:%M:%fZ', 'now')))
+                ",
+            )
+            .bind(preferences.user_uuid)
+            .bind(&preferences.theme)
+            .bind(preferences.editor_font_size)
+            .bind(&preferences.keybinding_mode)
+            .bind(&preferences.default_export_format)
+            .bind(&preferences.preview_layout)
+            .bind(preferences.updated_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for document in &backup.documents {
+            sqlx::query(
+                "
+                INSERT INTO documents
+                    (uuid, user_uuid, organisation_uuid, title, content, created_at, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                ",
+            )
+            .bind(document.uuid)
+            .bind(document.user_uuid)
+            .bind(document.organisation_uuid)
+            .bind(&document.title)
+            .bind(&document.content)
+            .bind(document.created_at)
+            .bind(document.updated_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ExportRepository for SqliteRepository {
+    async fn create_export(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<DataExport>, sqlx::Error> {
+        sqlx::query(
+            "
+            UPDATE DataExports
+            SET status = ?2
+            WHERE user_uuid = ?1 AND status = ?3
+                AND julianday(expires_at) <= julianday('now')
+            ",
+        )
+        .bind(user_uuid)
+        .bind(EXPORT_STATUS_FAILED)
+        .bind(EXPORT_STATUS_PENDING)
+        .execute(&self.pool)
+        .await?;
+
+        let result = sqlx::query_as::<_, DataExport>(
+            "
+            INSERT INTO DataExports (uuid, user_uuid, created_at, expires_at)
+            VALUES (?1, ?2, ?3, ?4)
+            RETURNING uuid, user_uuid, status, created_at, completed_at, expires_at, size_bytes
+            ",
+        )
+        .bind(uuid)
+        .bind(user_uuid)
+        .bind(Utc::now())
+        .bind(expires_at)
+        .fetch_one(&self.pool)
+        .await;
+
+        match result {
+            Ok(export) => Ok(Some(export)),
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn fetch_export(&self, uuid: Uuid, user_uuid: Uuid) -> Result<DataExport, sqlx::Error> {
+        sqlx::query_as::<_, DataExport>(
+            "
+            SELECT uuid, user_uuid, status, created_at, completed_at, expires_at, size_bytes
+            FROM DataExports
+            WHERE uuid = ?1 AND user_uuid = ?2 AND julianday(expires_at) > julianday('now')
+            ",
+        )
+        .bind(uuid)
+        .bind(user_uuid)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn fetch_export_archive(
+        &self,
+        uuid: Uuid,
+        user_uuid: Uuid,
+    ) -> Result<Option<Vec<u8>>, sqlx::Error> {
+        let archive: Option<Option<Vec<u8>>> = sqlx::query_scalar(
+            "
+            SELECT archive FROM DataExports
+            WHERE uuid = ?1 AND user_uuid = ?2 AND status = ?3
+                AND julianday(expires_at) > julianday('now')
+            ",
+        )
+        .bind(uuid)
+        .bind(user_uuid)
+        .bind(EXPORT_STATUS_READY)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(archive.flatten())
+    }
+
+    async fn complete_export(
+        &self,
+        uuid: Uuid,
+        archive: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "
+            UPDATE DataExports
+            SET status = ?1, archive = ?2, size_bytes = ?3, completed_at = ?4, expires_at = ?5
+            WHERE uuid = ?6
+            ",
+        )
+        .bind(EXPORT_STATUS_READY)
+        .bind(archive)
+        .bind(archive.len() as i64)
+        .bind(Utc::now())
+        .bind(expires_at)
+        .bind(uuid)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn fail_export(&self, uuid: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "
+            UPDATE DataExports
+            SET status = ?1, completed_at = ?2
+            WHERE uuid = ?3
+            ",
+        )
+        .bind(EXPORT_STATUS_FAILED)
+        .bind(Utc::now())
+        .bind(uuid)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_expired_exports(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "
+            DELETE FROM DataExports
+            WHERE julianday(expires_at) < julianday('now')
+            ",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+// Document queries shared by the plain and quota checked writes, which run them on a
+// transaction
+async fn fetch_document_by_uuid(
+    executor: impl SqliteExecutor<'_>,
+    uuid: Uuid,
+    user_uuid: Uuid,
+    organisation_uuid: Option<Uuid>,
+) -> Result<Document, sqlx::Error> {
+    sqlx::query_as::<_, Document>(
+        "
+        SELECT * FROM documents
+        WHERE uuid = ?1 AND (
+            (?3 IS NULL AND organisation_uuid IS NULL AND user_uuid = ?2)
+            OR organisation_uuid = ?3
+        )
+        ",
+    )
+    .bind(uuid)
+    .bind(user_uuid)
+    .bind(organisation_uuid)
+    .fetch_one(executor)
+    .await
+}
+
+async fn create_document(
+    executor: impl SqliteExecutor<'_>,
+    uuid: Uuid,
+    user_uuid: Uuid,
+    organisation_uuid: Option<Uuid>,
+    title: &str,
+    content: &str,
+) -> Result<Document, sqlx::Error> {
+    let now = Utc::now();
+
+    sqlx::query_as::<_, Document>(
+        "
+        INSERT INTO documents
+            (uuid, user_uuid, organisation_uuid, title, content, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)
+        RETURNING *
+        ",
+    )
+    .bind(uuid)
+    .bind(user_uuid)
+    .bind(organisation_uuid)
+    .bind(title)
+    .bind(content)
+    .bind(now)
+    .fetch_one(executor)
+    .await
+}
+
+async fn update_document(
+    executor: impl SqliteExecutor<'_>,
+    uuid: Uuid,
+    user_uuid: Uuid,
+    organisation_uuid: Option<Uuid>,
+    title: &str,
+    content: &str,
+) -> Result<Document, sqlx::Error> {
+    sqlx::query_as::<_, Document>(
+        "
+        UPDATE documents
+        SET title = ?1, content = ?2, updated_at = ?3
+        WHERE uuid = ?4 AND (
+            (?6 IS NULL AND organisation_uuid IS NULL AND user_uuid = ?5)
+            OR organisation_uuid = ?6
+        )
+        RETURNING *
+        ",
+    )
+    .bind(title)
+    .bind(content)
+    .bind(Utc::now())
+    .bind(uuid)
+    .bind(user_uuid)
+    .bind(organisation_uuid)
+    .fetch_one(executor)
+    .await
+}
+
+async fn fetch_usage(
+    executor: impl SqliteExecutor<'_>,
+    user_uuid: Uuid,
+) -> Result<UserUsage, sqlx::Error> {
+    // length() counts characters of text, the blob cast makes it count bytes
+    sqlx::query_as::<_, UserUsage>(
+        "
+        SELECT
+            COUNT(*) AS document_count,
+            COALESCE(SUM(length(CAST(content AS BLOB))), 0) AS storage_bytes,
+            COALESCE(MAX(length(CAST(content AS BLOB))), 0) AS largest_document_bytes
+        FROM documents
+        WHERE user_uuid = ?1
+        ",
+    )
+    .bind(user_uuid)
+    .fetch_one(executor)
+    .await
+}
+
+// Transactions start out reading, and two that read before writing can't both upgrade. A
+// write that changes nothing takes the database's write lock up front, so other writers wait
+// for the transaction to end instead
+async fn lock_for_writing(tx: &mut Transaction<'_, Sqlite>) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET uuid = uuid WHERE 0")
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use std::str::FromStr;
+
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::{DatabaseBackend, DatabaseConfig};
+use crate::db::connection::{Database, MIGRATOR};
+
+// Databases for tests. SQLite ones live in memory, PostgreSQL ones need TEST_DATABASE_URL to
+// point at a server the tests may create schemas on
+
+pub async fn sqlite() -> Database {
+    Database::connect(&DatabaseConfig {
+        backend: DatabaseBackend::Sqlite,
+        url: "sqlite::memory:".to_string(),
+        max_connections: 1,
+    })
+    .await
+    .expect("in-memory SQLite database")
+}
+
+// A freshly migrated schema of its own, so tests can run in parallel against one server
+pub struct PostgresSchema {
+    pub database: Database,
+    admin_pool: PgPool,
+    name: String,
+}
+
+impl PostgresSchema {
+    // None when TEST_DATABASE_URL isn't set, the caller skips the test then. CI always sets
+    // it, so there a missing URL fails the test instead of letting it pass without running
+    pub async fn create() -> Option<Self> {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            if std::env::var_os("CI").is_some() {
+                panic!("TEST_DATABASE_URL must be set when CI is");
+            }
+            eprintln!("TEST_DATABASE_URL isn't set, skipping the PostgreSQL test");
+            return None;
+        };
+
+        let admin_pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect(&url)
+            .await
+            .expect("TEST_DATABASE_URL is reachable");
+        let name = format!("test_{}", Uuid::new_v4().simple());
+        sqlx::query(&format!("CREATE SCHEMA {name}"))
+            .execute(&admin_pool)
+            .await
+            .expect("test schema is created");
+
+        let options = PgConnectOptions::from_str(&url)
+            .expect("TEST_DATABASE_URL is valid")
+            .options([("search_path", name.as_str())]);
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect_with(options)
+            .await
+            .expect("test schema is reachable");
+        MIGRATOR.run(&pool).await.expect("test schema migrates");
+
+        Some(PostgresSchema {
+            database: Database::Postgres(pool),
+            admin_pool,
+            name,
+        })
+    }
+
+    // Only reached when the test passed, a failed test leaves its schema behind to look at
+    pub async fn drop(self) {
+        self.database.close().await;
+        sqlx::query(&format!("DROP SCHEMA {} CASCADE", self.name))
+            .execute(&self.admin_pool)
+            .await
+            .expect("test schema is dropped");
+    }
+}
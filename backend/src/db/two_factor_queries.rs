@@ -0,0 +1,287 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::models::login_challenge::LoginChallenge;
+use crate::models::user_totp::UserTotp;
+
+pub async fn fetch_user_totp(
+    pool: &PgPool,
+    user_uuid: Uuid,
+) -> Result<Option<UserTotp>, sqlx::Error> {
+    let user_totp = sqlx::query_as!(
+        UserTotp,
+        "
+        SELECT * FROM UserTotp
+        WHERE user_uuid = $1
+        ",
+        user_uuid
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(user_totp)
+}
+
+// Stores a new secret that only takes effect once the user confirms it with a valid code.
+// An already enabled secret is never overwritten
+pub async fn upsert_pending_user_totp(
+    pool: &PgPool,
+    user_uuid: Uuid,
+    secret: &str,
+) -> Result<UserTotp, sqlx::Error> {
+    let user_totp = sqlx::query_as!(
+        UserTotp,
+        "
+        INSERT INTO UserTotp (user_uuid, secret, enabled, created_at, updated_at)
+        VALUES ($1, $2, FALSE, DEFAULT, DEFAULT)
+        ON CONFLICT (user_uuid) DO UPDATE
+        SET secret = EXCLUDED.secret, last_used_step = NULL, updated_at = CURRENT_TIMESTAMP
+        WHERE UserTotp.enabled = FALSE
+        RETURNING *
+        ",
+        user_uuid,
+        secret
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(user_totp)
+}
+
+// Enables the pending secret and replaces any existing recovery codes in one transaction
+pub async fn enable_user_totp(
+    pool: &PgPool,
+    user_uuid: Uuid,
+    used_step: i64,
+    recovery_code_hashes: &[String],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "
+        UPDATE UserTotp
+        SET enabled = TRUE, last_used_step = $1, updated_at = CURRENT_TIMESTAMP
+        WHERE user_uuid = $2
+        ",
+        used_step,
+        user_uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    replace_recovery_codes_in(&mut tx, user_uuid, recovery_code_hashes).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Records that the code for a time step was used. Returns false if the step (or a later
+// one) was already used, which means the code is being replayed
+pub async fn mark_totp_step_used(
+    pool: &PgPool,
+    user_uuid: Uuid,
+    step: i64,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "
+        UPDATE UserTotp
+        SET last_used_step = $1
+        WHERE user_uuid = $2 AND (last_used_step IS NULL OR last_used_step < $1)
+        ",
+        step,
+        user_uuid
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn delete_user_totp(pool: &PgPool, user_uuid: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM RecoveryCodes
+        WHERE user_uuid = $1
+        ",
+        user_uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM UserTotp
+        WHERE user_uuid = $1
+        ",
+        user_uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn replace_recovery_codes(
+    pool: &PgPool,
+    user_uuid: Uuid,
+    recovery_code_hashes: &[String],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    replace_recovery_codes_in(&mut tx, user_uuid, recovery_code_hashes).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+async fn replace_recovery_codes_in(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    user_uuid: Uuid,
+    recovery_code_hashes: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        DELETE FROM RecoveryCodes
+        WHERE user_uuid = $1
+        ",
+        user_uuid
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    let uuids: Vec<Uuid> = recovery_code_hashes
+        .iter()
+        .map(|_| Uuid::new_v4())
+        .collect();
+
+    sqlx::query!(
+        "
+        INSERT INTO RecoveryCodes (uuid, user_uuid, code_hash)
+        SELECT code.uuid, $1, code.code_hash
+        FROM UNNEST($2::uuid[], $3::text[]) AS code(uuid, code_hash)
+        ",
+        user_uuid,
+        &uuids,
+        recovery_code_hashes
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+// Consumes a recovery code. Returns false if the code doesn't exist or was already used
+pub async fn use_recovery_code(
+    pool: &PgPool,
+    user_uuid: Uuid,
+    code_hash: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "
+        UPDATE RecoveryCodes
+        SET used_at = NOW()
+        WHERE user_uuid = $1 AND code_hash = $2 AND used_at IS NULL
+        ",
+        user_uuid,
+        code_hash
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn count_unused_recovery_codes(
+    pool: &PgPool,
+    user_uuid: Uuid,
+) -> Result<i64, sqlx::Error> {
+    let count = sqlx::query_scalar!(
+        "
+        SELECT COUNT(*) FROM RecoveryCodes
+        WHERE user_uuid = $1 AND used_at IS NULL
+        ",
+        user_uuid
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count.unwrap_or(0))
+}
+
+pub async fn create_login_challenge(
+    pool: &PgPool,
+    user_uuid: Uuid,
+    challenge_duration: Duration,
+) -> Result<LoginChallenge, sqlx::Error> {
+    let expires_at = Utc::now() + challenge_duration;
+
+    let login_challenge = sqlx::query_as!(
+        LoginChallenge,
+        "
+        INSERT INTO LoginChallenges (uuid, user_uuid, attempts, created_at, expires_at)
+        VALUES ($1, $2, 0, DEFAULT, $3)
+        RETURNING *
+        ",
+        Uuid::new_v4(),
+        user_uuid,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(login_challenge)
+}
+
+// Fetches a challenge that hasn't expired and counts this as an attempt to answer it
+pub async fn use_login_challenge_attempt(
+    pool: &PgPool,
+    uuid: Uuid,
+) -> Result<Option<LoginChallenge>, sqlx::Error> {
+    let login_challenge = sqlx::query_as!(
+        LoginChallenge,
+        "
+        UPDATE LoginChallenges
+        SET attempts = attempts + 1
+        WHERE uuid = $1 AND expires_at > NOW()
+        RETURNING *
+        ",
+        uuid
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(login_challenge)
+}
+
+pub async fn delete_login_challenge(pool: &PgPool, uuid: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        DELETE FROM LoginChallenges
+        WHERE uuid = $1
+        ",
+        uuid
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_expired_login_challenges(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        DELETE FROM LoginChallenges
+        WHERE expires_at < NOW()
+        "
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -1,8 +1,10 @@
+use chrono::{DateTime, Utc};
 use sqlx::PgPool;
 use std::time::Duration;
 use uuid::Uuid;
 
 use crate::models::user::User;
+use crate::models::user_preferences::UserPreferences;
 use crate::models::user_session::UserSession;
 
 pub async fn fetch_user_by_email(pool: &PgPool, email: &str) -> Result<User, sqlx::Error> {
@@ -20,6 +22,21 @@ pub async fn fetch_user_by_email(pool: &PgPool, email: &str) -> Result<User, sql
     Ok(user)
 }
 
+pub async fn fetch_user_by_uuid(pool: &PgPool, uuid: Uuid) -> Result<User, sqlx::Error> {
+    let user = sqlx::query_as!(
+        User,
+        "
+        SELECT * FROM users
+        WHERE uuid = $1
+        ",
+        uuid
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(user)
+}
+
 pub async fn fetch_user_by_session_uuid(
     pool: &PgPool,
     session_uuid: Uuid,
@@ -27,16 +44,13 @@ pub async fn fetch_user_by_session_uuid(
     let user = sqlx::query_as!(
         User,
         "
-        SELECT u.uuid, u.username, u.email, u.created_at, u.updated_at
+        SELECT u.uuid, u.username, u.email, u.created_at, u.updated_at, u.role, u.disabled_at,
+            u.deletion_scheduled_at, u.display_name, u.avatar_url
         FROM users AS u
         LEFT JOIN UserSessions AS s ON u.uuid = s.user_uuid
-        WHERE s.uuid = $1 AND s.expires_at > $2
+        WHERE s.uuid = $1 AND s.expires_at > NOW()
         ",
-        session_uuid,
-        chrono::offset::Utc::now()
-            .naive_utc()
-            .timestamp()
-            .to_string()
+        session_uuid
     )
     .fetch_one(pool)
     .await?;
@@ -44,24 +58,45 @@ pub async fn fetch_user_by_session_uuid(
     Ok(user)
 }
 
-pub async fn fetch_user_session_by_user_uuid(
+pub async fn fetch_active_user_session(
     pool: &PgPool,
-    user_uuid: Uuid,
-) -> Result<UserSession, sqlx::Error> {
+    session_uuid: Uuid,
+) -> Result<Option<UserSession>, sqlx::Error> {
     let user_session = sqlx::query_as!(
         UserSession,
         "
         SELECT * FROM UserSessions
-        WHERE user_uuid = $1
+        WHERE uuid = $1 AND expires_at > NOW()
         ",
-        user_uuid
+        session_uuid
     )
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await?;
 
     Ok(user_session)
 }
 
+// Sessions the user can still sign in with, rotated identifiers in their grace period are
+// left out
+pub async fn fetch_active_user_sessions(
+    pool: &PgPool,
+    user_uuid: Uuid,
+) -> Result<Vec<UserSession>, sqlx::Error> {
+    let user_sessions = sqlx::query_as!(
+        UserSession,
+        "
+        SELECT * FROM UserSessions
+        WHERE user_uuid = $1 AND expires_at > NOW() AND replaced_by IS NULL
+        ORDER BY created_at
+        ",
+        user_uuid
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(user_sessions)
+}
+
 pub async fn create_user(
     pool: &PgPool,
     uuid: Uuid,
@@ -91,19 +126,19 @@ pub async fn create_user_session(
     session_duration: Duration,
 ) -> Result<UserSession, sqlx::Error> {
     let uuid = Uuid::new_v4();
-    let created_at_timestamp = chrono::offset::Utc::now().naive_utc().timestamp();
-    let expires_at_timestamp = created_at_timestamp + session_duration.as_secs() as i64;
+    let created_at = Utc::now();
+    let expires_at = created_at + session_duration;
 
     sqlx::query_as!(
         UserSession,
         "
-        INSERT INTO UserSessions (uuid, user_uuid, created_at, expires_at)
-        VALUES ($1, $2, $3, $4)
+        INSERT INTO UserSessions (uuid, user_uuid, created_at, expires_at, issued_at)
+        VALUES ($1, $2, $3, $4, $3)
         ",
         uuid,
         user_uuid,
-        created_at_timestamp.to_string(),
-        expires_at_timestamp.to_string(),
+        created_at,
+        expires_at,
     )
     .execute(pool)
     .await?;
@@ -122,17 +157,314 @@ pub async fn create_user_session(
     Ok(user_session)
 }
 
+pub async fn extend_user_session(
+    pool: &PgPool,
+    session_uuid: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<UserSession, sqlx::Error> {
+    let user_session = sqlx::query_as!(
+        UserSession,
+        "
+        UPDATE UserSessions
+        SET expires_at = $1
+        WHERE uuid = $2
+        RETURNING *
+        ",
+        expires_at,
+        session_uuid
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(user_session)
+}
+
+// Replaces a session with a new identifier that keeps the original login time. The old
+// identifier stays valid until grace_expires_at so requests already in flight still succeed.
+// When concurrent requests rotate the same session only the first one does, the others get
+// the session it created
+pub async fn rotate_user_session(
+    pool: &PgPool,
+    session_uuid: Uuid,
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    grace_expires_at: DateTime<Utc>,
+) -> Result<UserSession, sqlx::Error> {
+    let new_uuid = Uuid::new_v4();
+    let mut tx = pool.begin().await?;
+
+    // Locks the row, a concurrent rotation waits here and then finds it already replaced
+    let replaced = sqlx::query!(
+        "
+        UPDATE UserSessions
+        SET replaced_by = $1, expires_at = $2
+        WHERE uuid = $3 AND replaced_by IS NULL
+        ",
+        new_uuid,
+        grace_expires_at,
+        session_uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if replaced.rows_affected() == 0 {
+        let user_session = sqlx::query_as!(
+            UserSession,
+            "
+            SELECT s.* FROM UserSessions AS s
+            JOIN UserSessions AS replaced ON replaced.replaced_by = s.uuid
+            WHERE replaced.uuid = $1
+            ",
+            session_uuid
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        return Ok(user_session);
+    }
+
+    let user_session = sqlx::query_as!(
+        UserSession,
+        "
+        INSERT INTO UserSessions (uuid, user_uuid, created_at, expires_at, issued_at)
+        SELECT $1, user_uuid, created_at, $2, $3
+        FROM UserSessions
+        WHERE uuid = $4
+        RETURNING *
+        ",
+        new_uuid,
+        expires_at,
+        issued_at,
+        session_uuid
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(user_session)
+}
+
+pub async fn update_user_profile(
+    pool: &PgPool,
+    uuid: Uuid,
+    display_name: Option<&str>,
+    avatar_url: Option<&str>,
+) -> Result<User, sqlx::Error> {
+    let user = sqlx::query_as!(
+        User,
+        "
+        UPDATE users
+        SET display_name = $1, avatar_url = $2, updated_at = CURRENT_TIMESTAMP
+        WHERE uuid = $3
+        RETURNING *
+        ",
+        display_name,
+        avatar_url,
+        uuid
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(user)
+}
+
+pub async fn fetch_user_preferences(
+    pool: &PgPool,
+    user_uuid: Uuid,
+) -> Result<UserPreferences, sqlx::Error> {
+    let preferences = sqlx::query_as!(
+        UserPreferences,
+        r#"
+        SELECT
+            user_uuid, theme, editor_font_size, keybinding_mode, default_export_format,
+            preview_layout, updated_at AS "updated_at?"
+        FROM UserPreferences
+        WHERE user_uuid = $1
+        "#,
+        user_uuid
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(preferences.unwrap_or_else(|| UserPreferences::defaults(user_uuid)))
+}
+
+pub async fn update_user_preferences(
+    pool: &PgPool,
+    preferences: &UserPreferences,
+) -> Result<UserPreferences, sqlx::Error> {
+    let preferences = sqlx::query_as!(
+        UserPreferences,
+        r#"
+        INSERT INTO UserPreferences (
+            user_uuid, theme, editor_font_size, keybinding_mode, default_export_format,
+            preview_layout
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (user_uuid) DO UPDATE
+        SET theme = EXCLUDED.theme,
+            editor_font_size = EXCLUDED.editor_font_size,
+            keybinding_mode = EXCLUDED.keybinding_mode,
+            default_export_format = EXCLUDED.default_export_format,
+            preview_layout = EXCLUDED.preview_layout,
+            updated_at = CURRENT_TIMESTAMP
+        RETURNING
+            user_uuid, theme, editor_font_size, keybinding_mode, default_export_format,
+            preview_layout, updated_at AS "updated_at?"
+        "#,
+        preferences.user_uuid,
+        preferences.theme,
+        preferences.editor_font_size,
+        preferences.keybinding_mode,
+        preferences.default_export_format,
+        preferences.preview_layout
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(preferences)
+}
+
+// Deletes a user along with everything that references them, in one transaction.
+// Organisations the user is the only member of go with them. In the others their workspace
+// documents are handed to an owner, and when they were the only owner the member who joined
+// first takes over
 pub async fn delete_user(pool: &PgPool, uuid: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let organisations = sqlx::query_scalar!(
+        "
+        SELECT o.uuid FROM Organisations AS o
+        WHERE (
+            o.created_by = $1
+            OR EXISTS (
+                SELECT 1 FROM OrganisationMembers AS m
+                WHERE m.organisation_uuid = o.uuid AND m.user_uuid = $1
+            )
+        )
+        AND NOT EXISTS (
+            SELECT 1 FROM OrganisationMembers AS m
+            WHERE m.organisation_uuid = o.uuid AND m.user_uuid <> $1
+        )
+        ",
+        uuid
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
     sqlx::query!(
         "
-        DELETE FROM users
-        WHERE uuid = $1
+        DELETE FROM documents
+        WHERE organisation_uuid = ANY($1)
+        ",
+        &organisations
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM OrganisationInvites
+        WHERE organisation_uuid = ANY($1) OR invited_by = $2
         ",
+        &organisations,
         uuid
     )
-    .execute(pool)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM OrganisationMembers
+        WHERE organisation_uuid = ANY($1)
+        ",
+        &organisations
+    )
+    .execute(&mut *tx)
     .await?;
 
+    sqlx::query!(
+        "
+        DELETE FROM Organisations
+        WHERE uuid = ANY($1)
+        ",
+        &organisations
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // An organisation always keeps an owner
+    sqlx::query!(
+        "
+        UPDATE OrganisationMembers
+        SET role = 'owner'
+        WHERE (organisation_uuid, user_uuid) IN (
+            SELECT DISTINCT ON (m.organisation_uuid) m.organisation_uuid, m.user_uuid
+            FROM OrganisationMembers AS m
+            WHERE m.user_uuid <> $1
+                AND m.organisation_uuid IN (
+                    SELECT organisation_uuid FROM OrganisationMembers
+                    WHERE user_uuid = $1 AND role = 'owner'
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM OrganisationMembers AS o
+                    WHERE o.organisation_uuid = m.organisation_uuid
+                        AND o.user_uuid <> $1 AND o.role = 'owner'
+                )
+            ORDER BY m.organisation_uuid, m.created_at, m.user_uuid
+        )
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        UPDATE documents AS d
+        SET user_uuid = (
+            SELECT m.user_uuid FROM OrganisationMembers AS m
+            WHERE m.organisation_uuid = d.organisation_uuid
+                AND m.user_uuid <> $1 AND m.role = 'owner'
+            ORDER BY m.created_at, m.user_uuid
+            LIMIT 1
+        )
+        WHERE d.user_uuid = $1 AND d.organisation_uuid IS NOT NULL
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        UPDATE Organisations AS o
+        SET created_by = (
+            SELECT m.user_uuid FROM OrganisationMembers AS m
+            WHERE m.organisation_uuid = o.uuid AND m.user_uuid <> $1 AND m.role = 'owner'
+            ORDER BY m.created_at, m.user_uuid
+            LIMIT 1
+        )
+        WHERE o.created_by = $1
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM OrganisationMembers
+        WHERE user_uuid = $1
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // Everything else referencing the user goes before the user itself
     sqlx::query!(
         "
         DELETE FROM documents
@@ -140,12 +472,110 @@ pub async fn delete_user(pool: &PgPool, uuid: Uuid) -> Result<(), sqlx::Error> {
         ",
         uuid
     )
-    .execute(pool)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM UserSessions
+        WHERE user_uuid = $1
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM MagicLinks
+        WHERE user_uuid = $1
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM LoginChallenges
+        WHERE user_uuid = $1
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM RecoveryCodes
+        WHERE user_uuid = $1
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
     .await?;
 
+    sqlx::query!(
+        "
+        DELETE FROM UserTotp
+        WHERE user_uuid = $1
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM users
+        WHERE uuid = $1
+        ",
+        uuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
     Ok(())
 }
 
+pub async fn schedule_user_deletion(
+    pool: &PgPool,
+    uuid: Uuid,
+    deletion_scheduled_at: Option<DateTime<Utc>>,
+) -> Result<User, sqlx::Error> {
+    let user = sqlx::query_as!(
+        User,
+        "
+        UPDATE users
+        SET deletion_scheduled_at = $1, updated_at = CURRENT_TIMESTAMP
+        WHERE uuid = $2
+        RETURNING *
+        ",
+        deletion_scheduled_at,
+        uuid
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(user)
+}
+
+pub async fn fetch_users_due_for_deletion(pool: &PgPool) -> Result<Vec<Uuid>, sqlx::Error> {
+    let users = sqlx::query_scalar!(
+        "
+        SELECT uuid FROM users
+        WHERE deletion_scheduled_at <= NOW()
+        ORDER BY deletion_scheduled_at
+        "
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(users)
+}
+
 pub async fn delete_user_session(pool: &PgPool, session_uuid: Uuid) -> Result<(), sqlx::Error> {
     sqlx::query!(
         "
@@ -160,19 +590,45 @@ pub async fn delete_user_session(pool: &PgPool, session_uuid: Uuid) -> Result<()
     Ok(())
 }
 
-pub async fn delete_expired_sessions(pool: &PgPool) -> Result<(), sqlx::Error> {
-    sqlx::query!(
+// Logs a user out everywhere. Returns how many sessions were removed
+pub async fn delete_user_sessions(pool: &PgPool, user_uuid: Uuid) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
         "
         DELETE FROM UserSessions
-        WHERE expires_at < $1
+        WHERE user_uuid = $1
         ",
-        chrono::offset::Utc::now()
-            .naive_utc()
-            .timestamp()
-            .to_string()
+        user_uuid
     )
     .execute(pool)
     .await?;
 
-    Ok(())
+    Ok(result.rows_affected())
+}
+
+// Sessions that can still be used, rotated identifiers in their grace period are left out
+pub async fn count_active_sessions(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    let count = sqlx::query_scalar!(
+        "
+        SELECT COUNT(*) FROM UserSessions
+        WHERE expires_at > NOW() AND replaced_by IS NULL
+        "
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count.unwrap_or(0))
+}
+
+// Returns how many sessions were removed
+pub async fn delete_expired_sessions(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "
+        DELETE FROM UserSessions
+        WHERE expires_at < NOW()
+        "
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
 }
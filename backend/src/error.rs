@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use axum::http::header::{CONTENT_TYPE, RETRY_AFTER};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+
+// Everything a handler can fail with. Each variant maps to one HTTP status and a stable
+// `code` the frontend can switch on, the `detail` is meant for people and may change
+#[derive(Debug)]
+pub enum AppError {
+    // The request is malformed, e.g. an identifier that isn't a UUID
+    BadRequest(String),
+    // The request is well formed but its content isn't acceptable
+    Validation(String),
+    // Like Validation, but listing every field that failed and why
+    InvalidFields(Vec<FieldError>),
+    PayloadTooLarge(String),
+    // The user stores as much as their quota allows
+    QuotaExceeded(String),
+    // There is no valid session
+    Unauthenticated,
+    // A code, link or challenge that was supposed to prove who the user is didn't check out
+    InvalidCredentials(String),
+    // The account exists but an administrator disabled it
+    AccountDisabled,
+    // The user is signed in but isn't allowed to do this
+    Forbidden(String),
+    NotFound(String),
+    // The request clashes with the current state, e.g. removing the last owner
+    Conflict(String),
+    // The client used up its request budget, it can try again after the duration
+    RateLimited(Duration),
+    // The feature isn't available with how the server is set up, e.g. on the SQLite backend
+    NotImplemented(String),
+    Database(sqlx::Error),
+    Internal(anyhow::Error),
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+// RFC 7807 problem details, extended with the stable error code and any invalid fields
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    // One of the codes from AppError::code
+    code: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Validation(_) | AppError::InvalidFields(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::QuotaExceeded(_) => StatusCode::INSUFFICIENT_STORAGE,
+            AppError::Unauthenticated | AppError::InvalidCredentials(_) => StatusCode::UNAUTHORIZED,
+            AppError::AccountDisabled | AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            AppError::Database(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Validation(_) | AppError::InvalidFields(_) => "validation_failed",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::QuotaExceeded(_) => "quota_exceeded",
+            AppError::Unauthenticated => "unauthenticated",
+            AppError::InvalidCredentials(_) => "invalid_credentials",
+            AppError::AccountDisabled => "account_disabled",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::RateLimited(_) => "rate_limited",
+            AppError::NotImplemented(_) => "not_implemented",
+            AppError::Database(_) | AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            AppError::BadRequest(detail)
+            | AppError::Validation(detail)
+            | AppError::PayloadTooLarge(detail)
+            | AppError::QuotaExceeded(detail)
+            | AppError::InvalidCredentials(detail)
+            | AppError::Forbidden(detail)
+            | AppError::NotFound(detail)
+            | AppError::Conflict(detail)
+            | AppError::NotImplemented(detail) => detail.clone(),
+            AppError::InvalidFields(_) => "Some fields aren't valid".to_string(),
+            AppError::Unauthenticated => "You need to sign in".to_string(),
+            AppError::AccountDisabled => "This account has been disabled".to_string(),
+            AppError::RateLimited(retry_after) => format!(
+                "Too many requests, try again in {} seconds",
+                retry_after_secs(*retry_after)
+            ),
+            // Internal errors are logged, never shown to the client
+            AppError::Database(_) | AppError::Internal(_) => {
+                "Something went wrong on our side".to_string()
+            }
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        match &self {
+            AppError::Database(err) => tracing::error!(error = %err, "Database error"),
+            AppError::Internal(err) => {
+                tracing::error!(error = format!("{err:#}"), "Internal error")
+            }
+            _ => tracing::debug!(code = self.code(), detail = %self.detail(), "Request failed"),
+        }
+
+        let status = self.status();
+        let retry_after = match self {
+            AppError::RateLimited(retry_after) => Some(retry_after_secs(retry_after)),
+            _ => None,
+        };
+        let problem = ProblemDetails {
+            problem_type: "about:blank",
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            detail: self.detail(),
+            code: self.code(),
+            errors: match self {
+                AppError::InvalidFields(errors) => errors,
+                _ => Vec::new(),
+            },
+        };
+
+        let mut response = (
+            status,
+            [(CONTENT_TYPE, "application/problem+json")],
+            Json(problem),
+        )
+            .into_response();
+        if let Some(secs) = retry_after {
+            response.headers_mut().insert(RETRY_AFTER, secs.into());
+        }
+        response
+    }
+}
+
+// Retry-After takes whole seconds, rounded up so a client waiting that long gets through
+fn retry_after_secs(retry_after: Duration) -> u64 {
+    retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0)
+}
+
+// A missing row means whatever the request pointed at doesn't exist (or isn't visible to
+// the user, which looks the same from the outside). A unique constraint turning a row away
+// means it clashes with one that's already there
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => {
+                AppError::NotFound("The requested resource doesn't exist".to_string())
+            }
+            sqlx::Error::Database(err) if err.is_unique_violation() => {
+                AppError::Conflict("The resource already exists".to_string())
+            }
+            err => AppError::Database(err),
+        }
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        AppError::Internal(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::db::test_support;
+
+    #[tokio::test]
+    async fn unique_violations_are_conflicts() {
+        let users = test_support::sqlite().await.users();
+        let uuid = Uuid::new_v4();
+        users
+            .create_user(uuid, "alice", "alice@example.com")
+            .await
+            .unwrap();
+        let err = users
+            .create_user(uuid, "alice", "alice@example.com")
+            .await
+            .unwrap_err();
+
+        let err = AppError::from(err);
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+        assert_eq!(err.code(), "conflict");
+    }
+}
@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use crate::mailer::{Email, Mailer};
+
+// Development mailer that writes every email to a directory, so links can be followed
+// without a real mail server. Without a directory only the recipient and subject are
+// logged, bodies hold sign in links and never go to the log
+pub struct FileMailer {
+    dir: Option<PathBuf>,
+}
+
+impl FileMailer {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        FileMailer { dir }
+    }
+}
+
+#[async_trait::async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), anyhow::Error> {
+        let contents = format!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            email.to, email.subject, email.body
+        );
+
+        let Some(dir) = &self.dir else {
+            tracing::info!(
+                to = %email.to,
+                subject = %email.subject,
+                "Email not sent, set MAILER=file to write emails to a directory"
+            );
+            return Ok(());
+        };
+
+        tokio::fs::create_dir_all(dir)
+            .await
+            .context("Failed to create the mail directory")?;
+
+        let path = dir.join(format!(
+            "{}-{}.eml",
+            chrono::offset::Utc::now().format("%Y%m%dT%H%M%S"),
+            uuid::Uuid::new_v4()
+        ));
+        tokio::fs::write(&path, contents)
+            .await
+            .with_context(|| format!("Failed to write email to {}", path.display()))?;
+
+        Ok(())
+    }
+}
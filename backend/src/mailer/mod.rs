@@ -0,0 +1,81 @@
+pub mod file;
+pub mod smtp;
+
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+
+use file::FileMailer;
+use smtp::{SmtpMailer, SmtpSecurity};
+
+// An email ready to be handed to a mailer
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// Delivers emails, implementations decide where they actually end up
+#[async_trait::async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), anyhow::Error>;
+}
+
+pub type SharedMailer = Arc<dyn Mailer>;
+
+// Picks the mailer from the MAILER environment variable, falling back to the log mailer.
+// Release builds warn about that since sign in links and invites then go nowhere
+pub fn from_env() -> Result<SharedMailer, anyhow::Error> {
+    let mailer = match env::var("MAILER") {
+        Ok(mailer) => mailer,
+        Err(_) => {
+            if !cfg!(debug_assertions) {
+                tracing::warn!(
+                    "MAILER isn't set, emails such as sign in links are only logged. Set it to smtp to send them"
+                );
+            }
+            "log".to_string()
+        }
+    };
+
+    match mailer.as_str() {
+        "smtp" => {
+            let host =
+                env::var("SMTP_HOST").context("Missing the SMTP_HOST environment variable")?;
+            let port = env::var("SMTP_PORT")
+                .ok()
+                .map(|port| port.parse::<u16>())
+                .transpose()
+                .context("SMTP_PORT must be a valid u16")?;
+            let security = match env::var("SMTP_SECURITY").as_deref() {
+                Ok("none") => SmtpSecurity::None,
+                Ok("starttls") | Err(_) => SmtpSecurity::StartTls,
+                Ok("tls") => SmtpSecurity::Tls,
+                Ok(other) => bail!("SMTP_SECURITY must be none, starttls or tls, got {other}"),
+            };
+            let credentials = match (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+                (Ok(username), Ok(password)) => Some((username, password)),
+                _ => None,
+            };
+            let from =
+                env::var("MAIL_FROM").context("Missing the MAIL_FROM environment variable")?;
+
+            Ok(Arc::new(SmtpMailer::new(
+                &host,
+                port,
+                security,
+                credentials,
+                &from,
+            )?))
+        }
+        "file" => {
+            let dir = env::var("MAILER_FILE_DIR").unwrap_or_else(|_| "./mail".to_string());
+            Ok(Arc::new(FileMailer::new(Some(PathBuf::from(dir)))))
+        }
+        "log" => Ok(Arc::new(FileMailer::new(None))),
+        other => bail!("MAILER must be smtp, file or log, got {other}"),
+    }
+}
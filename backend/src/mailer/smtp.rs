@@ -0,0 +1,175 @@
+use anyhow::Context;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::mailer::{Email, Mailer};
+
+// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy)]
+pub enum SmtpSecurity {
+    // Plain connection, only meant for local SMTP sinks like MailHog or Mailpit
+    None,
+    StartTls,
+    Tls,
+}
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(
+        host: &str,
+        port: Option<u16>,
+        security: SmtpSecurity,
+        credentials: Option<(String, String)>,
+        from: &str,
+    ) -> Result<Self, anyhow::Error> {
+        let mut builder = match security {
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .context("Failed to set up STARTTLS for the SMTP server")?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .context("Failed to set up TLS for the SMTP server")?,
+        };
+
+        if let Some(port) = port {
+            builder = builder.port(port);
+        }
+
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(SmtpMailer {
+            transport: builder.build(),
+            from: from.parse().context("MAIL_FROM is not a valid mailbox")?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> Result<(), anyhow::Error> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(email.to.parse().context("Invalid recipient address")?)
+            .subject(email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body)
+            .context("Failed to build email")?;
+
+        self.transport
+            .send(message)
+            .await
+            .context("Failed to send email")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    // A local SMTP sink that accepts one email and hands back what it received after DATA
+    async fn smtp_sink() -> (u16, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let sink = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut message = String::new();
+            let mut in_data = false;
+
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                if in_data {
+                    if line == "." {
+                        in_data = false;
+                        writer.write_all(b"250 Queued\r\n").await.unwrap();
+                    } else {
+                        message.push_str(&line);
+                        message.push('\n');
+                    }
+                    continue;
+                }
+
+                let command = line.to_ascii_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") {
+                    b"250 localhost\r\n"
+                } else if command.starts_with("DATA") {
+                    in_data = true;
+                    b"354 End data with <CR><LF>.<CR><LF>\r\n"
+                } else if command.starts_with("QUIT") {
+                    writer.write_all(b"221 Bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 OK\r\n"
+                };
+                writer.write_all(reply).await.unwrap();
+            }
+
+            message
+        });
+
+        (port, sink)
+    }
+
+    #[tokio::test]
+    async fn sends_through_the_smtp_server() {
+        let (port, sink) = smtp_sink().await;
+        let mailer = SmtpMailer::new(
+            "127.0.0.1",
+            Some(port),
+            SmtpSecurity::None,
+            None,
+            "MarkdownEdit <noreply@example.com>",
+        )
+        .unwrap();
+
+        mailer
+            .send(Email {
+                to: "alice@example.com".to_string(),
+                subject: "Sign in to MarkdownEdit".to_string(),
+                body: "Use the link below to sign in.".to_string(),
+            })
+            .await
+            .unwrap();
+        drop(mailer);
+
+        let message = sink.await.unwrap();
+        assert!(message.contains("From: MarkdownEdit <noreply@example.com>"));
+        assert!(message.contains("To: alice@example.com"));
+        assert!(message.contains("Subject: Sign in to MarkdownEdit"));
+        assert!(message.contains("Use the link below to sign in."));
+    }
+
+    #[tokio::test]
+    async fn refuses_invalid_recipients() {
+        let mailer = SmtpMailer::new(
+            "127.0.0.1",
+            Some(1),
+            SmtpSecurity::None,
+            None,
+            "noreply@example.com",
+        )
+        .unwrap();
+
+        let sent = mailer
+            .send(Email {
+                to: "not an address".to_string(),
+                subject: "Hi".to_string(),
+                body: "Hi".to_string(),
+            })
+            .await;
+        assert!(sent.is_err());
+    }
+}
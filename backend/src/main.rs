@@ -1,86 +1,251 @@
+mod app;
+mod commands;
+mod config;
 mod db;
+mod error;
+mod mailer;
 mod models;
 mod routes;
+mod state;
 mod utils;
-use axum::Router;
+
+#[cfg(test)]
+mod tests;
+
+use config::Invocation;
+use db::connection::Database;
 use dotenv::dotenv;
-use http::header::CONTENT_TYPE;
-use http::{HeaderValue, Method};
-use routes::auth::google_auth_router;
-use routes::documents::document_routes;
-use routes::users::users_routes;
-use std::time::Duration;
-use std::{env, net::SocketAddr};
+use routes::auth::GoogleOAuth;
+use state::AppState;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::time;
-use tower_http::{cors::CorsLayer, services::ServeDir};
+use tokio_util::sync::CancellationToken;
+use utils::account_deletion;
+use utils::constants::METRICS_GAUGE_REFRESH_INTERVAL;
+use utils::magic_link::MagicLinkSigner;
+use utils::monitoring;
+use utils::rate_limit::RateLimiter;
+use utils::shutdown::{self, BackgroundTasks};
+use utils::telemetry;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
+    // merge the command line, environment and config file before anything else runs
+    let invocation = Invocation::load().unwrap_or_else(|err| {
+        eprintln!("Invalid configuration: {:#}", err);
+        std::process::exit(1);
+    });
+    let config = match invocation {
+        Invocation::Serve(config) => *config,
+        Invocation::Admin(command, database) => {
+            if let Err(err) = commands::run(command, &database).await {
+                eprintln!("Error: {:#}", err);
+                std::process::exit(1);
+            }
+            return;
+        }
+    };
+    telemetry::init(config.tracing_level, config.log_format).unwrap_or_else(|err| {
+        eprintln!("Failed to set up logging: {:#}", err);
+        std::process::exit(1);
+    });
+
+    let metrics_handle = monitoring::install_recorder().unwrap_or_else(|err| {
+        tracing::error!(error = format!("{err:#}"), "Failed to set up metrics");
+        std::process::exit(1);
+    });
+
     // connect to the database and run migrations
-    let pool = db::connection::connect(env::var("DATABASE_URL").unwrap().as_str())
+    let database = Database::connect(&config.database)
         .await
         .unwrap_or_else(|err| {
-            eprintln!("Database error: {}", err);
+            tracing::error!(error = %err, "Database error");
             std::process::exit(1);
         });
 
-    let port = env::var("PORT")
-        .unwrap_or_else(|_| "8080".to_string())
-        .parse::<u16>()
-        .expect("PORT must be a valid u16");
+    // promote the users listed in ADMIN_EMAILS so there is a way to bootstrap the first admin
+    if let Err(err) = database.users().promote_admins(&config.admin_emails).await {
+        tracing::error!(error = %err, "Error promoting admins");
+    }
+
+    let mailer = mailer::from_env().unwrap_or_else(|err| {
+        tracing::error!(error = format!("{err:#}"), "Invalid mailer configuration");
+        std::process::exit(1);
+    });
+    let magic_link_signer = MagicLinkSigner::from_env();
 
     // run it with hyper
-    let listener = tokio::net::TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port)))
+    let bind_address = SocketAddr::new(config.bind_address, config.port);
+    let listener = tokio::net::TcpListener::bind(bind_address)
         .await
-        .unwrap();
+        .unwrap_or_else(|err| {
+            tracing::error!(error = %err, %bind_address, "Failed to listen");
+            std::process::exit(1);
+        });
+    tracing::info!(%bind_address, "Listening");
+
+    // cancelled once a shutdown signal arrives, everything long running listens to it
+    let shutdown = CancellationToken::new();
 
     // spawn a task to delete expired sessions periodically
-    tokio::spawn(delete_expired_sessions_periodically(pool.clone()));
-
-    let cors_origin = env::var("CLIENT_URL")
-        .unwrap()
-        .as_str()
-        .parse::<HeaderValue>()
-        .unwrap();
-    let cors_middleware = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::PUT])
-        .allow_origin(cors_origin)
-        .allow_headers([CONTENT_TYPE])
-        .allow_credentials(true);
-
-    let auth_router = google_auth_router(pool.clone()).layer(cors_middleware.clone());
-    let users_router = users_routes(pool.clone()).layer(cors_middleware.clone());
-    let documents_router = document_routes(pool.clone()).layer(cors_middleware.clone());
-
-    let dist_dir = if cfg!(debug_assertions) {
-        "../frontend/dist/"
-    } else {
-        "./dist"
+    let cleanup_task = tokio::spawn(delete_expired_sessions_periodically(
+        database.clone(),
+        shutdown.child_token(),
+    ));
+
+    // spawn a task to keep the database gauges of the metrics endpoint up to date
+    let gauges_task = tokio::spawn(refresh_database_gauges_periodically(
+        database.clone(),
+        shutdown.child_token(),
+    ));
+
+    let config = Arc::new(config);
+    let google_oauth = GoogleOAuth::new(&config.base_url);
+    // Flipped when shutdown starts so readiness probes fail before connections are drained
+    let shutting_down = Arc::new(AtomicBool::new(false));
+    tokio::spawn(shutdown::watch_for_shutdown(
+        shutting_down.clone(),
+        shutdown.clone(),
+        config.shutdown_readiness_delay,
+    ));
+
+    // work requests start that outlives them, stopped once the requests are drained
+    let background_tasks = BackgroundTasks::new();
+
+    // shared by every router
+    let app = app::router(AppState {
+        users: database.users(),
+        documents: database.documents(),
+        exports: database.exports(),
+        database: database.clone(),
+        config: config.clone(),
+        mailer,
+        magic_link_signer,
+        google_oauth,
+        rate_limiter: RateLimiter::new(config.rate_limit, config.trust_forwarded_for),
+        metrics_handle,
+        shutting_down,
+        background_tasks: background_tasks.clone(),
+    });
+
+    // start the server, once shutdown starts it stops accepting connections and waits for
+    // the ones in flight to finish
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown.clone().cancelled_owned());
+    let mut server = tokio::spawn(async move { server.await });
+
+    let result = tokio::select! {
+        result = &mut server => result,
+        _ = shutdown.cancelled() => {
+            match time::timeout(config.shutdown_timeout, &mut server).await {
+                Ok(result) => result,
+                Err(_) => {
+                    tracing::warn!(
+                        timeout_secs = config.shutdown_timeout.as_secs(),
+                        "Timed out draining requests, dropping the remaining connections"
+                    );
+                    server.abort();
+                    Ok(Ok(()))
+                }
+            }
+        }
     };
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => tracing::error!(error = %err, "Server error"),
+        Err(err) => tracing::error!(error = %err, "Server task failed"),
+    }
 
-    let app = Router::new()
-        .nest_service("/", ServeDir::new(dist_dir))
-        .nest("/auth", auth_router)
-        .nest("/users", users_router)
-        .nest("/documents", documents_router);
+    // the server may have stopped on its own, make sure the background tasks stop too
+    shutdown.cancel();
+    if let Err(err) = cleanup_task.await {
+        tracing::error!(error = %err, "Cleanup task failed");
+    }
+    if let Err(err) = gauges_task.await {
+        tracing::error!(error = %err, "Metrics task failed");
+    }
+    background_tasks.shutdown(config.shutdown_timeout).await;
 
-    // start the server
-    axum::serve(listener, app).await.unwrap_or_else(|err| {
-        eprintln!("Server error: {}", err);
-        std::process::exit(1);
-    })
+    database.close().await;
+    tracing::info!("Shutdown complete");
 }
 
-async fn delete_expired_sessions_periodically(pool: sqlx::PgPool) {
-    // Run indefinitely
+async fn delete_expired_sessions_periodically(database: Database, shutdown: CancellationToken) {
+    let users = database.users();
+    let exports = database.exports();
+
+    // Run until the server shuts down
     loop {
         // Wait for 1 hour before deleting expired sessions again
-        time::sleep(Duration::from_secs(3600)).await;
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = time::sleep(Duration::from_secs(3600)) => {}
+        }
 
         // Delete expired sessions
-        if let Err(err) = db::user_queries::delete_expired_sessions(&pool).await {
-            eprintln!("Error deleting expired sessions: {}", err);
+        run_cleanup_task("delete_expired_sessions", users.delete_expired_sessions()).await;
+
+        // Delete accounts whose deletion grace period is over
+        run_cleanup_task(
+            "delete_scheduled_accounts",
+            account_deletion::delete_scheduled_accounts(&database),
+        )
+        .await;
+
+        // Delete account exports whose download link has expired, archives included
+        run_cleanup_task("delete_expired_exports", exports.delete_expired_exports()).await;
+
+        // Login challenges and magic links only exist on PostgreSQL
+        let Some(pool) = database.postgres() else {
+            continue;
+        };
+
+        // Delete abandoned two-factor login challenges
+        run_cleanup_task(
+            "delete_expired_login_challenges",
+            db::two_factor_queries::delete_expired_login_challenges(pool),
+        )
+        .await;
+
+        // Delete magic links that can no longer be used
+        run_cleanup_task(
+            "delete_expired_magic_links",
+            db::magic_link_queries::delete_expired_magic_links(pool),
+        )
+        .await;
+    }
+}
+
+async fn refresh_database_gauges_periodically(database: Database, shutdown: CancellationToken) {
+    // Run until the server shuts down, starting right away so the first scrape has values
+    loop {
+        monitoring::record_database_gauges(&database).await;
+
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = time::sleep(METRICS_GAUGE_REFRESH_INTERVAL) => {}
         }
     }
 }
+
+// Runs one cleanup step, recording how it went for the metrics endpoint
+async fn run_cleanup_task<F, T>(task: &'static str, cleanup: F)
+where
+    F: Future<Output = Result<T, sqlx::Error>>,
+{
+    let started = Instant::now();
+    let result = cleanup.await;
+    monitoring::record_background_task(task, result.is_ok(), started.elapsed());
+
+    if let Err(err) = result {
+        tracing::error!(error = %err, task, "Background task failed");
+    }
+}
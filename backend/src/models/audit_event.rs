@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuditEvent {
+    pub uuid: Uuid,
+    pub user_uuid: Option<Uuid>,
+    pub event_type: String,
+    pub target_type: Option<String>,
+    pub target_uuid: Option<Uuid>,
+    pub organisation_uuid: Option<Uuid>,
+    #[schema(value_type = Object)]
+    pub metadata: serde_json::Value,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Filters for listing audit events, every one of them is optional
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditEventFilter {
+    pub user_uuid: Option<Uuid>,
+    pub event_type: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditEventType {
+    Login,
+    LoginFailed,
+    Logout,
+    SessionCreated,
+    TwoFactorEnabled,
+    TwoFactorDisabled,
+    RecoveryCodesRegenerated,
+    DocumentCreated,
+    DocumentUpdated,
+    DocumentDeleted,
+    OrganisationCreated,
+    OrganisationDeleted,
+    OrganisationInviteCreated,
+    OrganisationInviteRevoked,
+    OrganisationMemberAdded,
+    OrganisationMemberRemoved,
+    OrganisationMemberRoleChanged,
+    AccountProfileUpdated,
+    AccountDeleted,
+    AccountDeletionScheduled,
+    AccountDeletionCancelled,
+    AccountExportRequested,
+    AdminUserDisabled,
+    AdminUserEnabled,
+    AdminUserRoleChanged,
+    AdminSessionsRevoked,
+}
+
+impl AuditEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventType::Login => "auth.login",
+            AuditEventType::LoginFailed => "auth.login_failed",
+            AuditEventType::Logout => "auth.logout",
+            AuditEventType::SessionCreated => "session.created",
+            AuditEventType::TwoFactorEnabled => "auth.2fa_enabled",
+            AuditEventType::TwoFactorDisabled => "auth.2fa_disabled",
+            AuditEventType::RecoveryCodesRegenerated => "auth.recovery_codes_regenerated",
+            AuditEventType::DocumentCreated => "document.created",
+            AuditEventType::DocumentUpdated => "document.updated",
+            AuditEventType::DocumentDeleted => "document.deleted",
+            AuditEventType::OrganisationCreated => "organisation.created",
+            AuditEventType::OrganisationDeleted => "organisation.deleted",
+            AuditEventType::OrganisationInviteCreated => "organisation.invite_created",
+            AuditEventType::OrganisationInviteRevoked => "organisation.invite_revoked",
+            AuditEventType::OrganisationMemberAdded => "organisation.member_added",
+            AuditEventType::OrganisationMemberRemoved => "organisation.member_removed",
+            AuditEventType::OrganisationMemberRoleChanged => "organisation.member_role_changed",
+            AuditEventType::AccountProfileUpdated => "user.profile_updated",
+            AuditEventType::AccountDeleted => "user.deleted",
+            AuditEventType::AccountDeletionScheduled => "user.deletion_scheduled",
+            AuditEventType::AccountDeletionCancelled => "user.deletion_cancelled",
+            AuditEventType::AccountExportRequested => "user.export_requested",
+            AuditEventType::AdminUserDisabled => "admin.user_disabled",
+            AuditEventType::AdminUserEnabled => "admin.user_enabled",
+            AuditEventType::AdminUserRoleChanged => "admin.user_role_changed",
+            AuditEventType::AdminSessionsRevoked => "admin.sessions_revoked",
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Rows backups hold as they are stored, where the models the API uses leave columns out or
+// join others in
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OrganisationMemberRecord {
+    pub organisation_uuid: Uuid,
+    pub user_uuid: Uuid,
+    pub role: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+// Unlike UserTotp the secret is kept, a restored account has to accept the same codes
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct UserTotpRecord {
+    pub user_uuid: Uuid,
+    pub secret: String,
+    pub enabled: bool,
+    pub last_used_step: Option<i64>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RecoveryCodeRecord {
+    pub uuid: Uuid,
+    pub user_uuid: Uuid,
+    pub code_hash: String,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
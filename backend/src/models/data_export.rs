@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+// An archive of a user's own data, built in the background after they ask for it. The
+// archive itself is only read when it's downloaded
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, sqlx::FromRow)]
+pub struct DataExport {
+    pub uuid: Uuid,
+    pub user_uuid: Uuid,
+    // pending, ready or failed
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    // The export and its archive are deleted after this
+    pub expires_at: DateTime<Utc>,
+    pub size_bytes: Option<i64>,
+}
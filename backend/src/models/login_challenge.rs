@@ -0,0 +1,13 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// A pending login that is waiting for the user's second factor
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginChallenge {
+    pub uuid: Uuid,
+    pub user_uuid: Uuid,
+    pub attempts: i32,
+    pub created_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+}
@@ -1,3 +1,13 @@
+pub mod audit_event;
+pub mod backup;
+pub mod data_export;
 pub mod document;
+pub mod login_challenge;
+pub mod magic_link;
+pub mod organisation;
 pub mod user;
+pub mod user_overview;
+pub mod user_preferences;
 pub mod user_session;
+pub mod user_totp;
+pub mod user_usage;
@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Organisation {
+    pub uuid: Uuid,
+    pub name: String,
+    pub created_by: Uuid,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// An organisation as seen by one of its members
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OrganisationMembership {
+    pub uuid: Uuid,
+    pub name: String,
+    pub role: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OrganisationMember {
+    pub user_uuid: Uuid,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OrganisationInvite {
+    pub uuid: Uuid,
+    pub organisation_uuid: Uuid,
+    pub email: String,
+    pub role: String,
+    pub invited_by: Uuid,
+    pub created_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+}
@@ -1,11 +1,32 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::utils::constants::ROLE_ADMIN;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, sqlx::FromRow)]
 pub struct User {
     pub uuid: Uuid,
     pub username: String,
     pub email: String,
-    pub created_at: Option<String>,
-    pub updated_at: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub role: String,
+    pub disabled_at: Option<DateTime<Utc>>,
+    // Set while the account waits out the grace period before it's deleted
+    pub deletion_scheduled_at: Option<DateTime<Utc>>,
+    // Chosen by the user, clients fall back to username when they aren't set
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+impl User {
+    pub fn is_admin(&self) -> bool {
+        self.role == ROLE_ADMIN
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled_at.is_some()
+    }
 }
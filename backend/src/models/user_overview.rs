@@ -0,0 +1,26 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+// A user along with what they store and how many sessions they have, for administrators
+#[derive(Debug, Serialize, Deserialize, ToSchema, sqlx::FromRow)]
+pub struct UserOverview {
+    pub uuid: Uuid,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub document_count: i64,
+    pub storage_bytes: i64,
+    pub active_sessions: i64,
+}
+
+// Storage used across the whole instance
+#[derive(Debug, Serialize, Deserialize, ToSchema, sqlx::FromRow)]
+pub struct StorageUsage {
+    pub user_count: i64,
+    pub document_count: i64,
+    pub storage_bytes: i64,
+}
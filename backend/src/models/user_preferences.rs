@@ -0,0 +1,32 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+// Editor settings stored for a user, see the UserPreferences table for the allowed values
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, sqlx::FromRow)]
+pub struct UserPreferences {
+    pub user_uuid: Uuid,
+    pub theme: String,
+    pub editor_font_size: i32,
+    pub keybinding_mode: String,
+    pub default_export_format: String,
+    pub preview_layout: String,
+    // None until the user saves their preferences for the first time
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl UserPreferences {
+    // What a user who never saved any preferences gets, matching the column defaults
+    pub fn defaults(user_uuid: Uuid) -> Self {
+        UserPreferences {
+            user_uuid,
+            theme: "system".to_string(),
+            editor_font_size: 14,
+            keybinding_mode: "default".to_string(),
+            default_export_format: "markdown".to_string(),
+            preview_layout: "split".to_string(),
+            updated_at: None,
+        }
+    }
+}
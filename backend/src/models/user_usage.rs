@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// What a user stores, counted over every document they wrote, organisation documents
+// included. Sizes are the bytes of the Markdown content
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, sqlx::FromRow)]
+pub struct UserUsage {
+    pub document_count: i64,
+    pub storage_bytes: i64,
+    pub largest_document_bytes: i64,
+}
@@ -0,0 +1,272 @@
+use axum::extract::{Path, Query, State};
+use axum::middleware;
+use axum::{
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::db::audit_queries;
+use crate::db::connection::Database;
+use crate::db::repository::SharedUserRepository;
+use crate::error::{AppError, ProblemDetails};
+use crate::models::audit_event::{AuditEvent, AuditEventFilter, AuditEventType};
+use crate::models::user::User;
+use crate::models::user_overview::{StorageUsage, UserOverview};
+use crate::routes::{router_from, RouteTable};
+use crate::state::AppState;
+use crate::utils::audit::{self, AuditContext, AuditRecord};
+use crate::utils::auth::{require_admin, AuthUser};
+use crate::utils::constants::{ROLE_ADMIN, ROLE_USER};
+use crate::utils::postgres::Postgres;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct RoleRequest {
+    #[schema(example = "admin")]
+    role: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct SessionsDeletedResponse {
+    sessions_deleted: u64,
+}
+
+// Every route in here goes through require_admin, so new endpoints can't forget the check
+pub fn admin_routes(state: AppState) -> Router<AppState> {
+    router_from(route_table()).route_layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+pub fn route_table() -> RouteTable {
+    vec![
+        ("/users", get(get_users)),
+        ("/users/:uuid/disable", post(disable_user)),
+        ("/users/:uuid/enable", post(enable_user)),
+        ("/users/:uuid/role", put(update_user_role)),
+        ("/users/:uuid/sessions", delete(delete_user_sessions)),
+        ("/storage", get(get_storage_usage)),
+        ("/audit", get(get_audit_events)),
+    ]
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/users",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Every user with what they store and their active sessions", body = Vec<UserOverview>),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Not an admin", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn get_users(
+    State(users): State<SharedUserRepository>,
+) -> Result<Json<Vec<UserOverview>>, AppError> {
+    let users = users.fetch_user_overviews().await?;
+
+    Ok(Json(users))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/{uuid}/disable",
+    tag = "admin",
+    params(("uuid" = Uuid, Path, description = "User id")),
+    responses(
+        (status = 200, description = "The user is disabled and signed out everywhere", body = User),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Not an admin", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Admins can't disable their own account", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn disable_user(
+    State(database): State<Database>,
+    State(users): State<SharedUserRepository>,
+    AuthUser(admin): AuthUser,
+    audit_context: AuditContext,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<User>, AppError> {
+    // Admins can't lock themselves out
+    if uuid == admin.uuid {
+        return Err(AppError::Conflict(
+            "You can't disable your own account".to_string(),
+        ));
+    }
+
+    let user = users.set_user_disabled(uuid, true).await?;
+
+    // Disabling an account also ends every session it has
+    let sessions_deleted = users.delete_user_sessions(uuid).await?;
+
+    audit::record(
+        database.postgres(),
+        &audit_context,
+        AuditRecord::new(AuditEventType::AdminUserDisabled, Some(admin.uuid))
+            .target("user", uuid)
+            .metadata(serde_json::json!({ "sessions_deleted": sessions_deleted })),
+    )
+    .await;
+
+    Ok(Json(user))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/{uuid}/enable",
+    tag = "admin",
+    params(("uuid" = Uuid, Path, description = "User id")),
+    responses(
+        (status = 200, body = User),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Not an admin", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such user", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn enable_user(
+    State(database): State<Database>,
+    State(users): State<SharedUserRepository>,
+    AuthUser(admin): AuthUser,
+    audit_context: AuditContext,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<User>, AppError> {
+    let user = users.set_user_disabled(uuid, false).await?;
+
+    audit::record(
+        database.postgres(),
+        &audit_context,
+        AuditRecord::new(AuditEventType::AdminUserEnabled, Some(admin.uuid)).target("user", uuid),
+    )
+    .await;
+
+    Ok(Json(user))
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/users/{uuid}/role",
+    tag = "admin",
+    params(("uuid" = Uuid, Path, description = "User id")),
+    request_body = RoleRequest,
+    responses(
+        (status = 200, body = User),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Not an admin", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Admins can't remove their own admin role", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "The role isn't user or admin", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn update_user_role(
+    State(database): State<Database>,
+    State(users): State<SharedUserRepository>,
+    AuthUser(admin): AuthUser,
+    audit_context: AuditContext,
+    Path(uuid): Path<Uuid>,
+    request: Json<RoleRequest>,
+) -> Result<Json<User>, AppError> {
+    if request.role != ROLE_USER && request.role != ROLE_ADMIN {
+        return Err(AppError::Validation(format!(
+            "The role has to be either {ROLE_USER} or {ROLE_ADMIN}"
+        )));
+    }
+
+    // Admins can't demote themselves, so there is always at least one admin left
+    if uuid == admin.uuid && request.role != ROLE_ADMIN {
+        return Err(AppError::Conflict(
+            "You can't remove your own admin role".to_string(),
+        ));
+    }
+
+    let user = users.set_user_role(uuid, &request.role).await?;
+
+    audit::record(
+        database.postgres(),
+        &audit_context,
+        AuditRecord::new(AuditEventType::AdminUserRoleChanged, Some(admin.uuid))
+            .target("user", uuid)
+            .metadata(serde_json::json!({ "role": user.role })),
+    )
+    .await;
+
+    Ok(Json(user))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{uuid}/sessions",
+    tag = "admin",
+    params(("uuid" = Uuid, Path, description = "User id")),
+    responses(
+        (status = 200, description = "The user is signed out everywhere", body = SessionsDeletedResponse),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Not an admin", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn delete_user_sessions(
+    State(database): State<Database>,
+    State(users): State<SharedUserRepository>,
+    AuthUser(admin): AuthUser,
+    audit_context: AuditContext,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<SessionsDeletedResponse>, AppError> {
+    let sessions_deleted = users.delete_user_sessions(uuid).await?;
+
+    audit::record(
+        database.postgres(),
+        &audit_context,
+        AuditRecord::new(AuditEventType::AdminSessionsRevoked, Some(admin.uuid))
+            .target("user", uuid)
+            .metadata(serde_json::json!({ "sessions_deleted": sessions_deleted })),
+    )
+    .await;
+
+    Ok(Json(SessionsDeletedResponse { sessions_deleted }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/storage",
+    tag = "admin",
+    responses(
+        (status = 200, body = StorageUsage),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Not an admin", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn get_storage_usage(
+    State(users): State<SharedUserRepository>,
+) -> Result<Json<StorageUsage>, AppError> {
+    let storage_usage = users.fetch_storage_usage().await?;
+
+    Ok(Json(storage_usage))
+}
+
+// Lists audit events across every account, optionally narrowed down to one user. The audit
+// log is only kept on PostgreSQL
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    tag = "admin",
+    params(AuditEventFilter),
+    responses(
+        (status = 200, body = Vec<AuditEvent>),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Not an admin", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn get_audit_events(
+    Postgres(pool): Postgres,
+    Query(filter): Query<AuditEventFilter>,
+) -> Result<Json<Vec<AuditEvent>>, AppError> {
+    let events = audit_queries::fetch_audit_events(&pool, &filter).await?;
+
+    Ok(Json(events))
+}
@@ -1,12 +1,12 @@
-use std::env;
+use std::sync::Arc;
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use axum::{
     extract::{Query, State},
     http::StatusCode,
-    response::{ErrorResponse, IntoResponse, Redirect},
-    routing::get,
-    Router,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Json, Router,
 };
 use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
 use oauth2::{
@@ -14,11 +14,30 @@ use oauth2::{
 };
 use oauth2::{reqwest::async_http_client, PkceCodeVerifier};
 use oauth2::{AuthUrl, ClientId, ClientSecret, RedirectUrl, TokenUrl};
-
-use crate::db::user_queries;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::config::{Config, SharedConfig};
+use crate::db::connection::Database;
+use crate::db::repository::{SharedUserRepository, UserRepository};
+use crate::db::{magic_link_queries, two_factor_queries};
+use crate::error::{AppError, ProblemDetails};
+use crate::mailer::{Email, SharedMailer};
+use crate::models::audit_event::AuditEventType;
+use crate::models::user::User;
+use crate::routes::two_factor::TwoFactorCodeRequest;
+use crate::routes::{router_from, RouteTable};
+use crate::state::AppState;
+use crate::utils::audit::{self, AuditContext, AuditRecord};
 use crate::utils::constants::{
-    COOKIE_AUTH_CODE_VERIFIER, COOKIE_AUTH_CSRF_STATE, COOKIE_AUTH_SESSION, SESSION_DURATION,
+    COOKIE_AUTH_2FA_CHALLENGE, COOKIE_AUTH_CODE_VERIFIER, COOKIE_AUTH_CSRF_STATE,
+    COOKIE_AUTH_SESSION, LOGIN_CHALLENGE_DURATION, LOGIN_CHALLENGE_MAX_ATTEMPTS,
+    MAGIC_LINK_DURATION, ROLE_ADMIN,
 };
+use crate::utils::helpers::verify_second_factor;
+use crate::utils::magic_link::MagicLinkSigner;
+use crate::utils::postgres::Postgres;
+use crate::utils::session::{session_cookie, SessionConfig};
+use crate::utils::shutdown::BackgroundTasks;
 
 // What we get back from Google
 #[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
@@ -28,22 +47,67 @@ struct GoogleUser {
     email: String,
 }
 
+// What the client sends to ask for a magic link
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct MagicLinkRequest {
+    email: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, IntoParams)]
+struct MagicLinkVerifyRequest {
+    token: String,
+}
+
 // What we send to Google
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize, IntoParams)]
 struct AuthRequest {
     code: String,
     state: String,
 }
 
-pub fn google_auth_router(pool: sqlx::PgPool) -> Router {
-    Router::new()
-        .route("/logout", get(logout))
-        .route("/google/login", get(login))
-        .route("/google/callback", get(callback))
-        .with_state(pool)
+pub fn google_auth_router() -> Router<AppState> {
+    router_from(route_table())
+}
+
+pub fn route_table() -> RouteTable {
+    vec![
+        ("/logout", get(logout)),
+        ("/google/login", get(login)),
+        ("/google/callback", get(callback)),
+        ("/2fa/verify", post(verify_login_challenge)),
+        ("/magic-link", post(request_magic_link)),
+        ("/magic-link/verify", get(verify_magic_link)),
+    ]
+}
+
+// The Google OAuth client, built once at startup. When it can't be built, e.g. because the
+// credentials are missing, the error is kept so sign in attempts and readiness checks can
+// report it
+#[derive(Clone)]
+pub struct GoogleOAuth(Arc<Result<BasicClient, String>>);
+
+impl GoogleOAuth {
+    pub fn new(base_url: &str) -> Self {
+        let client = get_oauth_client(base_url).map_err(|err| format!("{err:#}"));
+        if let Err(err) = &client {
+            tracing::warn!(error = %err, "Google sign in is unavailable");
+        }
+
+        GoogleOAuth(Arc::new(client))
+    }
+
+    pub fn client(&self) -> Result<&BasicClient, AppError> {
+        self.0.as_ref().as_ref().map_err(|err| {
+            AppError::Internal(anyhow!("Failed to create google auth client: {err}"))
+        })
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        self.0.as_ref().as_ref().map(|_| ()).map_err(Clone::clone)
+    }
 }
 
-fn get_oauth_client() -> Result<BasicClient, anyhow::Error> {
+fn get_oauth_client(base_url: &str) -> Result<BasicClient, anyhow::Error> {
     let client_id = ClientId::new(
         std::env::var("GOOGLE_CLIENT_ID")
             .context("Missing the GOOGLE_CLIENT_ID environment variable")?,
@@ -59,7 +123,6 @@ fn get_oauth_client() -> Result<BasicClient, anyhow::Error> {
     let token_url = TokenUrl::new("https://www.googleapis.com/oauth2/v3/token".to_string())
         .context("Invalid token endpoint URL")?;
 
-    let base_url = std::env::var("BASE_URL").context("Failed to get app base url")?;
     let redirect_url = RedirectUrl::new(format!("{base_url}/auth/google/callback"))
         .context("Invalid redirect url")?;
 
@@ -69,11 +132,14 @@ fn get_oauth_client() -> Result<BasicClient, anyhow::Error> {
     Ok(client)
 }
 
-async fn login() -> Result<impl IntoResponse, StatusCode> {
-    let client = get_oauth_client().map_err(|err| {
-        eprintln!("Failed to create google auth client: {}", err);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+#[utoipa::path(
+    get,
+    path = "/auth/google/login",
+    tag = "auth",
+    responses((status = 303, description = "Redirects to Google's consent screen"))
+)]
+async fn login(State(google_oauth): State<GoogleOAuth>) -> Result<impl IntoResponse, AppError> {
+    let client = google_oauth.client()?;
 
     let (pkce_code_challenge, pkce_code_verifier) = PkceCodeChallenge::new_random_sha256();
 
@@ -113,28 +179,44 @@ async fn login() -> Result<impl IntoResponse, StatusCode> {
     Ok((cookies, Redirect::to(authorize_url.as_str())))
 }
 
+#[utoipa::path(
+    get,
+    path = "/auth/google/callback",
+    tag = "auth",
+    params(AuthRequest),
+    responses(
+        (status = 303, description = "Signs the user in, or asks for the second factor, and redirects to the client"),
+        (status = 400, description = "The sign in attempt expired or its state doesn't match", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The account is disabled", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 async fn callback(
     cookies: CookieJar,
-    State(pool): State<sqlx::PgPool>,
+    State(database): State<Database>,
+    State(users): State<SharedUserRepository>,
+    State(config): State<SharedConfig>,
+    State(google_oauth): State<GoogleOAuth>,
+    audit_context: AuditContext,
     Query(query): Query<AuthRequest>,
-) -> Result<impl IntoResponse, ErrorResponse> {
+) -> Result<impl IntoResponse, AppError> {
     let code = query.code;
     let state = query.state;
     let stored_state = cookies.get(COOKIE_AUTH_CSRF_STATE);
     let stored_code_verifier = cookies.get(COOKIE_AUTH_CODE_VERIFIER);
 
     let (Some(csrf_state), Some(code_verifier)) = (stored_state, stored_code_verifier) else {
-        return Err(ErrorResponse::from(StatusCode::BAD_REQUEST));
+        return Err(AppError::BadRequest(
+            "The sign in attempt expired, please try again".to_string(),
+        ));
     };
 
     if csrf_state.value() != state {
-        return Err(ErrorResponse::from(StatusCode::BAD_REQUEST));
+        return Err(AppError::BadRequest(
+            "The sign in state doesn't match".to_string(),
+        ));
     }
 
-    let client = get_oauth_client().map_err(|err| {
-        eprintln!("Failed to create google auth client: {}", err);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let client = google_oauth.client()?;
 
     let code = AuthorizationCode::new(code);
     let pkce_code_verifier = PkceCodeVerifier::new(code_verifier.value().to_owned());
@@ -144,10 +226,7 @@ async fn callback(
         .set_pkce_verifier(pkce_code_verifier)
         .request_async(async_http_client)
         .await
-        .map_err(|err| {
-            eprintln!("Failed to get token response: {}", err);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .context("Failed to get token response")?;
 
     // Get the Google user info
     let google_user = reqwest::Client::new()
@@ -155,68 +234,30 @@ async fn callback(
         .bearer_auth(token_response.access_token().secret())
         .send()
         .await
-        .map_err(|err| {
-            eprintln!("Failed to get user info: {}", err);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?
+        .context("Failed to get user info")?
         .json::<GoogleUser>()
         .await
-        .map_err(|err| {
-            eprintln!("Failed to parse user info: {}", err);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .context("Failed to parse user info")?;
 
     // Check if the user exists and create a new user if they don't
     let account_email = google_user.email.clone().to_string();
-    let existing_user = user_queries::fetch_user_by_email(&pool, account_email.as_str())
-        .await
-        .context("Failed to get user")
-        .map_err(|err| {
-            eprintln!("Failed to get user: {}", err);
-            err
-        });
+    let existing_user = users.fetch_user_by_email(account_email.as_str()).await;
 
     let user = match existing_user {
         Ok(user) => user,
-        Err(_) => {
-            let new_user = user_queries::create_user(
-                &pool,
-                uuid::Uuid::new_v4(),
-                google_user.name.as_str(),
-                account_email.as_str(),
-            )
-            .await
-            .map_err(|err| {
-                eprintln!("Failed to create user: {}", err);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+        Err(sqlx::Error::RowNotFound) => {
+            let new_user = users
+                .create_user(
+                    uuid::Uuid::new_v4(),
+                    google_user.name.as_str(),
+                    account_email.as_str(),
+                )
+                .await
+                .context("Failed to create user")?;
 
             new_user
         }
-    };
-
-    // check if the user session exists already and is valid. if not create a new one
-    let user_session = user_queries::fetch_user_session_by_user_uuid(&pool, user.uuid)
-        .await
-        .context("Failed to get user session")
-        .map_err(|err| {
-            eprintln!("Failed to get user session: {}", err);
-            err
-        });
-
-    let user_session = match user_session {
-        Ok(user_session) => user_session,
-        Err(_) => {
-            let new_user_session =
-                user_queries::create_user_session(&pool, user.uuid, SESSION_DURATION)
-                    .await
-                    .map_err(|err| {
-                        eprintln!("Failed to create user session: {}", err);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?;
-
-            new_user_session
-        }
+        Err(err) => return Err(err.into()),
     };
 
     // Remove code_verifier and csrf_state cookies
@@ -228,43 +269,399 @@ async fn callback(
     remove_code_verifier.set_path("/");
     remove_code_verifier.make_removal();
 
-    let session_cookie: Cookie =
-        Cookie::build((COOKIE_AUTH_SESSION, user_session.uuid.to_string()))
-            .same_site(SameSite::Lax)
-            .http_only(true)
-            .path("/")
-            .max_age(cookie::time::Duration::milliseconds(
-                SESSION_DURATION.as_millis() as i64,
-            ))
-            .into();
-
     let cookies = CookieJar::new()
         .add(remove_csrf_cookie)
-        .add(remove_code_verifier)
-        .add(session_cookie);
+        .add(remove_code_verifier);
+
+    complete_login(
+        database.postgres(),
+        users.as_ref(),
+        &user,
+        &config,
+        &audit_context,
+        "google",
+        cookies,
+    )
+    .await
+}
+
+// Finishes a login once the user proved who they are. Users with two-factor authentication
+// get a short lived challenge for their second factor, everyone else gets a session.
+// Without PostgreSQL there is no two-factor authentication or audit log
+async fn complete_login(
+    pool: Option<&sqlx::PgPool>,
+    users: &dyn UserRepository,
+    user: &User,
+    config: &Config,
+    audit_context: &AuditContext,
+    method: &str,
+    cookies: CookieJar,
+) -> Result<Response, AppError> {
+    // Disabled accounts can't sign in no matter how they prove who they are
+    if user.is_disabled() {
+        audit::record(
+            pool,
+            audit_context,
+            AuditRecord::new(AuditEventType::LoginFailed, Some(user.uuid))
+                .metadata(serde_json::json!({ "method": method, "reason": "disabled" })),
+        )
+        .await;
+        return Err(AppError::AccountDisabled);
+    }
+
+    // Startup only promotes the listed users that already exist, the rest are promoted the
+    // first time they sign in
+    if user.role != ROLE_ADMIN && config.admin_emails.contains(&user.email) {
+        users
+            .set_user_role(user.uuid, ROLE_ADMIN)
+            .await
+            .context("Failed to promote admin")?;
+    }
+
+    if let Some(pool) = pool {
+        let user_totp = two_factor_queries::fetch_user_totp(pool, user.uuid)
+            .await
+            .context("Failed to get two-factor settings")?;
+
+        if user_totp.is_some_and(|user_totp| user_totp.enabled) {
+            let login_challenge = two_factor_queries::create_login_challenge(
+                pool,
+                user.uuid,
+                LOGIN_CHALLENGE_DURATION,
+            )
+            .await
+            .context("Failed to create login challenge")?;
+
+            let challenge_cookie: Cookie =
+                Cookie::build((COOKIE_AUTH_2FA_CHALLENGE, login_challenge.uuid.to_string()))
+                    .http_only(true)
+                    .path("/")
+                    .same_site(SameSite::Lax)
+                    .max_age(cookie::time::Duration::seconds(
+                        LOGIN_CHALLENGE_DURATION.as_secs() as i64,
+                    ))
+                    .into();
+
+            return Ok((
+                cookies.add(challenge_cookie),
+                Redirect::to(format!("{}/2fa", config.client_url).as_str()),
+            )
+                .into_response());
+        }
+    }
+
+    // Always issue a fresh session on login so an identifier planted before authentication
+    // can never be reused
+    let user_session = users
+        .create_user_session(user.uuid, config.session.idle_timeout)
+        .await
+        .context("Failed to create user session")?;
+
+    record_login(pool, audit_context, user.uuid, method).await;
+
+    let session_cookie = session_cookie(user_session.uuid, config.session.idle_timeout);
 
     Ok((
-        cookies,
-        Redirect::to(env::var("CLIENT_URL").unwrap().as_str()).into_response(),
-    ))
+        cookies.add(session_cookie),
+        Redirect::to(config.client_url.as_str()),
+    )
+        .into_response())
 }
 
+// Records a successful sign in along with the session it created. The session identifier
+// itself is never logged since it's what authenticates the user
+async fn record_login(
+    pool: Option<&sqlx::PgPool>,
+    audit_context: &AuditContext,
+    user_uuid: uuid::Uuid,
+    method: &str,
+) {
+    audit::record(
+        pool,
+        audit_context,
+        AuditRecord::new(AuditEventType::Login, Some(user_uuid))
+            .metadata(serde_json::json!({ "method": method })),
+    )
+    .await;
+    audit::record(
+        pool,
+        audit_context,
+        AuditRecord::new(AuditEventType::SessionCreated, Some(user_uuid))
+            .metadata(serde_json::json!({ "reason": "login" })),
+    )
+    .await;
+}
+
+// Emails the user a single use link that signs them in. The response is the same whether
+// or not an account exists so this can't be used to find out who has one
+#[utoipa::path(
+    post,
+    path = "/auth/magic-link",
+    tag = "auth",
+    request_body = MagicLinkRequest,
+    responses((status = 202, description = "A sign in link was sent if the account exists"))
+)]
+async fn request_magic_link(
+    Postgres(pool): Postgres,
+    State(users): State<SharedUserRepository>,
+    State(config): State<SharedConfig>,
+    State(mailer): State<SharedMailer>,
+    State(signer): State<MagicLinkSigner>,
+    State(background_tasks): State<BackgroundTasks>,
+    request: Json<MagicLinkRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let email = request.email.trim();
+
+    let user = match users.fetch_user_by_email(email).await {
+        Ok(user) if user.is_disabled() => return Ok(StatusCode::ACCEPTED),
+        Ok(user) => user,
+        Err(sqlx::Error::RowNotFound) => return Ok(StatusCode::ACCEPTED),
+        Err(err) => return Err(err.into()),
+    };
+
+    let magic_link = magic_link_queries::create_magic_link(&pool, user.uuid, MAGIC_LINK_DURATION)
+        .await
+        .context("Failed to create magic link")?;
+
+    let link = format!(
+        "{}/auth/magic-link/verify?token={}",
+        config.base_url,
+        signer.sign(magic_link.uuid)
+    );
+
+    let email = Email {
+        to: user.email,
+        subject: "Sign in to MarkdownEdit".to_string(),
+        body: format!(
+            "Hi {},\n\nUse the link below to sign in. It expires in {} minutes and can only be used once.\n\n{}\n\nIf you didn't ask for this you can ignore this email.",
+            user.username,
+            MAGIC_LINK_DURATION.as_secs() / 60,
+            link
+        ),
+    };
+
+    // Sent in the background, waiting on the mail server or failing when it's down would
+    // tell existing accounts apart from the rest
+    background_tasks.spawn(|_| async move {
+        if let Err(err) = mailer.send(email).await {
+            tracing::error!(error = format!("{err:#}"), "Failed to send magic link");
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/magic-link/verify",
+    tag = "auth",
+    params(MagicLinkVerifyRequest),
+    responses(
+        (status = 303, description = "Signs the user in, or asks for the second factor, and redirects to the client"),
+        (status = 401, description = "The link isn't valid, expired or was already used", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+async fn verify_magic_link(
+    Postgres(pool): Postgres,
+    State(users): State<SharedUserRepository>,
+    State(config): State<SharedConfig>,
+    State(signer): State<MagicLinkSigner>,
+    audit_context: AuditContext,
+    Query(query): Query<MagicLinkVerifyRequest>,
+) -> Result<Response, AppError> {
+    let Some(link_uuid) = signer.verify(&query.token) else {
+        return Err(AppError::InvalidCredentials(
+            "This sign in link isn't valid".to_string(),
+        ));
+    };
+
+    let magic_link = magic_link_queries::consume_magic_link(&pool, link_uuid)
+        .await
+        .context("Failed to use magic link")?;
+
+    let Some(magic_link) = magic_link else {
+        return Err(AppError::InvalidCredentials(
+            "This sign in link expired or was already used".to_string(),
+        ));
+    };
+
+    let user = users
+        .fetch_user_by_uuid(magic_link.user_uuid)
+        .await
+        .context("Failed to get user")?;
+
+    complete_login(
+        Some(&pool),
+        users.as_ref(),
+        &user,
+        &config,
+        &audit_context,
+        "magic_link",
+        CookieJar::new(),
+    )
+    .await
+}
+
+// Completes a login for users with two-factor authentication by checking their TOTP or
+// recovery code against the pending challenge and issuing the session
+#[utoipa::path(
+    post,
+    path = "/auth/2fa/verify",
+    tag = "auth",
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 204, description = "The code checked out and the session cookie is set"),
+        (status = 401, description = "No pending challenge or the code isn't valid", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+async fn verify_login_challenge(
+    cookies: CookieJar,
+    Postgres(pool): Postgres,
+    State(users): State<SharedUserRepository>,
+    State(session_config): State<SessionConfig>,
+    audit_context: AuditContext,
+    request: Json<TwoFactorCodeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let Some(challenge_cookie) = cookies.get(COOKIE_AUTH_2FA_CHALLENGE) else {
+        return Err(AppError::Unauthenticated);
+    };
+
+    let Ok(challenge_uuid) = uuid::Uuid::parse_str(challenge_cookie.value()) else {
+        return Err(AppError::BadRequest(
+            "The two-factor challenge is malformed".to_string(),
+        ));
+    };
+
+    let login_challenge = two_factor_queries::use_login_challenge_attempt(&pool, challenge_uuid)
+        .await
+        .context("Failed to get login challenge")?;
+
+    let Some(login_challenge) = login_challenge else {
+        return Err(AppError::InvalidCredentials(
+            "The two-factor challenge expired, please sign in again".to_string(),
+        ));
+    };
+
+    // Too many wrong codes means the user has to sign in with Google again
+    if login_challenge.attempts > LOGIN_CHALLENGE_MAX_ATTEMPTS {
+        two_factor_queries::delete_login_challenge(&pool, login_challenge.uuid)
+            .await
+            .context("Failed to delete login challenge")?;
+        audit::record(
+            &pool,
+            &audit_context,
+            AuditRecord::new(AuditEventType::LoginFailed, Some(login_challenge.user_uuid))
+                .metadata(
+                    serde_json::json!({ "method": "two_factor", "reason": "too_many_attempts" }),
+                ),
+        )
+        .await;
+        return Err(AppError::InvalidCredentials(
+            "Too many wrong codes, please sign in again".to_string(),
+        ));
+    }
+
+    let user = users
+        .fetch_user_by_uuid(login_challenge.user_uuid)
+        .await
+        .context("Failed to get user")?;
+
+    let user_totp = two_factor_queries::fetch_user_totp(&pool, user.uuid)
+        .await
+        .context("Failed to get two-factor settings")?;
+
+    let Some(user_totp) = user_totp.filter(|user_totp| user_totp.enabled) else {
+        return Err(AppError::InvalidCredentials(
+            "Two-factor authentication is no longer enabled, please sign in again".to_string(),
+        ));
+    };
+
+    // The account may have been disabled while the challenge was pending
+    if user.is_disabled() {
+        return Err(AppError::AccountDisabled);
+    }
+
+    let verified = verify_second_factor(&pool, &user, &user_totp, &request.code)
+        .await
+        .context("Failed to verify second factor")?;
+    if !verified {
+        audit::record(
+            &pool,
+            &audit_context,
+            AuditRecord::new(AuditEventType::LoginFailed, Some(user.uuid))
+                .metadata(serde_json::json!({ "method": "two_factor", "reason": "invalid_code" })),
+        )
+        .await;
+        return Err(AppError::InvalidCredentials(
+            "The code is wrong or was already used".to_string(),
+        ));
+    }
+
+    two_factor_queries::delete_login_challenge(&pool, login_challenge.uuid)
+        .await
+        .context("Failed to delete login challenge")?;
+
+    let user_session = users
+        .create_user_session(user.uuid, session_config.idle_timeout)
+        .await
+        .context("Failed to create user session")?;
+
+    record_login(Some(&pool), &audit_context, user.uuid, "two_factor").await;
+
+    let mut remove_challenge_cookie = Cookie::new(COOKIE_AUTH_2FA_CHALLENGE, "");
+    remove_challenge_cookie.set_path("/");
+    remove_challenge_cookie.make_removal();
+
+    let cookies = CookieJar::new()
+        .add(remove_challenge_cookie)
+        .add(session_cookie(
+            user_session.uuid,
+            session_config.idle_timeout,
+        ));
+
+    Ok((cookies, StatusCode::NO_CONTENT))
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/logout",
+    tag = "auth",
+    responses(
+        (status = 303, description = "Ends the session and redirects to the client"),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
 pub async fn logout(
     mut cookies: CookieJar,
-    State(pool): State<sqlx::PgPool>,
-) -> Result<impl IntoResponse, ErrorResponse> {
+    State(database): State<Database>,
+    State(users): State<SharedUserRepository>,
+    State(config): State<SharedConfig>,
+    audit_context: AuditContext,
+) -> Result<impl IntoResponse, AppError> {
     let session_cookie = cookies.get(COOKIE_AUTH_SESSION);
 
     let Some(session_cookie) = session_cookie else {
-        return Err(ErrorResponse::from(StatusCode::UNAUTHORIZED));
+        return Err(AppError::Unauthenticated);
     };
 
-    user_queries::delete_user_session(
-        &pool,
-        uuid::Uuid::parse_str(session_cookie.value()).unwrap(),
-    )
-    .await
-    .map_err(|_| ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let Ok(session_uuid) = uuid::Uuid::parse_str(session_cookie.value()) else {
+        return Err(AppError::Unauthenticated);
+    };
+
+    // Look up who is logging out before the session is gone
+    let user = users.fetch_user_by_session_uuid(session_uuid).await.ok();
+
+    users.delete_user_session(session_uuid).await?;
+
+    if let Some(user) = user {
+        audit::record(
+            database.postgres(),
+            &audit_context,
+            AuditRecord::new(AuditEventType::Logout, Some(user.uuid)),
+        )
+        .await;
+    }
 
     let mut remove_session_cookie = Cookie::new(COOKIE_AUTH_SESSION, "");
     remove_session_cookie.set_path("/");
@@ -274,6 +671,6 @@ pub async fn logout(
 
     Ok((
         cookies,
-        Redirect::to(env::var("CLIENT_URL").unwrap().as_str()).into_response(),
+        Redirect::to(config.client_url.as_str()).into_response(),
     ))
 }
@@ -1,207 +1,318 @@
 use axum::extract::State;
-use axum::http::StatusCode;
-use axum::response::ErrorResponse;
+use axum::http::HeaderMap;
 use axum::Json;
 use axum::{
     routing::{delete, get, post, put},
     Router,
 };
-use axum_extra::extract::cookie::CookieJar;
+use utoipa::ToSchema;
 use uuid::Uuid;
+use validator::Validate;
 
-use crate::db::document_queries;
+use crate::config::SharedConfig;
+use crate::db::connection::Database;
+use crate::db::repository::SharedDocumentRepository;
+use crate::error::{AppError, ProblemDetails};
+use crate::models::audit_event::AuditEventType;
 use crate::models::document::Document;
-use crate::utils::helpers::check_user_session;
-
-pub fn document_routes(pool: sqlx::PgPool) -> Router {
-    Router::new()
-        .route("/:uuid", get(get_document_by_uuid))
-        .route("/all", get(get_all_documents_by_user_uuid))
-        .route("/create", post(create_document))
-        .route("/update/:uuid", put(update_document))
-        .route("/delete/:uuid", delete(delete_document))
-        .with_state(pool)
+use crate::routes::{router_from, RouteTable};
+use crate::state::AppState;
+use crate::utils::audit::{self, AuditContext, AuditRecord};
+use crate::utils::auth::AuthUser;
+use crate::utils::constants::DOCUMENT_TITLE_MAX_LENGTH;
+use crate::utils::validation::{validate_document_content, validate_uuid, ValidatedJson};
+use crate::utils::workspace::resolve_workspace;
+
+// What the client sends to create a document. The client may pick the document's uuid so
+// it can refer to it before the response arrives. Fields are optional so missing ones are
+// reported alongside any other invalid field
+#[derive(Debug, serde::Deserialize, Validate, ToSchema)]
+struct CreateDocumentRequest {
+    #[validate(custom(function = "validate_uuid"))]
+    #[schema(format = Uuid)]
+    uuid: Option<String>,
+    #[validate(
+        required(message = "Is required"),
+        length(
+            min = 1,
+            max = DOCUMENT_TITLE_MAX_LENGTH,
+            message = "Has to be between 1 and 255 characters"
+        )
+    )]
+    #[schema(required = true, nullable = false, min_length = 1, max_length = 255)]
+    title: Option<String>,
+    #[validate(
+        required(message = "Is required"),
+        custom(function = "validate_document_content")
+    )]
+    #[schema(required = true, nullable = false)]
+    content: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, Validate, ToSchema)]
+struct UpdateDocumentRequest {
+    #[validate(
+        required(message = "Is required"),
+        length(
+            min = 1,
+            max = DOCUMENT_TITLE_MAX_LENGTH,
+            message = "Has to be between 1 and 255 characters"
+        )
+    )]
+    #[schema(required = true, nullable = false, min_length = 1, max_length = 255)]
+    title: Option<String>,
+    #[validate(
+        required(message = "Is required"),
+        custom(function = "validate_document_content")
+    )]
+    #[schema(required = true, nullable = false)]
+    content: Option<String>,
+}
+
+pub fn document_routes() -> Router<AppState> {
+    router_from(route_table())
+}
+
+pub fn route_table() -> RouteTable {
+    vec![
+        ("/:uuid", get(get_document_by_uuid)),
+        ("/all", get(get_all_documents_by_user_uuid)),
+        ("/create", post(create_document)),
+        ("/update/:uuid", put(update_document)),
+        ("/delete/:uuid", delete(delete_document)),
+    ]
 }
 
+#[utoipa::path(
+    get,
+    path = "/documents/{uuid}",
+    tag = "documents",
+    params(("uuid" = Uuid, Path, description = "Document id"), ("x-workspace-id" = Option<String>, Header, description = "Organisation to work in, personal documents when missing")),
+    responses(
+        (status = 200, body = Document),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such document in the workspace", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
 async fn get_document_by_uuid(
-    cookies: CookieJar,
-    State(pool): State<sqlx::PgPool>,
+    AuthUser(user): AuthUser,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    State(documents): State<SharedDocumentRepository>,
     params: axum::extract::Path<String>,
-) -> Result<Json<Document>, ErrorResponse> {
+) -> Result<Json<Document>, AppError> {
     // Parse the UUID from the request parameters
-    let uuid = match Uuid::parse_str(&params) {
-        Ok(uuid) => uuid,
-        Err(_) => {
-            return Err(ErrorResponse::from(StatusCode::BAD_REQUEST));
-        }
-    };
+    let uuid = Uuid::parse_str(&params).map_err(|_| {
+        AppError::BadRequest("The document identifier isn't a valid UUID".to_string())
+    })?;
 
-    // Check if the user is logged in
-    let user_uuid = match check_user_session(cookies, pool.clone()).await {
-        Ok(user) => user.uuid,
-        Err(err) => {
-            eprintln!("Database error: {:?}", err);
-            return Err(ErrorResponse::from(StatusCode::UNAUTHORIZED));
-        }
-    };
+    // Resolve which workspace the request works on
+    let workspace = resolve_workspace(&headers, database.postgres(), &user).await?;
 
     // Fetch the document from the database
-    let document = match document_queries::fetch_document_by_uuid(&pool, uuid, user_uuid).await {
-        Ok(document) => document,
-        Err(err) => {
-            eprintln!("Database error: {}", err);
-            return Err(ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR));
-        }
-    };
+    let document = documents
+        .fetch_document_by_uuid(uuid, user.uuid, workspace.organisation_uuid())
+        .await?;
 
     Ok(Json(document))
 }
 
+#[utoipa::path(
+    get,
+    path = "/documents/all",
+    tag = "documents",
+    params(("x-workspace-id" = Option<String>, Header, description = "Organisation to work in, personal documents when missing")),
+    responses(
+        (status = 200, body = Vec<Document>),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
 async fn get_all_documents_by_user_uuid(
-    cookies: CookieJar,
-    State(pool): State<sqlx::PgPool>,
-) -> Result<Json<Vec<Document>>, ErrorResponse> {
-    // Check if the user is logged in
-    let user_uuid = match check_user_session(cookies, pool.clone()).await {
-        Ok(user) => user.uuid,
-        Err(err) => {
-            eprintln!("Database error: {:?}", err);
-            return Err(ErrorResponse::from(StatusCode::UNAUTHORIZED));
-        }
-    };
+    AuthUser(user): AuthUser,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    State(documents): State<SharedDocumentRepository>,
+) -> Result<Json<Vec<Document>>, AppError> {
+    // Resolve which workspace the request works on
+    let workspace = resolve_workspace(&headers, database.postgres(), &user).await?;
 
     // Fetch all documents from the database
-    let documents = match document_queries::fetch_all_documents_for_user(&pool, user_uuid).await {
-        Ok(documents) => documents,
-        Err(err) => {
-            eprintln!("Database error: {}", err);
-            return Err(ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR));
-        }
-    };
+    let documents = documents
+        .fetch_all_documents_for_user(user.uuid, workspace.organisation_uuid())
+        .await?;
 
     Ok(Json(documents))
 }
 
+#[utoipa::path(
+    post,
+    path = "/documents/create",
+    tag = "documents",
+    params(("x-workspace-id" = Option<String>, Header, description = "Organisation to work in, personal documents when missing")),
+    request_body = CreateDocumentRequest,
+    responses(
+        (status = 200, body = Document),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "A document with the uuid already exists", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 413, description = "The document is larger than the quota allows", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid fields", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 507, description = "The user's document or storage quota is used up", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
 async fn create_document(
-    cookies: CookieJar,
-    State(pool): State<sqlx::PgPool>,
-    request: Json<Document>,
-) -> Result<Json<Document>, ErrorResponse> {
-    // Check if the user is logged in
-    let user_uuid = match check_user_session(cookies, pool.clone()).await {
-        Ok(user) => user.uuid,
-        Err(err) => {
-            eprintln!("Database error: {:?}", err);
-            return Err(ErrorResponse::from(StatusCode::UNAUTHORIZED));
-        }
+    AuthUser(user): AuthUser,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    State(documents): State<SharedDocumentRepository>,
+    State(config): State<SharedConfig>,
+    audit_context: AuditContext,
+    ValidatedJson(request): ValidatedJson<CreateDocumentRequest>,
+) -> Result<Json<Document>, AppError> {
+    // Resolve which workspace the request works on
+    let workspace = resolve_workspace(&headers, database.postgres(), &user).await?;
+
+    // The body was validated by the extractor, so the uuid parses and the title is present
+    let uuid = match request.uuid.as_deref() {
+        Some(uuid) => Uuid::parse_str(uuid)
+            .map_err(|_| AppError::BadRequest("The uuid isn't valid".to_string()))?,
+        None => Uuid::new_v4(),
     };
 
-    // Parse the request body
-    let request_body = request.0;
-    let title = request_body.title;
-    let content = request_body.content;
-    let uuid = request_body.uuid;
-
-    // Create the document in the database
-    let document = match document_queries::create_document(
-        &pool,
-        uuid.clone().unwrap_or(Uuid::new_v4()),
-        user_uuid,
-        title.clone().expect("title is required").as_str(),
-        content.clone().expect("content is required").as_str(),
+    // Create the document in the database. New documents count towards the quota of the
+    // user writing them
+    let document = documents
+        .create_document_within_quota(
+            uuid,
+            user.uuid,
+            workspace.organisation_uuid(),
+            request.title.as_deref().unwrap_or_default(),
+            request.content.as_deref().unwrap_or_default(),
+            &config.quotas,
+        )
+        .await??;
+
+    audit::record(
+        database.postgres(),
+        &audit_context,
+        AuditRecord::new(AuditEventType::DocumentCreated, Some(user.uuid))
+            .target("document", document.uuid)
+            .organisation(document.organisation_uuid),
     )
-    .await
-    {
-        Ok(document) => document,
-        Err(err) => {
-            eprintln!("Database error: {}", err);
-            return Err(ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR));
-        }
-    };
+    .await;
 
     Ok(Json(document))
 }
 
+#[utoipa::path(
+    put,
+    path = "/documents/update/{uuid}",
+    tag = "documents",
+    params(("uuid" = Uuid, Path, description = "Document id"), ("x-workspace-id" = Option<String>, Header, description = "Organisation to work in, personal documents when missing")),
+    request_body = UpdateDocumentRequest,
+    responses(
+        (status = 200, body = Document),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such document in the workspace", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 413, description = "The document is larger than the quota allows", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid fields", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 507, description = "The user's storage quota is used up", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+#[allow(clippy::too_many_arguments)]
 async fn update_document(
-    cookies: CookieJar,
-    State(pool): State<sqlx::PgPool>,
+    AuthUser(user): AuthUser,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    State(documents): State<SharedDocumentRepository>,
+    State(config): State<SharedConfig>,
+    audit_context: AuditContext,
     params: axum::extract::Path<String>,
-    request: Json<Document>,
-) -> Result<Json<Document>, ErrorResponse> {
-    // Check if the user is logged in
-    let user_uuid = match check_user_session(cookies, pool.clone()).await {
-        Ok(user) => user.uuid,
-        Err(err) => {
-            eprintln!("Database error: {:?}", err);
-            return Err(ErrorResponse::from(StatusCode::UNAUTHORIZED));
-        }
-    };
+    ValidatedJson(request): ValidatedJson<UpdateDocumentRequest>,
+) -> Result<Json<Document>, AppError> {
+    // Resolve which workspace the request works on
+    let workspace = resolve_workspace(&headers, database.postgres(), &user).await?;
 
     // Parse the UUID from the request parameters
-    let uuid = match Uuid::parse_str(&params) {
-        Ok(uuid) => uuid,
-        Err(_) => {
-            return Err(ErrorResponse::from(StatusCode::BAD_REQUEST));
-        }
-    };
+    let uuid = Uuid::parse_str(&params).map_err(|_| {
+        AppError::BadRequest("The document identifier isn't a valid UUID".to_string())
+    })?;
+
+    // Update the document in the database, the extractor made sure both fields are present.
+    // The document stays with its author, so a change counts towards their quota even when
+    // someone else in the organisation makes it
+    let document = documents
+        .update_document_within_quota(
+            uuid,
+            user.uuid,
+            workspace.organisation_uuid(),
+            request.title.as_deref().unwrap_or_default(),
+            request.content.as_deref().unwrap_or_default(),
+            &config.quotas,
+        )
+        .await??;
 
-    // Parse the request body
-    let request_body = request.0;
-    let title = request_body.title;
-    let content = request_body.content;
-    let updated_at = request_body.updated_at;
-
-    // Update the document in the database
-    let document = match document_queries::update_document(
-        &pool,
-        uuid,
-        user_uuid,
-        title.clone().expect("title is required").as_str(),
-        content.clone().expect("content is required").as_str(),
-        updated_at.clone().expect("updated_at is required").as_str(),
+    audit::record(
+        database.postgres(),
+        &audit_context,
+        AuditRecord::new(AuditEventType::DocumentUpdated, Some(user.uuid))
+            .target("document", document.uuid)
+            .organisation(document.organisation_uuid),
     )
-    .await
-    {
-        Ok(document) => document,
-        Err(err) => {
-            eprintln!("Database error: {}", err);
-            return Err(ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR));
-        }
-    };
+    .await;
 
     Ok(Json(document))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/documents/delete/{uuid}",
+    tag = "documents",
+    params(("uuid" = Uuid, Path, description = "Document id"), ("x-workspace-id" = Option<String>, Header, description = "Organisation to work in, personal documents when missing")),
+    responses(
+        (status = 200, description = "The deleted document", body = Document),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such document, or one the user can't delete", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
 async fn delete_document(
-    cookies: CookieJar,
-    State(pool): State<sqlx::PgPool>,
+    AuthUser(user): AuthUser,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    State(documents): State<SharedDocumentRepository>,
+    audit_context: AuditContext,
     params: axum::extract::Path<String>,
-) -> Result<Json<Document>, ErrorResponse> {
+) -> Result<Json<Document>, AppError> {
     // Parse the UUID from the request parameters
-    let uuid = match Uuid::parse_str(&params) {
-        Ok(uuid) => uuid,
-        Err(_) => {
-            return Err(ErrorResponse::from(StatusCode::BAD_REQUEST));
-        }
-    };
+    let uuid = Uuid::parse_str(&params).map_err(|_| {
+        AppError::BadRequest("The document identifier isn't a valid UUID".to_string())
+    })?;
 
-    // Check if the user is logged in
-    let user_uuid = match check_user_session(cookies, pool.clone()).await {
-        Ok(user) => user.uuid,
-        Err(err) => {
-            eprintln!("Database error: {:?}", err);
-            return Err(ErrorResponse::from(StatusCode::UNAUTHORIZED));
-        }
-    };
+    // Resolve which workspace the request works on
+    let workspace = resolve_workspace(&headers, database.postgres(), &user).await?;
 
     // Delete the document from the database
-    let document = match document_queries::delete_document(&pool, uuid, user_uuid).await {
-        Ok(document) => document,
-        Err(err) => {
-            eprintln!("Database error: {}", err);
-            return Err(ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR));
-        }
-    };
+    let document = documents
+        .delete_document(
+            uuid,
+            user.uuid,
+            workspace.organisation_uuid(),
+            workspace.can_manage_documents(),
+        )
+        .await?;
+
+    audit::record(
+        database.postgres(),
+        &audit_context,
+        AuditRecord::new(AuditEventType::DocumentDeleted, Some(user.uuid))
+            .target("document", document.uuid)
+            .organisation(document.organisation_uuid),
+    )
+    .await;
 
     Ok(Json(document))
 }
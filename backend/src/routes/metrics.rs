@@ -0,0 +1,52 @@
+use axum::extract::State;
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE};
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use axum::{routing::get, Router};
+use sha2::{Digest, Sha256};
+
+use crate::error::{AppError, ProblemDetails};
+use crate::routes::{router_from, RouteTable};
+use crate::state::AppState;
+
+pub fn metrics_routes() -> Router<AppState> {
+    router_from(route_table())
+}
+
+pub fn route_table() -> RouteTable {
+    vec![("/metrics", get(metrics_handler))]
+}
+
+// Renders what has been recorded so far, the database gauges are refreshed in the background
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "metrics",
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text format", body = String, content_type = "text/plain"),
+        (status = 401, description = "METRICS_TOKEN is set and the request doesn't carry it", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security((), ("metrics_token" = []))
+)]
+async fn metrics_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(token) = &state.config.metrics_token {
+        let provided = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+
+        // Compare digests so the check takes the same time however much of the token matches
+        if Sha256::digest(provided.as_bytes()) != Sha256::digest(token.as_bytes()) {
+            return Err(AppError::Unauthenticated);
+        }
+    }
+
+    Ok((
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics_handle.render(),
+    ))
+}
@@ -1,3 +1,26 @@
+pub mod admin;
 pub mod auth;
 pub mod documents;
+pub mod health;
+pub mod metrics;
+pub mod openapi;
+pub mod organisations;
+pub mod two_factor;
 pub mod users;
+
+use axum::routing::MethodRouter;
+use axum::Router;
+
+use crate::state::AppState;
+
+// The paths of a router along with their handlers. The routers the OpenAPI spec describes
+// are built from one, so the spec tests can list every path they serve
+pub type RouteTable = Vec<(&'static str, MethodRouter<AppState>)>;
+
+pub fn router_from(table: RouteTable) -> Router<AppState> {
+    table
+        .into_iter()
+        .fold(Router::new(), |router, (path, handlers)| {
+            router.route(path, handlers)
+        })
+}
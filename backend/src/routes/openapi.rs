@@ -0,0 +1,327 @@
+use axum::Router;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::error::{FieldError, ProblemDetails};
+use crate::routes::{admin, auth, documents, health, metrics, organisations, two_factor, users};
+use crate::state::AppState;
+use crate::utils::constants::COOKIE_AUTH_SESSION;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "MarkdownEdit API",
+        description = "Errors are returned as RFC 7807 problem details with a stable `code`"
+    ),
+    paths(
+        auth::login,
+        auth::callback,
+        auth::request_magic_link,
+        auth::verify_magic_link,
+        auth::verify_login_challenge,
+        auth::logout,
+        users::get_user_by_uuid,
+        users::get_audit_events,
+        users::update_profile,
+        users::get_usage,
+        users::get_preferences,
+        users::update_preferences,
+        users::delete_user,
+        users::cancel_user_deletion,
+        users::request_export,
+        users::get_export,
+        users::download_export,
+        two_factor::get_two_factor_status,
+        two_factor::enroll_totp,
+        two_factor::verify_totp_enrollment,
+        two_factor::disable_totp,
+        two_factor::regenerate_recovery_codes,
+        documents::get_document_by_uuid,
+        documents::get_all_documents_by_user_uuid,
+        documents::create_document,
+        documents::update_document,
+        documents::delete_document,
+        organisations::get_organisations,
+        organisations::create_organisation,
+        organisations::get_organisation,
+        organisations::update_organisation,
+        organisations::delete_organisation,
+        organisations::update_member_role,
+        organisations::delete_member,
+        organisations::get_invites,
+        organisations::create_invite,
+        organisations::delete_invite,
+        organisations::accept_invite,
+        admin::get_users,
+        admin::disable_user,
+        admin::enable_user,
+        admin::update_user_role,
+        admin::delete_user_sessions,
+        admin::get_storage_usage,
+        admin::get_audit_events,
+        health::healthz,
+        health::readyz,
+        metrics::metrics_handler,
+    ),
+    components(schemas(ProblemDetails, FieldError)),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "auth", description = "Signing in and out"),
+        (name = "users", description = "The signed in user's account"),
+        (name = "two-factor", description = "Authenticator apps and recovery codes for the signed in user"),
+        (name = "documents", description = "Markdown documents in the personal or an organisation workspace"),
+        (name = "organisations", description = "Organisations the signed in user belongs to, their members and invites"),
+        (name = "admin", description = "Managing every account, for users with the admin role"),
+        (name = "health", description = "Liveness and readiness probes"),
+        (name = "metrics", description = "Prometheus metrics"),
+    )
+)]
+pub struct ApiDoc;
+
+// Signed in requests are authenticated by the session cookie, scrapes of /metrics by
+// METRICS_TOKEN when one is set
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(COOKIE_AUTH_SESSION))),
+        );
+        components.add_security_scheme(
+            "metrics_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+// Serves the spec at /openapi.json and Swagger UI at /docs
+pub fn openapi_routes() -> Router<AppState> {
+    SwaggerUi::new("/docs")
+        .url("/openapi.json", ApiDoc::openapi())
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashSet};
+
+    use axum::body::Body;
+    use axum::extract::{MatchedPath, Request};
+    use axum::http::{HeaderValue, Method, StatusCode};
+    use axum::middleware::{self, Next};
+    use axum::response::Response;
+    use sqlx::postgres::PgPoolOptions;
+    use tower::ServiceExt;
+    use utoipa::openapi::path::HttpMethod;
+
+    use super::*;
+    use crate::app;
+    use crate::db::connection::Database;
+    use crate::routes::{router_from, RouteTable};
+
+    const MATCHED_PATH_HEADER: &str = "x-matched-path";
+
+    // The routers the spec describes, by the prefix app::router nests them under
+    fn documented_routers() -> [(&'static str, RouteTable); 8] {
+        [
+            ("/auth", auth::route_table()),
+            ("/users", users::route_table()),
+            ("/users/me/2fa", two_factor::route_table()),
+            ("/documents", documents::route_table()),
+            ("/organisations", organisations::route_table()),
+            ("/admin", admin::route_table()),
+            ("", health::route_table()),
+            ("", metrics::route_table()),
+        ]
+    }
+
+    fn test_state() -> AppState {
+        // Nothing here reaches the database, so the pool never has to connect
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/markdown_edit")
+            .expect("valid database URL");
+
+        AppState::for_tests(Database::Postgres(pool))
+    }
+
+    fn test_app() -> Router {
+        app::router(test_state()).layer(middleware::from_fn(echo_matched_path))
+    }
+
+    // The documented routers without the session check in front, which would answer before
+    // axum gets to reject a method the route doesn't have
+    fn unauthenticated_routers() -> Router {
+        documented_routers()
+            .into_iter()
+            .fold(Router::new(), |router, (prefix, table)| match prefix {
+                "" => router.merge(router_from(table)),
+                prefix => router.nest(prefix, router_from(table)),
+            })
+            .with_state(test_state())
+    }
+
+    // Tells the test which route, if any, handled the request
+    async fn echo_matched_path(request: Request, next: Next) -> Response {
+        let matched_path = request.extensions().get::<MatchedPath>().cloned();
+        let mut response = next.run(request).await;
+
+        if let Some(matched_path) = matched_path {
+            response.headers_mut().insert(
+                MATCHED_PATH_HEADER,
+                HeaderValue::from_str(matched_path.as_str()).unwrap(),
+            );
+        }
+
+        response
+    }
+
+    // "/documents/{uuid}" in the spec is "/documents/:uuid" in axum
+    fn to_axum_path(path: &str) -> String {
+        path.split('/')
+            .map(|segment| match segment.strip_prefix('{') {
+                Some(param) => format!(":{}", param.trim_end_matches('}')),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    fn to_method(method: &HttpMethod) -> Method {
+        match method {
+            HttpMethod::Get => Method::GET,
+            HttpMethod::Post => Method::POST,
+            HttpMethod::Put => Method::PUT,
+            HttpMethod::Patch => Method::PATCH,
+            HttpMethod::Delete => Method::DELETE,
+            HttpMethod::Head => Method::HEAD,
+            HttpMethod::Options => Method::OPTIONS,
+            HttpMethod::Trace => Method::TRACE,
+        }
+    }
+
+    fn documented_operations() -> HashSet<(String, Method)> {
+        let spec = ApiDoc::openapi();
+        let mut operations = HashSet::new();
+
+        for (path, item) in &spec.paths.paths {
+            let operations_by_method = [
+                (HttpMethod::Get, &item.get),
+                (HttpMethod::Post, &item.post),
+                (HttpMethod::Put, &item.put),
+                (HttpMethod::Patch, &item.patch),
+                (HttpMethod::Delete, &item.delete),
+            ];
+            for (method, operation) in operations_by_method {
+                if operation.is_some() {
+                    operations.insert((to_axum_path(path), to_method(&method)));
+                }
+            }
+        }
+
+        operations
+    }
+
+    // Every path the documented routers serve, the way axum matches them once nested
+    fn registered_paths() -> BTreeSet<String> {
+        documented_routers()
+            .into_iter()
+            .flat_map(|(prefix, table)| {
+                table.into_iter().map(move |(path, _)| match path {
+                    "/" => prefix.to_string(),
+                    path => format!("{prefix}{path}"),
+                })
+            })
+            .collect()
+    }
+
+    async fn send(app: &Router, method: Method, path: &str) -> Response {
+        // Any value works for path parameters since the session check runs first
+        let uri = path
+            .split('/')
+            .map(|segment| match segment.starts_with(':') {
+                true => uuid::Uuid::nil().to_string(),
+                false => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn documented_operations_are_routed() {
+        let app = test_app();
+
+        for (path, method) in documented_operations() {
+            let response = send(&app, method.clone(), &path).await;
+
+            assert_ne!(
+                response.status(),
+                StatusCode::METHOD_NOT_ALLOWED,
+                "{method} {path} is documented but the route doesn't accept {method}"
+            );
+            assert_eq!(
+                response
+                    .headers()
+                    .get(MATCHED_PATH_HEADER)
+                    .and_then(|value| value.to_str().ok()),
+                Some(path.as_str()),
+                "{method} {path} is documented but no route matches it"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn routes_are_documented() {
+        let operations = documented_operations();
+        let documented_paths: BTreeSet<String> =
+            operations.iter().map(|(path, _)| path.clone()).collect();
+
+        assert_eq!(
+            registered_paths(),
+            documented_paths,
+            "The routes and the OpenAPI spec list different paths"
+        );
+
+        // Every method a documented path accepts has to be documented as well
+        let routers = unauthenticated_routers();
+        for path in &documented_paths {
+            for method in [
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ] {
+                if operations.contains(&(path.clone(), method.clone())) {
+                    continue;
+                }
+
+                let response = send(&routers, method.clone(), path).await;
+                assert_eq!(
+                    response.status(),
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "{method} {path} is routed but not documented"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn spec_is_valid_json() {
+        let spec = ApiDoc::openapi().to_json().expect("spec serializes");
+        let spec: serde_json::Value = serde_json::from_str(&spec).unwrap();
+
+        assert_eq!(spec["openapi"], "3.1.0");
+        assert!(spec["components"]["schemas"]["Document"].is_object());
+        assert!(spec["components"]["schemas"]["User"].is_object());
+    }
+}
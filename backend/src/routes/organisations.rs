@@ -0,0 +1,621 @@
+use anyhow::Context;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::Response;
+use axum::{
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::config::SharedConfig;
+use crate::db::organisation_queries;
+use crate::error::{AppError, ProblemDetails};
+use crate::mailer::{Email, SharedMailer};
+use crate::models::audit_event::AuditEventType;
+use crate::models::organisation::{
+    Organisation, OrganisationInvite, OrganisationMember, OrganisationMembership,
+};
+use crate::models::user::User;
+use crate::routes::{router_from, RouteTable};
+use crate::state::AppState;
+use crate::utils::audit::{self, AuditContext, AuditRecord};
+use crate::utils::auth::AuthUser;
+use crate::utils::constants::{
+    INVITE_EMAIL_MAX_LENGTH, ORGANISATION_INVITE_DURATION, ORGANISATION_NAME_MAX_LENGTH,
+    ORG_ROLE_MEMBER, ORG_ROLE_OWNER,
+};
+use crate::utils::postgres::Postgres;
+use crate::utils::validation::{
+    validate_invite_role, validate_not_blank, validate_organisation_role, validate_uuid,
+    ValidatedJson,
+};
+use crate::utils::workspace::is_organisation_admin;
+
+// The name is stored trimmed
+#[derive(Debug, serde::Deserialize, Validate, ToSchema)]
+struct OrganisationRequest {
+    #[validate(
+        required(message = "Is required"),
+        length(
+            min = 1,
+            max = ORGANISATION_NAME_MAX_LENGTH,
+            message = "Has to be between 1 and 255 characters"
+        ),
+        custom(function = "validate_not_blank")
+    )]
+    #[schema(required = true, nullable = false, min_length = 1, max_length = 255)]
+    name: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, Validate, ToSchema)]
+struct MemberRoleRequest {
+    #[validate(
+        required(message = "Is required"),
+        custom(function = "validate_organisation_role")
+    )]
+    #[schema(required = true, nullable = false, example = "admin")]
+    role: Option<String>,
+}
+
+// The role defaults to member
+#[derive(Debug, serde::Deserialize, Validate, ToSchema)]
+struct InviteRequest {
+    #[validate(
+        required(message = "Is required"),
+        length(
+            max = INVITE_EMAIL_MAX_LENGTH,
+            message = "Can't be longer than 255 characters"
+        ),
+        email(message = "Has to be an email address")
+    )]
+    #[schema(required = true, nullable = false, max_length = 255)]
+    email: Option<String>,
+    #[validate(custom(function = "validate_invite_role"))]
+    #[schema(example = "member")]
+    role: Option<String>,
+}
+
+// The token is the one in the link of the invite email
+#[derive(Debug, serde::Deserialize, Validate, ToSchema)]
+struct AcceptInviteRequest {
+    #[validate(required(message = "Is required"), custom(function = "validate_uuid"))]
+    #[schema(required = true, nullable = false, format = Uuid)]
+    token: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct OrganisationDetails {
+    #[serde(flatten)]
+    organisation: Organisation,
+    role: String,
+    members: Vec<OrganisationMember>,
+}
+
+pub fn organisation_routes() -> Router<AppState> {
+    router_from(route_table())
+}
+
+pub fn route_table() -> RouteTable {
+    vec![
+        ("/", get(get_organisations).post(create_organisation)),
+        (
+            "/:uuid",
+            get(get_organisation)
+                .put(update_organisation)
+                .delete(delete_organisation),
+        ),
+        (
+            "/:uuid/members/:user_uuid",
+            put(update_member_role).delete(delete_member),
+        ),
+        ("/:uuid/invites", get(get_invites).post(create_invite)),
+        ("/:uuid/invites/:invite_uuid", delete(delete_invite)),
+        ("/invites/accept", post(accept_invite)),
+    ]
+}
+
+// Helper function to fetch the user's role in an organisation. Non-members get a 404 so
+// they can't tell which organisations exist
+async fn member_role(
+    pool: &sqlx::PgPool,
+    organisation_uuid: Uuid,
+    user: &User,
+) -> Result<String, AppError> {
+    let role = organisation_queries::fetch_member_role(pool, organisation_uuid, user.uuid).await?;
+
+    role.ok_or_else(|| AppError::NotFound("The organisation doesn't exist".to_string()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/organisations",
+    tag = "organisations",
+    responses(
+        (status = 200, description = "The organisations the user is a member of, with their role", body = Vec<OrganisationMembership>),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn get_organisations(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+) -> Result<Json<Vec<OrganisationMembership>>, AppError> {
+    let organisations =
+        organisation_queries::fetch_organisations_for_user(&pool, user.uuid).await?;
+
+    Ok(Json(organisations))
+}
+
+#[utoipa::path(
+    post,
+    path = "/organisations",
+    tag = "organisations",
+    request_body = OrganisationRequest,
+    responses(
+        (status = 200, description = "The organisation, owned by the user", body = Organisation),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "A field is missing or isn't valid", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn create_organisation(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    audit_context: AuditContext,
+    ValidatedJson(request): ValidatedJson<OrganisationRequest>,
+) -> Result<Json<Organisation>, AppError> {
+    let name = request.name.as_deref().unwrap_or_default().trim();
+
+    let organisation =
+        organisation_queries::create_organisation(&pool, Uuid::new_v4(), name, user.uuid).await?;
+
+    audit::record(
+        &pool,
+        &audit_context,
+        AuditRecord::new(AuditEventType::OrganisationCreated, Some(user.uuid))
+            .target("organisation", organisation.uuid)
+            .organisation(Some(organisation.uuid)),
+    )
+    .await;
+
+    Ok(Json(organisation))
+}
+
+#[utoipa::path(
+    get,
+    path = "/organisations/{uuid}",
+    tag = "organisations",
+    params(("uuid" = Uuid, Path, description = "Organisation id")),
+    responses(
+        (status = 200, body = OrganisationDetails),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such organisation, or the user isn't a member", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn get_organisation(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<OrganisationDetails>, AppError> {
+    let role = member_role(&pool, uuid, &user).await?;
+
+    let organisation = organisation_queries::fetch_organisation_by_uuid(&pool, uuid).await?;
+
+    let members = organisation_queries::fetch_members(&pool, uuid).await?;
+
+    Ok(Json(OrganisationDetails {
+        organisation,
+        role,
+        members,
+    }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/organisations/{uuid}",
+    tag = "organisations",
+    params(("uuid" = Uuid, Path, description = "Organisation id")),
+    request_body = OrganisationRequest,
+    responses(
+        (status = 200, body = Organisation),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Only organisation admins can rename it", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such organisation, or the user isn't a member", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "A field is missing or isn't valid", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn update_organisation(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    Path(uuid): Path<Uuid>,
+    ValidatedJson(request): ValidatedJson<OrganisationRequest>,
+) -> Result<Json<Organisation>, AppError> {
+    if !is_organisation_admin(&member_role(&pool, uuid, &user).await?) {
+        return Err(AppError::Forbidden(
+            "Only organisation admins can rename it".to_string(),
+        ));
+    }
+
+    let name = request.name.as_deref().unwrap_or_default().trim();
+
+    let organisation = organisation_queries::update_organisation_name(&pool, uuid, name).await?;
+
+    Ok(Json(organisation))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/organisations/{uuid}",
+    tag = "organisations",
+    params(("uuid" = Uuid, Path, description = "Organisation id")),
+    responses(
+        (status = 200, description = "The organisation and its documents are deleted"),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Only owners can delete the organisation", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such organisation, or the user isn't a member", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn delete_organisation(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    audit_context: AuditContext,
+    Path(uuid): Path<Uuid>,
+) -> Result<Response<Body>, AppError> {
+    // Only owners can delete an organisation and everything in it
+    if member_role(&pool, uuid, &user).await? != ORG_ROLE_OWNER {
+        return Err(AppError::Forbidden(
+            "Only owners can delete the organisation".to_string(),
+        ));
+    }
+
+    organisation_queries::delete_organisation(&pool, uuid).await?;
+
+    audit::record(
+        &pool,
+        &audit_context,
+        AuditRecord::new(AuditEventType::OrganisationDeleted, Some(user.uuid))
+            .target("organisation", uuid)
+            .organisation(Some(uuid)),
+    )
+    .await;
+
+    Ok(Response::new(Body::empty()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/organisations/{uuid}/members/{user_uuid}",
+    tag = "organisations",
+    params(("uuid" = Uuid, Path, description = "Organisation id"), ("user_uuid" = Uuid, Path, description = "Member's user id")),
+    request_body = MemberRoleRequest,
+    responses(
+        (status = 200, description = "The role is changed"),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Only admins change roles and only owners change ownership", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such organisation or member", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "The organisation would be left without an owner", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "A field is missing or isn't valid", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn update_member_role(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    audit_context: AuditContext,
+    Path((uuid, member_uuid)): Path<(Uuid, Uuid)>,
+    ValidatedJson(request): ValidatedJson<MemberRoleRequest>,
+) -> Result<Response<Body>, AppError> {
+    let role = member_role(&pool, uuid, &user).await?;
+    if !is_organisation_admin(&role) {
+        return Err(AppError::Forbidden(
+            "Only organisation admins can change roles".to_string(),
+        ));
+    }
+
+    let new_role = request.role.as_deref().unwrap_or_default();
+
+    let member_role = organisation_queries::fetch_member_role(&pool, uuid, member_uuid).await?;
+    let Some(member_role) = member_role else {
+        return Err(AppError::NotFound(
+            "The user isn't a member of the organisation".to_string(),
+        ));
+    };
+
+    // Only owners can hand out or take away ownership
+    if (new_role == ORG_ROLE_OWNER || member_role == ORG_ROLE_OWNER) && role != ORG_ROLE_OWNER {
+        return Err(AppError::Forbidden(
+            "Only owners can change ownership".to_string(),
+        ));
+    }
+
+    // An organisation always keeps at least one owner
+    if member_role == ORG_ROLE_OWNER && new_role != ORG_ROLE_OWNER {
+        ensure_another_owner(&pool, uuid).await?;
+    }
+
+    organisation_queries::update_member_role(&pool, uuid, member_uuid, new_role).await?;
+
+    audit::record(
+        &pool,
+        &audit_context,
+        AuditRecord::new(
+            AuditEventType::OrganisationMemberRoleChanged,
+            Some(user.uuid),
+        )
+        .target("user", member_uuid)
+        .organisation(Some(uuid))
+        .metadata(serde_json::json!({ "from": member_role, "to": new_role })),
+    )
+    .await;
+
+    Ok(Response::new(Body::empty()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/organisations/{uuid}/members/{user_uuid}",
+    tag = "organisations",
+    params(("uuid" = Uuid, Path, description = "Organisation id"), ("user_uuid" = Uuid, Path, description = "Member's user id, the user's own to leave")),
+    responses(
+        (status = 200, description = "The member is removed"),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Only admins remove other members and only owners remove owners", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such organisation or member", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "The organisation would be left without an owner", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn delete_member(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    audit_context: AuditContext,
+    Path((uuid, member_uuid)): Path<(Uuid, Uuid)>,
+) -> Result<Response<Body>, AppError> {
+    // Members can always leave, removing someone else takes an admin
+    let role = member_role(&pool, uuid, &user).await?;
+    if member_uuid != user.uuid && !is_organisation_admin(&role) {
+        return Err(AppError::Forbidden(
+            "Only organisation admins can remove other members".to_string(),
+        ));
+    }
+
+    let member_role = organisation_queries::fetch_member_role(&pool, uuid, member_uuid).await?;
+    let Some(member_role) = member_role else {
+        return Err(AppError::NotFound(
+            "The user isn't a member of the organisation".to_string(),
+        ));
+    };
+
+    if member_role == ORG_ROLE_OWNER {
+        if member_uuid != user.uuid && role != ORG_ROLE_OWNER {
+            return Err(AppError::Forbidden(
+                "Only owners can remove another owner".to_string(),
+            ));
+        }
+        ensure_another_owner(&pool, uuid).await?;
+    }
+
+    organisation_queries::delete_member(&pool, uuid, member_uuid).await?;
+
+    audit::record(
+        &pool,
+        &audit_context,
+        AuditRecord::new(AuditEventType::OrganisationMemberRemoved, Some(user.uuid))
+            .target("user", member_uuid)
+            .organisation(Some(uuid))
+            .metadata(serde_json::json!({ "role": member_role })),
+    )
+    .await;
+
+    Ok(Response::new(Body::empty()))
+}
+
+// Helper function to refuse changes that would leave an organisation without an owner
+async fn ensure_another_owner(
+    pool: &sqlx::PgPool,
+    organisation_uuid: Uuid,
+) -> Result<(), AppError> {
+    let owners = organisation_queries::count_owners(pool, organisation_uuid).await?;
+
+    if owners <= 1 {
+        return Err(AppError::Conflict(
+            "An organisation needs at least one owner".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/organisations/{uuid}/invites",
+    tag = "organisations",
+    params(("uuid" = Uuid, Path, description = "Organisation id")),
+    responses(
+        (status = 200, description = "Invites that are neither accepted nor expired", body = Vec<OrganisationInvite>),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Only organisation admins can see invites", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such organisation, or the user isn't a member", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn get_invites(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<Vec<OrganisationInvite>>, AppError> {
+    if !is_organisation_admin(&member_role(&pool, uuid, &user).await?) {
+        return Err(AppError::Forbidden(
+            "Only organisation admins can see invites".to_string(),
+        ));
+    }
+
+    let invites = organisation_queries::fetch_pending_invites(&pool, uuid).await?;
+
+    Ok(Json(invites))
+}
+
+#[utoipa::path(
+    post,
+    path = "/organisations/{uuid}/invites",
+    tag = "organisations",
+    params(("uuid" = Uuid, Path, description = "Organisation id")),
+    request_body = InviteRequest,
+    responses(
+        (status = 200, description = "The invite, emailed to the address", body = OrganisationInvite),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Only organisation admins can invite people", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such organisation, or the user isn't a member", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "A field is missing or isn't valid", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn create_invite(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    audit_context: AuditContext,
+    State(config): State<SharedConfig>,
+    State(mailer): State<SharedMailer>,
+    Path(uuid): Path<Uuid>,
+    ValidatedJson(request): ValidatedJson<InviteRequest>,
+) -> Result<Json<OrganisationInvite>, AppError> {
+    if !is_organisation_admin(&member_role(&pool, uuid, &user).await?) {
+        return Err(AppError::Forbidden(
+            "Only organisation admins can invite people".to_string(),
+        ));
+    }
+
+    let role = request.role.as_deref().unwrap_or(ORG_ROLE_MEMBER);
+    let email = request.email.as_deref().unwrap_or_default();
+
+    let organisation = organisation_queries::fetch_organisation_by_uuid(&pool, uuid).await?;
+
+    let invite = organisation_queries::create_invite(
+        &pool,
+        uuid,
+        email,
+        role,
+        user.uuid,
+        ORGANISATION_INVITE_DURATION,
+    )
+    .await?;
+
+    let link = format!("{}/invites/accept?token={}", config.client_url, invite.uuid);
+
+    let email = Email {
+        to: invite.email.clone(),
+        subject: format!("You've been invited to {} on MarkdownEdit", organisation.name),
+        body: format!(
+            "{} invited you to join {} on MarkdownEdit.\n\nSign in with this email address and open the link below to accept. It expires in {} days.\n\n{}",
+            user.username,
+            organisation.name,
+            ORGANISATION_INVITE_DURATION.as_secs() / (60 * 60 * 24),
+            link
+        ),
+    };
+
+    mailer.send(email).await.context("Failed to send invite")?;
+
+    audit::record(
+        &pool,
+        &audit_context,
+        AuditRecord::new(AuditEventType::OrganisationInviteCreated, Some(user.uuid))
+            .target("invite", invite.uuid)
+            .organisation(Some(uuid))
+            .metadata(serde_json::json!({ "email": invite.email, "role": invite.role })),
+    )
+    .await;
+
+    Ok(Json(invite))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/organisations/{uuid}/invites/{invite_uuid}",
+    tag = "organisations",
+    params(("uuid" = Uuid, Path, description = "Organisation id"), ("invite_uuid" = Uuid, Path, description = "Invite id")),
+    responses(
+        (status = 200, description = "The invite is revoked"),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Only organisation admins can revoke invites", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such organisation, or the user isn't a member", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn delete_invite(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    audit_context: AuditContext,
+    Path((uuid, invite_uuid)): Path<(Uuid, Uuid)>,
+) -> Result<Response<Body>, AppError> {
+    if !is_organisation_admin(&member_role(&pool, uuid, &user).await?) {
+        return Err(AppError::Forbidden(
+            "Only organisation admins can revoke invites".to_string(),
+        ));
+    }
+
+    organisation_queries::delete_invite(&pool, uuid, invite_uuid).await?;
+
+    audit::record(
+        &pool,
+        &audit_context,
+        AuditRecord::new(AuditEventType::OrganisationInviteRevoked, Some(user.uuid))
+            .target("invite", invite_uuid)
+            .organisation(Some(uuid)),
+    )
+    .await;
+
+    Ok(Response::new(Body::empty()))
+}
+
+// Sent by the client's invite page, which has the invitee sign in first when the link in
+// the invite email is opened signed out. The signed in user's email has to match the invite
+#[utoipa::path(
+    post,
+    path = "/organisations/invites/accept",
+    tag = "organisations",
+    request_body = AcceptInviteRequest,
+    responses(
+        (status = 200, description = "The user is a member now", body = OrganisationInvite),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "The invite doesn't exist, expired or is for another email address", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "A field is missing or isn't valid", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn accept_invite(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    audit_context: AuditContext,
+    ValidatedJson(request): ValidatedJson<AcceptInviteRequest>,
+) -> Result<Json<OrganisationInvite>, AppError> {
+    // The body was validated by the extractor, so the token parses
+    let token = Uuid::parse_str(request.token.as_deref().unwrap_or_default())
+        .map_err(|_| AppError::BadRequest("The token isn't valid".to_string()))?;
+
+    let invite = organisation_queries::accept_invite(&pool, token, user.uuid, &user.email).await?;
+
+    let Some(invite) = invite else {
+        return Err(AppError::NotFound(
+            "The invite doesn't exist, expired or is for another email address".to_string(),
+        ));
+    };
+
+    audit::record(
+        &pool,
+        &audit_context,
+        AuditRecord::new(AuditEventType::OrganisationMemberAdded, Some(user.uuid))
+            .target("user", user.uuid)
+            .organisation(Some(invite.organisation_uuid))
+            .metadata(serde_json::json!({ "role": invite.role, "invite_uuid": invite.uuid })),
+    )
+    .await;
+
+    Ok(Json(invite))
+}
@@ -0,0 +1,274 @@
+use anyhow::Context;
+use axum::body::Body;
+use axum::http::Response;
+use axum::Json;
+use axum::{
+    routing::{get, post},
+    Router,
+};
+
+use crate::db::two_factor_queries;
+use crate::error::{AppError, ProblemDetails};
+use crate::models::audit_event::AuditEventType;
+use crate::routes::{router_from, RouteTable};
+use crate::state::AppState;
+use crate::utils::audit::{self, AuditContext, AuditRecord};
+use crate::utils::auth::AuthUser;
+use crate::utils::helpers::verify_second_factor;
+use crate::utils::postgres::Postgres;
+use crate::utils::totp;
+
+// What the client sends to confirm a second factor
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct TwoFactorCodeRequest {
+    pub code: String,
+}
+
+// What the client needs to add the account to an authenticator app
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+struct TotpEnrollmentResponse {
+    otpauth_uri: String,
+    qr_svg: String,
+    secret: String,
+}
+
+// Shown once, only their hashes are stored
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+struct RecoveryCodesResponse {
+    recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+struct TwoFactorStatusResponse {
+    enabled: bool,
+    recovery_codes_remaining: i64,
+}
+
+pub fn two_factor_routes() -> Router<AppState> {
+    router_from(route_table())
+}
+
+pub fn route_table() -> RouteTable {
+    vec![
+        ("/", get(get_two_factor_status)),
+        ("/totp", post(enroll_totp).delete(disable_totp)),
+        ("/totp/verify", post(verify_totp_enrollment)),
+        ("/recovery-codes", post(regenerate_recovery_codes)),
+    ]
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/me/2fa",
+    tag = "two-factor",
+    responses(
+        (status = 200, body = TwoFactorStatusResponse),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn get_two_factor_status(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+) -> Result<Json<TwoFactorStatusResponse>, AppError> {
+    let user_totp = two_factor_queries::fetch_user_totp(&pool, user.uuid).await?;
+
+    let enabled = user_totp.is_some_and(|user_totp| user_totp.enabled);
+    let recovery_codes_remaining = if enabled {
+        two_factor_queries::count_unused_recovery_codes(&pool, user.uuid).await?
+    } else {
+        0
+    };
+
+    Ok(Json(TwoFactorStatusResponse {
+        enabled,
+        recovery_codes_remaining,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/2fa/totp",
+    tag = "two-factor",
+    responses(
+        (status = 200, description = "A pending enrollment, confirmed with a code from the authenticator", body = TotpEnrollmentResponse),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Two-factor authentication is already enabled", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn enroll_totp(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+) -> Result<Json<TotpEnrollmentResponse>, AppError> {
+    // A user has to disable their current authenticator before enrolling a new one
+    let existing_totp = two_factor_queries::fetch_user_totp(&pool, user.uuid).await?;
+    if existing_totp.is_some_and(|user_totp| user_totp.enabled) {
+        return Err(AppError::Conflict(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+
+    // Store the secret as pending until the user proves their authenticator works
+    let secret = totp::generate_secret();
+    two_factor_queries::upsert_pending_user_totp(&pool, user.uuid, &secret).await?;
+
+    let otpauth_uri = totp::build_totp(&secret, &user.email)
+        .context("Failed to build TOTP")?
+        .get_url();
+    let qr_svg = totp::qr_code_svg(&otpauth_uri).context("Failed to render QR code")?;
+
+    Ok(Json(TotpEnrollmentResponse {
+        otpauth_uri,
+        qr_svg,
+        secret,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/2fa/totp/verify",
+    tag = "two-factor",
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, description = "Two-factor authentication is on", body = RecoveryCodesResponse),
+        (status = 401, description = "Not signed in, or the code isn't valid", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "There is no pending enrollment", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn verify_totp_enrollment(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    audit_context: AuditContext,
+    request: Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, AppError> {
+    let user_totp = two_factor_queries::fetch_user_totp(&pool, user.uuid).await?;
+
+    // There has to be a pending enrollment to confirm
+    let Some(user_totp) = user_totp.filter(|user_totp| !user_totp.enabled) else {
+        return Err(AppError::Conflict(
+            "There is no pending two-factor enrollment".to_string(),
+        ));
+    };
+
+    let Some(step) = totp::verify_code(&user_totp, &user.email, &request.code) else {
+        return Err(AppError::InvalidCredentials(
+            "The code isn't valid".to_string(),
+        ));
+    };
+
+    let recovery_codes = totp::generate_recovery_codes();
+    let recovery_code_hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|code| totp::hash_recovery_code(code))
+        .collect();
+
+    two_factor_queries::enable_user_totp(&pool, user.uuid, step, &recovery_code_hashes).await?;
+
+    audit::record(
+        &pool,
+        &audit_context,
+        AuditRecord::new(AuditEventType::TwoFactorEnabled, Some(user.uuid)),
+    )
+    .await;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/me/2fa/totp",
+    tag = "two-factor",
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, description = "Two-factor authentication is off"),
+        (status = 401, description = "Not signed in, or the code isn't valid", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn disable_totp(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    audit_context: AuditContext,
+    request: Json<TwoFactorCodeRequest>,
+) -> Result<Response<Body>, AppError> {
+    let user_totp = two_factor_queries::fetch_user_totp(&pool, user.uuid).await?;
+
+    // A pending enrollment can be discarded without a code, an enabled one can't
+    let was_enabled = user_totp
+        .as_ref()
+        .is_some_and(|user_totp| user_totp.enabled);
+    if let Some(user_totp) = user_totp.filter(|user_totp| user_totp.enabled) {
+        let verified = verify_second_factor(&pool, &user, &user_totp, &request.code).await?;
+        if !verified {
+            return Err(AppError::InvalidCredentials(
+                "The code isn't valid".to_string(),
+            ));
+        }
+    }
+
+    two_factor_queries::delete_user_totp(&pool, user.uuid).await?;
+
+    if was_enabled {
+        audit::record(
+            &pool,
+            &audit_context,
+            AuditRecord::new(AuditEventType::TwoFactorDisabled, Some(user.uuid)),
+        )
+        .await;
+    }
+
+    Ok(Response::new(Body::empty()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/2fa/recovery-codes",
+    tag = "two-factor",
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, description = "New codes, the old ones no longer work", body = RecoveryCodesResponse),
+        (status = 401, description = "Not signed in, or the code isn't valid", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Two-factor authentication isn't enabled", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn regenerate_recovery_codes(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    audit_context: AuditContext,
+    request: Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, AppError> {
+    let user_totp = two_factor_queries::fetch_user_totp(&pool, user.uuid).await?;
+
+    let Some(user_totp) = user_totp.filter(|user_totp| user_totp.enabled) else {
+        return Err(AppError::Conflict(
+            "Two-factor authentication isn't enabled".to_string(),
+        ));
+    };
+
+    let verified = verify_second_factor(&pool, &user, &user_totp, &request.code).await?;
+    if !verified {
+        return Err(AppError::InvalidCredentials(
+            "The code isn't valid".to_string(),
+        ));
+    }
+
+    let recovery_codes = totp::generate_recovery_codes();
+    let recovery_code_hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|code| totp::hash_recovery_code(code))
+        .collect();
+
+    two_factor_queries::replace_recovery_codes(&pool, user.uuid, &recovery_code_hashes).await?;
+
+    audit::record(
+        &pool,
+        &audit_context,
+        AuditRecord::new(AuditEventType::RecoveryCodesRegenerated, Some(user.uuid)),
+    )
+    .await;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
@@ -1,61 +1,526 @@
-use axum::body::Body;
-use axum::extract::State;
-use axum::http::{Response, StatusCode};
-use axum::response::ErrorResponse;
+use axum::extract::{Path, Query, State};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
 use axum::Json;
 use axum::{
-    routing::{delete, get},
+    routing::{delete, get, post},
     Router,
 };
-use axum_extra::extract::cookie::CookieJar;
+use axum_extra::extract::cookie::{Cookie, CookieJar};
+use chrono::Utc;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
 
-use crate::db::user_queries;
+use crate::config::SharedConfig;
+use crate::db::audit_queries;
+use crate::db::connection::Database;
+use crate::db::repository::{
+    SharedDocumentRepository, SharedExportRepository, SharedUserRepository,
+};
+use crate::error::{AppError, ProblemDetails};
+use crate::models::audit_event::{AuditEvent, AuditEventFilter, AuditEventType};
+use crate::models::data_export::DataExport;
 use crate::models::user::User;
-use crate::utils::helpers::check_user_session;
+use crate::models::user_preferences::UserPreferences;
+use crate::models::user_usage::UserUsage;
+use crate::routes::{router_from, RouteTable};
+use crate::state::AppState;
+use crate::utils::audit::{self, AuditContext, AuditRecord};
+use crate::utils::auth::AuthUser;
+use crate::utils::constants::{
+    AVATAR_URL_MAX_LENGTH, COOKIE_AUTH_SESSION, DISPLAY_NAME_MAX_LENGTH, EDITOR_FONT_SIZE_MAX,
+    EDITOR_FONT_SIZE_MIN, EXPORT_BUILD_DURATION, EXPORT_STATUS_FAILED, EXPORT_STATUS_READY,
+};
+use crate::utils::data_export;
+use crate::utils::postgres::Postgres;
+use crate::utils::quota::QuotaConfig;
+use crate::utils::shutdown::BackgroundTasks;
+use crate::utils::validation::{
+    deserialize_nullable, validate_avatar_url, validate_export_format, validate_keybinding_mode,
+    validate_preview_layout, validate_theme, ValidatedJson,
+};
 
-pub fn users_routes(pool: sqlx::PgPool) -> Router {
-    Router::new()
-        .route("/me", get(get_user_by_uuid))
-        .route("/delete", delete(delete_user))
-        .with_state(pool)
+// Only the fields that are sent change. Leaving one out keeps it, null clears it so the
+// client falls back to what Google provided
+#[derive(Debug, serde::Deserialize, Validate, ToSchema)]
+struct UpdateProfileRequest {
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    #[validate(length(
+        min = 1,
+        max = DISPLAY_NAME_MAX_LENGTH,
+        message = "Has to be between 1 and 64 characters"
+    ))]
+    #[schema(value_type = Option<String>, min_length = 1, max_length = 64)]
+    display_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    #[validate(
+        length(max = AVATAR_URL_MAX_LENGTH, message = "Can't be longer than 2048 characters"),
+        custom(function = "validate_avatar_url")
+    )]
+    #[schema(value_type = Option<String>, max_length = 2048)]
+    avatar_url: Option<Option<String>>,
 }
 
-async fn get_user_by_uuid(
-    cookies: CookieJar,
-    State(pool): State<sqlx::PgPool>,
-) -> Result<Json<User>, ErrorResponse> {
-    // Check if the user is logged in and fetch the user from the database
-    let user = match check_user_session(cookies, pool.clone()).await {
-        Ok(user) => user,
-        Err(err) => {
-            eprintln!("Database error: {:?}", err);
-            return Err(ErrorResponse::from(StatusCode::UNAUTHORIZED));
-        }
-    };
+// Preferences are replaced as a whole, so every field is required
+#[derive(Debug, serde::Deserialize, Validate, ToSchema)]
+struct UpdatePreferencesRequest {
+    #[validate(required(message = "Is required"), custom(function = "validate_theme"))]
+    #[schema(required = true, nullable = false, example = "system")]
+    theme: Option<String>,
+    #[validate(
+        required(message = "Is required"),
+        range(
+            min = EDITOR_FONT_SIZE_MIN,
+            max = EDITOR_FONT_SIZE_MAX,
+            message = "Has to be between 8 and 32"
+        )
+    )]
+    #[schema(required = true, nullable = false, minimum = 8, maximum = 32)]
+    editor_font_size: Option<i32>,
+    #[validate(
+        required(message = "Is required"),
+        custom(function = "validate_keybinding_mode")
+    )]
+    #[schema(required = true, nullable = false, example = "default")]
+    keybinding_mode: Option<String>,
+    #[validate(
+        required(message = "Is required"),
+        custom(function = "validate_export_format")
+    )]
+    #[schema(required = true, nullable = false, example = "markdown")]
+    default_export_format: Option<String>,
+    #[validate(
+        required(message = "Is required"),
+        custom(function = "validate_preview_layout")
+    )]
+    #[schema(required = true, nullable = false, example = "split")]
+    preview_layout: Option<String>,
+}
+
+pub fn users_routes() -> Router<AppState> {
+    router_from(route_table())
+}
+
+pub fn route_table() -> RouteTable {
+    vec![
+        ("/me", get(get_user_by_uuid).patch(update_profile)),
+        (
+            "/me/preferences",
+            get(get_preferences).put(update_preferences),
+        ),
+        ("/me/usage", get(get_usage)),
+        ("/me/audit", get(get_audit_events)),
+        ("/me/export", post(request_export)),
+        ("/me/export/:uuid", get(get_export)),
+        ("/me/export/:uuid/download", get(download_export)),
+        ("/delete", delete(delete_user)),
+        ("/delete/cancel", post(cancel_user_deletion)),
+    ]
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/me",
+    tag = "users",
+    responses(
+        (status = 200, body = User),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn get_user_by_uuid(AuthUser(user): AuthUser) -> Result<Json<User>, AppError> {
+    Ok(Json(user))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/users/me",
+    tag = "users",
+    request_body = UpdateProfileRequest,
+    responses(
+        (status = 200, body = User),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "A field isn't valid", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn update_profile(
+    AuthUser(user): AuthUser,
+    State(database): State<Database>,
+    State(users): State<SharedUserRepository>,
+    audit_context: AuditContext,
+    ValidatedJson(request): ValidatedJson<UpdateProfileRequest>,
+) -> Result<Json<User>, AppError> {
+    let display_name = request.display_name.unwrap_or(user.display_name);
+    let avatar_url = request.avatar_url.unwrap_or(user.avatar_url);
+
+    let user = users
+        .update_user_profile(user.uuid, display_name.as_deref(), avatar_url.as_deref())
+        .await?;
+
+    audit::record(
+        database.postgres(),
+        &audit_context,
+        AuditRecord::new(AuditEventType::AccountProfileUpdated, Some(user.uuid))
+            .target("user", user.uuid),
+    )
+    .await;
 
     Ok(Json(user))
 }
 
+#[utoipa::path(
+    get,
+    path = "/users/me/preferences",
+    tag = "users",
+    responses(
+        (status = 200, description = "The saved preferences, or the defaults when there are none", body = UserPreferences),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn get_preferences(
+    AuthUser(user): AuthUser,
+    State(users): State<SharedUserRepository>,
+) -> Result<Json<UserPreferences>, AppError> {
+    let preferences = users.fetch_user_preferences(user.uuid).await?;
+
+    Ok(Json(preferences))
+}
+
+#[utoipa::path(
+    put,
+    path = "/users/me/preferences",
+    tag = "users",
+    request_body = UpdatePreferencesRequest,
+    responses(
+        (status = 200, body = UserPreferences),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "A field is missing or isn't valid", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn update_preferences(
+    AuthUser(user): AuthUser,
+    State(users): State<SharedUserRepository>,
+    ValidatedJson(request): ValidatedJson<UpdatePreferencesRequest>,
+) -> Result<Json<UserPreferences>, AppError> {
+    // Validation made sure every field is there
+    let preferences = UserPreferences {
+        user_uuid: user.uuid,
+        theme: request.theme.unwrap_or_default(),
+        editor_font_size: request.editor_font_size.unwrap_or_default(),
+        keybinding_mode: request.keybinding_mode.unwrap_or_default(),
+        default_export_format: request.default_export_format.unwrap_or_default(),
+        preview_layout: request.preview_layout.unwrap_or_default(),
+        updated_at: None,
+    };
+
+    let preferences = users.update_user_preferences(&preferences).await?;
+
+    Ok(Json(preferences))
+}
+
+// What the user stores next to what they may store, for showing how much room is left
+#[derive(Debug, Serialize, ToSchema)]
+struct UsageResponse {
+    #[serde(flatten)]
+    usage: UserUsage,
+    #[serde(flatten)]
+    quota: QuotaConfig,
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/me/usage",
+    tag = "users",
+    responses(
+        (status = 200, body = UsageResponse),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn get_usage(
+    AuthUser(user): AuthUser,
+    State(documents): State<SharedDocumentRepository>,
+    State(config): State<SharedConfig>,
+) -> Result<Json<UsageResponse>, AppError> {
+    let usage = documents.fetch_usage(user.uuid).await?;
+
+    Ok(Json(UsageResponse {
+        usage,
+        quota: config.quotas,
+    }))
+}
+
+// Lets users review the security and document events recorded for their own account
+#[utoipa::path(
+    get,
+    path = "/users/me/audit",
+    tag = "users",
+    params(AuditEventFilter),
+    responses(
+        (status = 200, body = Vec<AuditEvent>),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn get_audit_events(
+    AuthUser(user): AuthUser,
+    Postgres(pool): Postgres,
+    Query(mut filter): Query<AuditEventFilter>,
+) -> Result<Json<Vec<AuditEvent>>, AppError> {
+    // Users only ever see their own events
+    filter.user_uuid = Some(user.uuid);
+
+    let events = audit_queries::fetch_audit_events(&pool, &filter).await?;
+
+    Ok(Json(events))
+}
+
+// Deletes the account right away, or with a grace period configured signs it out everywhere
+// and deletes it once the grace period is over
+#[utoipa::path(
+    delete,
+    path = "/users/delete",
+    tag = "users",
+    responses(
+        (status = 200, description = "The account was deleted"),
+        (status = 202, description = "The account is signed out and will be deleted at deletion_scheduled_at", body = User),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
 async fn delete_user(
+    AuthUser(user): AuthUser,
     cookies: CookieJar,
-    State(pool): State<sqlx::PgPool>,
-) -> Result<Response<Body>, ErrorResponse> {
-    // Check if the user is logged in
-    let user = match check_user_session(cookies, pool.clone()).await {
-        Ok(user) => user,
-        Err(err) => {
-            eprintln!("Database error: {:?}", err);
-            return Err(ErrorResponse::from(StatusCode::UNAUTHORIZED));
+    State(database): State<Database>,
+    State(users): State<SharedUserRepository>,
+    State(config): State<SharedConfig>,
+    audit_context: AuditContext,
+) -> Result<Response, AppError> {
+    let mut remove_session_cookie = Cookie::new(COOKIE_AUTH_SESSION, "");
+    remove_session_cookie.set_path("/");
+    remove_session_cookie.make_removal();
+    let cookies = cookies.add(remove_session_cookie);
+
+    if config.account_deletion_grace_period.is_zero() {
+        users.delete_user(user.uuid).await?;
+
+        audit::record(
+            database.postgres(),
+            &audit_context,
+            AuditRecord::new(AuditEventType::AccountDeleted, Some(user.uuid))
+                .target("user", user.uuid),
+        )
+        .await;
+
+        return Ok((StatusCode::OK, cookies).into_response());
+    }
+
+    // Asking again keeps the date the account was first scheduled for
+    let deletion_scheduled_at = user
+        .deletion_scheduled_at
+        .unwrap_or_else(|| Utc::now() + config.account_deletion_grace_period);
+    let user = users
+        .schedule_user_deletion(user.uuid, Some(deletion_scheduled_at))
+        .await?;
+    users.delete_user_sessions(user.uuid).await?;
+
+    audit::record(
+        database.postgres(),
+        &audit_context,
+        AuditRecord::new(AuditEventType::AccountDeletionScheduled, Some(user.uuid))
+            .target("user", user.uuid)
+            .metadata(serde_json::json!({ "deletion_scheduled_at": deletion_scheduled_at })),
+    )
+    .await;
+
+    Ok((StatusCode::ACCEPTED, cookies, Json(user)).into_response())
+}
+
+// Keeps an account that is waiting out its grace period. The user signs in again to get here
+#[utoipa::path(
+    post,
+    path = "/users/delete/cancel",
+    tag = "users",
+    responses(
+        (status = 200, description = "The account is no longer scheduled for deletion", body = User),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "The account isn't scheduled for deletion", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn cancel_user_deletion(
+    AuthUser(user): AuthUser,
+    State(database): State<Database>,
+    State(users): State<SharedUserRepository>,
+    audit_context: AuditContext,
+) -> Result<Json<User>, AppError> {
+    if user.deletion_scheduled_at.is_none() {
+        return Err(AppError::Conflict(
+            "The account isn't scheduled for deletion".to_string(),
+        ));
+    }
+
+    let user = users.schedule_user_deletion(user.uuid, None).await?;
+
+    audit::record(
+        database.postgres(),
+        &audit_context,
+        AuditRecord::new(AuditEventType::AccountDeletionCancelled, Some(user.uuid))
+            .target("user", user.uuid),
+    )
+    .await;
+
+    Ok(Json(user))
+}
+
+// An export along with the link to download it from once it's ready
+#[derive(Debug, Serialize, ToSchema)]
+struct DataExportResponse {
+    #[serde(flatten)]
+    export: DataExport,
+    download_url: Option<String>,
+}
+
+impl DataExportResponse {
+    fn new(export: DataExport, config: &SharedConfig) -> Self {
+        let download_url = (export.status == EXPORT_STATUS_READY).then(|| {
+            format!(
+                "{}/users/me/export/{}/download",
+                config.base_url, export.uuid
+            )
+        });
+
+        DataExportResponse {
+            export,
+            download_url,
         }
-    };
+    }
+}
 
-    // Delete the user from the database and return a success response
-    user_queries::delete_user(&pool, user.uuid)
-        .await
-        .map_err(|err| {
-            eprintln!("Database error: {}", err);
-            ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR)
+// Starts building an archive of everything stored about the user. It's built in the
+// background, the client polls the export until it's ready to download
+#[utoipa::path(
+    post,
+    path = "/users/me/export",
+    tag = "users",
+    responses(
+        (status = 202, description = "The archive is being built", body = DataExportResponse),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "An export is already being built", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+async fn request_export(
+    AuthUser(user): AuthUser,
+    State(database): State<Database>,
+    State(users): State<SharedUserRepository>,
+    State(documents): State<SharedDocumentRepository>,
+    State(exports): State<SharedExportRepository>,
+    State(config): State<SharedConfig>,
+    State(background_tasks): State<BackgroundTasks>,
+    audit_context: AuditContext,
+) -> Result<impl IntoResponse, AppError> {
+    // One build at a time per user. A build that never finishes, e.g. because the server
+    // crashed, expires and stops blocking the next one
+    let export = exports
+        .create_export(
+            Uuid::new_v4(),
+            user.uuid,
+            Utc::now() + EXPORT_BUILD_DURATION,
+        )
+        .await?
+        .ok_or_else(|| {
+            AppError::Conflict("An export is already being built for this account".to_string())
         })?;
 
-    Ok(Response::new(Body::empty()))
+    audit::record(
+        database.postgres(),
+        &audit_context,
+        AuditRecord::new(AuditEventType::AccountExportRequested, Some(user.uuid))
+            .target("export", export.uuid),
+    )
+    .await;
+
+    let export_uuid = export.uuid;
+    background_tasks.spawn(|cancel| {
+        data_export::build_export(users, documents, exports, user, export_uuid, cancel)
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(DataExportResponse::new(export, &config)),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/me/export/{uuid}",
+    tag = "users",
+    params(("uuid" = Uuid, Path, description = "Export id")),
+    responses(
+        (status = 200, body = DataExportResponse),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such export, or it expired", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn get_export(
+    AuthUser(user): AuthUser,
+    State(exports): State<SharedExportRepository>,
+    State(config): State<SharedConfig>,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<DataExportResponse>, AppError> {
+    let export = exports.fetch_export(uuid, user.uuid).await?;
+
+    Ok(Json(DataExportResponse::new(export, &config)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/me/export/{uuid}/download",
+    tag = "users",
+    params(("uuid" = Uuid, Path, description = "Export id")),
+    responses(
+        (status = 200, description = "The zip archive", content_type = "application/zip"),
+        (status = 401, description = "Not signed in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such export, or it expired", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "The archive isn't ready, or building it failed", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("session" = []))
+)]
+async fn download_export(
+    AuthUser(user): AuthUser,
+    State(exports): State<SharedExportRepository>,
+    Path(uuid): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let Some(archive) = exports.fetch_export_archive(uuid, user.uuid).await? else {
+        // Tell a missing export apart from one that isn't done yet
+        let export = exports.fetch_export(uuid, user.uuid).await?;
+        return Err(match export.status.as_str() {
+            EXPORT_STATUS_FAILED => {
+                AppError::Conflict("Building the export failed, request a new one".to_string())
+            }
+            _ => AppError::Conflict("The export isn't ready yet".to_string()),
+        });
+    };
+
+    let file_name = format!("markdown-edit-export-{}.zip", Utc::now().format("%Y-%m-%d"));
+
+    Ok((
+        [
+            (CONTENT_TYPE, "application/zip".to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        archive,
+    ))
 }
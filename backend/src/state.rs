@@ -0,0 +1,137 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use axum::extract::FromRef;
+use metrics_exporter_prometheus::PrometheusHandle;
+
+use crate::config::SharedConfig;
+use crate::db::connection::Database;
+use crate::db::repository::{
+    SharedDocumentRepository, SharedExportRepository, SharedUserRepository,
+};
+use crate::mailer::SharedMailer;
+use crate::routes::auth::GoogleOAuth;
+use crate::utils::magic_link::MagicLinkSigner;
+use crate::utils::rate_limit::RateLimiter;
+use crate::utils::session::SessionConfig;
+use crate::utils::shutdown::BackgroundTasks;
+
+// Everything the handlers share, built once in main.rs and handed to every router. Handlers
+// take the part they need with State<...>, e.g. State<SharedDocumentRepository>
+#[derive(Clone)]
+pub struct AppState {
+    pub database: Database,
+    pub users: SharedUserRepository,
+    pub documents: SharedDocumentRepository,
+    pub exports: SharedExportRepository,
+    pub config: SharedConfig,
+    pub mailer: SharedMailer,
+    pub magic_link_signer: MagicLinkSigner,
+    // Built once at startup rather than on every sign in
+    pub google_oauth: GoogleOAuth,
+    pub rate_limiter: RateLimiter,
+    pub metrics_handle: PrometheusHandle,
+    // Set once the server starts shutting down so traffic is routed elsewhere
+    pub shutting_down: Arc<AtomicBool>,
+    pub background_tasks: BackgroundTasks,
+}
+
+impl FromRef<AppState> for Database {
+    fn from_ref(state: &AppState) -> Self {
+        state.database.clone()
+    }
+}
+
+impl FromRef<AppState> for SharedUserRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.users.clone()
+    }
+}
+
+impl FromRef<AppState> for SharedDocumentRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.documents.clone()
+    }
+}
+
+impl FromRef<AppState> for SharedExportRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.exports.clone()
+    }
+}
+
+impl FromRef<AppState> for SharedConfig {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for SessionConfig {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.session
+    }
+}
+
+impl FromRef<AppState> for SharedMailer {
+    fn from_ref(state: &AppState) -> Self {
+        state.mailer.clone()
+    }
+}
+
+impl FromRef<AppState> for MagicLinkSigner {
+    fn from_ref(state: &AppState) -> Self {
+        state.magic_link_signer.clone()
+    }
+}
+
+impl FromRef<AppState> for GoogleOAuth {
+    fn from_ref(state: &AppState) -> Self {
+        state.google_oauth.clone()
+    }
+}
+
+impl FromRef<AppState> for RateLimiter {
+    fn from_ref(state: &AppState) -> Self {
+        state.rate_limiter.clone()
+    }
+}
+
+impl FromRef<AppState> for BackgroundTasks {
+    fn from_ref(state: &AppState) -> Self {
+        state.background_tasks.clone()
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    // State for tests. Emails only go to the log, and Google sign in only works when the
+    // environment has credentials
+    pub fn for_tests(database: Database) -> Self {
+        let config = crate::config::Config::from_args(&[
+            "--database-url",
+            "postgres://localhost/markdown_edit",
+            "--client-url",
+            "http://localhost:5173",
+            "--base-url",
+            "http://localhost:8080",
+        ])
+        .expect("test configuration is valid");
+
+        AppState {
+            users: database.users(),
+            documents: database.documents(),
+            exports: database.exports(),
+            database,
+            google_oauth: GoogleOAuth::new(&config.base_url),
+            rate_limiter: RateLimiter::new(config.rate_limit, config.trust_forwarded_for),
+            config: Arc::new(config),
+            mailer: Arc::new(crate::mailer::file::FileMailer::new(None)),
+            magic_link_signer: MagicLinkSigner::from_env(),
+            metrics_handle: metrics_exporter_prometheus::PrometheusBuilder::new()
+                .build_recorder()
+                .handle(),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            background_tasks: BackgroundTasks::new(),
+        }
+    }
+}
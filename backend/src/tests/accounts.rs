@@ -0,0 +1,163 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use serde_json::json;
+
+use super::harness::TestApp;
+use crate::utils::account_deletion;
+
+#[tokio::test]
+async fn deleting_an_account_removes_its_sessions_and_documents() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let phone = app.new_session(alice.user.clone()).await;
+    let bob = app.sign_in("bob").await;
+
+    for user in [&alice, &bob] {
+        let response = app
+            .post(
+                "/documents/create",
+                Some(user),
+                json!({ "title": "Notes", "content": "" }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+    }
+
+    let response = app.delete("/users/delete", Some(&alice)).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    // Every session the account had is gone, not only the one that asked
+    for session in [&alice, &phone] {
+        let response = app.get("/users/me", Some(session)).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    }
+
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents WHERE user_uuid = $1")
+        .bind(alice.user.uuid)
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(remaining, 0);
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE uuid = $1")
+        .bind(alice.user.uuid)
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(remaining, 0);
+
+    // Other accounts aren't affected
+    let response = app.get("/documents/all", Some(&bob)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body.as_array().unwrap().len(), 1);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn deleting_an_account_needs_a_session() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    let response = app.delete("/users/delete", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.get("/users/me", Some(&alice)).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn deleting_an_account_with_a_grace_period_can_be_cancelled() {
+    let Some(app) = TestApp::spawn_with(|config| {
+        config.account_deletion_grace_period = Duration::from_secs(60 * 60 * 24 * 7);
+    })
+    .await
+    else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let phone = app.new_session(alice.user.clone()).await;
+
+    let response = app.delete("/users/delete", Some(&alice)).await;
+    assert_eq!(response.status, StatusCode::ACCEPTED);
+    assert!(response.body["deletion_scheduled_at"].is_string());
+
+    // Scheduling the deletion signs the account out everywhere
+    for session in [&alice, &phone] {
+        let response = app.get("/users/me", Some(session)).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    }
+
+    // Signing in again during the grace period is how the owner gets to cancel
+    let alice = app.new_session(alice.user.clone()).await;
+    let response = app
+        .post("/users/delete/cancel", Some(&alice), json!({}))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body["deletion_scheduled_at"].is_null());
+
+    let response = app
+        .post("/users/delete/cancel", Some(&alice), json!({}))
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn scheduled_deletions_are_purged_once_the_grace_period_is_over() {
+    let Some(app) = TestApp::spawn_with(|config| {
+        config.account_deletion_grace_period = Duration::from_secs(60 * 60 * 24 * 7);
+    })
+    .await
+    else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let response = app
+        .post(
+            "/documents/create",
+            Some(&alice),
+            json!({ "title": "Notes", "content": "" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = app.delete("/users/delete", Some(&alice)).await;
+    assert_eq!(response.status, StatusCode::ACCEPTED);
+
+    // Nothing is deleted before the grace period is over
+    let database = &app.state.database;
+    assert_eq!(
+        account_deletion::delete_scheduled_accounts(database)
+            .await
+            .unwrap(),
+        0
+    );
+
+    sqlx::query("UPDATE users SET deletion_scheduled_at = NOW() - INTERVAL '1 minute'")
+        .execute(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(
+        account_deletion::delete_scheduled_accounts(database)
+            .await
+            .unwrap(),
+        1
+    );
+
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents WHERE user_uuid = $1")
+        .bind(alice.user.uuid)
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(remaining, 0);
+
+    app.finish().await;
+}
@@ -0,0 +1,228 @@
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+use super::harness::{TestApp, TestUser};
+use super::magic_links::link_path;
+use crate::utils::constants::{COOKIE_AUTH_SESSION, ROLE_ADMIN};
+
+async fn sign_in_admin(app: &TestApp, username: &str) -> TestUser {
+    let admin = app.sign_in(username).await;
+    let user = app
+        .state
+        .users
+        .set_user_role(admin.user.uuid, ROLE_ADMIN)
+        .await
+        .unwrap();
+
+    TestUser { user, ..admin }
+}
+
+// Signs in through an emailed link, so it goes the same way a real sign in does
+async fn sign_in_by_email(app: &TestApp, email: &str) -> Option<String> {
+    let requested = app
+        .post("/auth/magic-link", None, json!({ "email": email }))
+        .await;
+    assert_eq!(requested.status, StatusCode::ACCEPTED);
+    let link = link_path(&app.outbox.wait_for(email).await.body);
+
+    app.get(&link, None)
+        .await
+        .session_cookie()
+        .filter(|cookie| !cookie.is_empty())
+}
+
+#[tokio::test]
+async fn non_admins_cant_use_the_admin_api() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let bob = app.sign_in("bob").await;
+
+    let listed = app.get("/admin/users", Some(&alice)).await;
+    assert_eq!(listed.status, StatusCode::FORBIDDEN);
+    assert_eq!(listed.body["code"], "forbidden");
+
+    let signed_out = app
+        .delete(
+            &format!("/admin/users/{}/sessions", bob.user.uuid),
+            Some(&alice),
+        )
+        .await;
+    assert_eq!(signed_out.status, StatusCode::FORBIDDEN);
+    assert_eq!(
+        app.get("/users/me", Some(&bob)).await.status,
+        StatusCode::OK
+    );
+
+    let anonymous = app.get("/admin/users", None).await;
+    assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn disabled_users_cant_sign_in_until_enabled() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let admin = sign_in_admin(&app, "admin").await;
+    let bob = app.sign_in("bob").await;
+
+    let disabled = app
+        .post(
+            &format!("/admin/users/{}/disable", bob.user.uuid),
+            Some(&admin),
+            json!({}),
+        )
+        .await;
+    assert_eq!(disabled.status, StatusCode::OK);
+    assert!(disabled.body["disabled_at"].is_string());
+    assert_eq!(
+        app.get("/users/me", Some(&bob)).await.status,
+        StatusCode::UNAUTHORIZED
+    );
+    // Disabled accounts aren't sent a sign in link at all
+    let requested = app
+        .post("/auth/magic-link", None, json!({ "email": bob.user.email }))
+        .await;
+    assert_eq!(requested.status, StatusCode::ACCEPTED);
+    assert!(app.outbox.sent().is_empty());
+
+    let enabled = app
+        .post(
+            &format!("/admin/users/{}/enable", bob.user.uuid),
+            Some(&admin),
+            json!({}),
+        )
+        .await;
+    assert_eq!(enabled.status, StatusCode::OK);
+    assert!(enabled.body["disabled_at"].is_null());
+    assert!(sign_in_by_email(&app, &bob.user.email).await.is_some());
+
+    let disabled_self = app
+        .post(
+            &format!("/admin/users/{}/disable", admin.user.uuid),
+            Some(&admin),
+            json!({}),
+        )
+        .await;
+    assert_eq!(disabled_self.status, StatusCode::CONFLICT);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn admins_change_roles_but_not_their_own() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let admin = sign_in_admin(&app, "admin").await;
+    let bob = app.sign_in("bob").await;
+    let role_path = format!("/admin/users/{}/role", bob.user.uuid);
+
+    let invalid = app
+        .put(&role_path, Some(&admin), json!({ "role": "owner" }))
+        .await;
+    assert_eq!(invalid.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let promoted = app
+        .put(&role_path, Some(&admin), json!({ "role": "admin" }))
+        .await;
+    assert_eq!(promoted.status, StatusCode::OK);
+    assert_eq!(promoted.body["role"], "admin");
+    assert_eq!(
+        app.get("/admin/users", Some(&bob)).await.status,
+        StatusCode::OK
+    );
+
+    let demoted_self = app
+        .put(
+            &format!("/admin/users/{}/role", admin.user.uuid),
+            Some(&admin),
+            json!({ "role": "user" }),
+        )
+        .await;
+    assert_eq!(demoted_self.status, StatusCode::CONFLICT);
+
+    let demoted = app
+        .put(&role_path, Some(&admin), json!({ "role": "user" }))
+        .await;
+    assert_eq!(demoted.status, StatusCode::OK);
+    assert_eq!(
+        app.get("/admin/users", Some(&bob)).await.status,
+        StatusCode::FORBIDDEN
+    );
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn admins_sign_users_out_everywhere() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let admin = sign_in_admin(&app, "admin").await;
+    let bob = app.sign_in("bob").await;
+    let bob_elsewhere = app.new_session(bob.user.clone()).await;
+
+    let signed_out = app
+        .delete(
+            &format!("/admin/users/{}/sessions", bob.user.uuid),
+            Some(&admin),
+        )
+        .await;
+    assert_eq!(signed_out.status, StatusCode::OK);
+    assert_eq!(signed_out.body["sessions_deleted"], 2);
+    for session in [&bob, &bob_elsewhere] {
+        assert_eq!(
+            app.get("/users/me", Some(session)).await.status,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+    assert_eq!(
+        app.get("/users/me", Some(&admin)).await.status,
+        StatusCode::OK
+    );
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn listed_emails_are_made_admins_when_they_sign_in() {
+    let Some(app) = TestApp::spawn_with(|config| {
+        config.admin_emails = vec!["carol@example.com".to_string()];
+    })
+    .await
+    else {
+        return;
+    };
+    let carol = app.sign_in("carol").await;
+    let dave = app.sign_in("dave").await;
+    assert_eq!(
+        app.get("/admin/users", Some(&carol)).await.status,
+        StatusCode::FORBIDDEN
+    );
+
+    let session = sign_in_by_email(&app, &carol.user.email).await.unwrap();
+    let listed = app
+        .request_with_cookies(
+            Method::GET,
+            "/admin/users",
+            &[(COOKIE_AUTH_SESSION, session)],
+            None,
+        )
+        .await;
+    assert_eq!(listed.status, StatusCode::OK);
+
+    sign_in_by_email(&app, &dave.user.email).await.unwrap();
+    let dave = app
+        .state
+        .users
+        .fetch_user_by_uuid(dave.user.uuid)
+        .await
+        .unwrap();
+    assert_ne!(dave.role, ROLE_ADMIN);
+
+    app.finish().await;
+}
@@ -0,0 +1,167 @@
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::harness::{TestApp, TestUser};
+use super::magic_links::link_path;
+use crate::utils::constants::{COOKIE_AUTH_SESSION, ROLE_ADMIN};
+
+// The events recorded for the user, as the user sees them
+async fn audit_events(app: &TestApp, user: &TestUser) -> Vec<Value> {
+    let response = app.get("/users/me/audit", Some(user)).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    response.body.as_array().unwrap().clone()
+}
+
+fn find<'a>(events: &'a [Value], event_type: &str) -> Option<&'a Value> {
+    events
+        .iter()
+        .find(|event| event["event_type"] == event_type)
+}
+
+#[tokio::test]
+async fn logins_and_logouts_are_audited() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    let requested = app
+        .post(
+            "/auth/magic-link",
+            None,
+            json!({ "email": alice.user.email }),
+        )
+        .await;
+    assert_eq!(requested.status, StatusCode::ACCEPTED);
+    let link = link_path(&app.outbox.wait_for(&alice.user.email).await.body);
+    let session = app.get(&link, None).await.session_cookie().unwrap();
+
+    let events = audit_events(&app, &alice).await;
+    let login = find(&events, "auth.login").expect("the login is recorded");
+    assert_eq!(login["user_uuid"], alice.user.uuid.to_string());
+    assert_eq!(login["metadata"]["method"], "magic_link");
+    assert_eq!(login["ip_address"], "127.0.0.1");
+    assert!(find(&events, "session.created").is_some());
+    assert!(find(&events, "auth.logout").is_none());
+
+    let logged_out = app
+        .request_with_cookies(
+            Method::GET,
+            "/auth/logout",
+            &[(COOKIE_AUTH_SESSION, session)],
+            None,
+        )
+        .await;
+    assert_eq!(logged_out.status, StatusCode::SEE_OTHER);
+
+    let events = audit_events(&app, &alice).await;
+    assert!(find(&events, "auth.logout").is_some());
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn document_changes_are_audited() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    let created = app
+        .post(
+            "/documents/create",
+            Some(&alice),
+            json!({ "title": "Notes", "content": "# Notes" }),
+        )
+        .await;
+    assert_eq!(created.status, StatusCode::OK);
+    let uuid = created.body["uuid"].as_str().unwrap().to_string();
+    let updated = app
+        .put(
+            &format!("/documents/update/{uuid}"),
+            Some(&alice),
+            json!({ "title": "Renamed", "content": "# Renamed" }),
+        )
+        .await;
+    assert_eq!(updated.status, StatusCode::OK);
+    let deleted = app
+        .delete(&format!("/documents/delete/{uuid}"), Some(&alice))
+        .await;
+    assert_eq!(deleted.status, StatusCode::OK);
+
+    let events = audit_events(&app, &alice).await;
+    for event_type in ["document.created", "document.updated", "document.deleted"] {
+        let event = find(&events, event_type).expect("the change is recorded");
+        assert_eq!(event["user_uuid"], alice.user.uuid.to_string());
+        assert_eq!(event["target_type"], "document");
+        assert_eq!(event["target_uuid"], uuid);
+    }
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn account_deletions_are_audited_after_the_account_is_gone() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let admin = app.sign_in("admin").await;
+    app.state
+        .users
+        .set_user_role(admin.user.uuid, ROLE_ADMIN)
+        .await
+        .unwrap();
+    let alice = app.sign_in("alice").await;
+
+    let deleted = app.delete("/users/delete", Some(&alice)).await;
+    assert_eq!(deleted.status, StatusCode::OK);
+
+    let events = app
+        .get(
+            &format!("/admin/audit?user_uuid={}", alice.user.uuid),
+            Some(&admin),
+        )
+        .await;
+    assert_eq!(events.status, StatusCode::OK);
+    let events = events.body.as_array().unwrap();
+    let deletion = find(events, "user.deleted").expect("the deletion is recorded");
+    assert_eq!(deletion["target_type"], "user");
+    assert_eq!(deletion["target_uuid"], alice.user.uuid.to_string());
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn audit_events_cant_be_changed_or_removed() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let created = app
+        .post(
+            "/documents/create",
+            Some(&alice),
+            json!({ "title": "Notes", "content": "# Notes" }),
+        )
+        .await;
+    assert_eq!(created.status, StatusCode::OK);
+
+    for statement in [
+        "UPDATE audit_events SET event_type = 'document.deleted'",
+        "DELETE FROM audit_events",
+        "TRUNCATE audit_events",
+    ] {
+        let err = sqlx::query(statement)
+            .execute(app.pool())
+            .await
+            .expect_err("the audit log is append-only");
+        assert!(
+            err.to_string().contains("append-only"),
+            "{statement}: {err}"
+        );
+    }
+    assert_eq!(audit_events(&app, &alice).await.len(), 1);
+
+    app.finish().await;
+}
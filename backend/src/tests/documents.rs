@@ -0,0 +1,233 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+use super::harness::TestApp;
+use crate::utils::quota::QuotaConfig;
+
+#[tokio::test]
+async fn documents_can_be_created_read_updated_and_deleted() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    let created = app
+        .post(
+            "/documents/create",
+            Some(&alice),
+            json!({ "title": "Notes", "content": "# Notes" }),
+        )
+        .await;
+    assert_eq!(created.status, StatusCode::OK);
+    assert_eq!(created.body["title"], "Notes");
+    assert_eq!(created.body["user_uuid"], alice.user.uuid.to_string());
+    let uuid = created.body["uuid"].as_str().unwrap().to_string();
+
+    let fetched = app.get(&format!("/documents/{uuid}"), Some(&alice)).await;
+    assert_eq!(fetched.status, StatusCode::OK);
+    assert_eq!(fetched.body["content"], "# Notes");
+
+    let updated = app
+        .put(
+            &format!("/documents/update/{uuid}"),
+            Some(&alice),
+            json!({ "title": "Renamed", "content": "# Renamed" }),
+        )
+        .await;
+    assert_eq!(updated.status, StatusCode::OK);
+    assert_eq!(updated.body["title"], "Renamed");
+
+    let all = app.get("/documents/all", Some(&alice)).await;
+    assert_eq!(all.status, StatusCode::OK);
+    assert_eq!(all.body.as_array().unwrap().len(), 1);
+    assert_eq!(all.body[0]["content"], "# Renamed");
+
+    let deleted = app
+        .delete(&format!("/documents/delete/{uuid}"), Some(&alice))
+        .await;
+    assert_eq!(deleted.status, StatusCode::OK);
+
+    let gone = app.get(&format!("/documents/{uuid}"), Some(&alice)).await;
+    assert_eq!(gone.status, StatusCode::NOT_FOUND);
+    let all = app.get("/documents/all", Some(&alice)).await;
+    assert_eq!(all.body, json!([]));
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn documents_with_a_taken_uuid_are_conflicts() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let document = json!({
+        "uuid": "8f0b7c0e-4f1c-4a7e-9a6e-0d9a1f2b3c4d",
+        "title": "Notes",
+        "content": "# Notes",
+    });
+
+    let created = app
+        .post("/documents/create", Some(&alice), document.clone())
+        .await;
+    assert_eq!(created.status, StatusCode::OK);
+
+    let again = app.post("/documents/create", Some(&alice), document).await;
+    assert_eq!(again.status, StatusCode::CONFLICT);
+    assert_eq!(again.body["code"], "conflict");
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn invalid_documents_are_rejected() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    let response = app
+        .post("/documents/create", Some(&alice), json!({ "title": "" }))
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let fields: Vec<&str> = response.body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect();
+    // The content is required like it is for updates, an empty document sends ""
+    assert_eq!(fields, ["content", "title"]);
+    assert_eq!(response.body["errors"][0]["code"], "required");
+
+    let all = app.get("/documents/all", Some(&alice)).await;
+    assert_eq!(all.body, json!([]));
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn users_only_see_their_own_documents() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let bob = app.sign_in("bob").await;
+
+    let created = app
+        .post(
+            "/documents/create",
+            Some(&alice),
+            json!({ "title": "Private", "content": "Alice's" }),
+        )
+        .await;
+    let uuid = created.body["uuid"].as_str().unwrap().to_string();
+
+    // Someone else's document looks the same as one that doesn't exist
+    let fetched = app.get(&format!("/documents/{uuid}"), Some(&bob)).await;
+    assert_eq!(fetched.status, StatusCode::NOT_FOUND);
+
+    let all = app.get("/documents/all", Some(&bob)).await;
+    assert_eq!(all.body, json!([]));
+
+    let updated = app
+        .put(
+            &format!("/documents/update/{uuid}"),
+            Some(&bob),
+            json!({ "title": "Taken", "content": "Bob's now" }),
+        )
+        .await;
+    assert_eq!(updated.status, StatusCode::NOT_FOUND);
+
+    let deleted = app
+        .delete(&format!("/documents/delete/{uuid}"), Some(&bob))
+        .await;
+    assert_eq!(deleted.status, StatusCode::NOT_FOUND);
+
+    // Alice's document is untouched
+    let fetched = app.get(&format!("/documents/{uuid}"), Some(&alice)).await;
+    assert_eq!(fetched.status, StatusCode::OK);
+    assert_eq!(fetched.body["title"], "Private");
+    assert_eq!(fetched.body["content"], "Alice's");
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn quotas_limit_what_a_user_stores() {
+    let Some(app) = TestApp::spawn_with(|config| {
+        config.quotas = QuotaConfig::new(2, 10, 8).unwrap();
+    })
+    .await
+    else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    let response = app
+        .post(
+            "/documents/create",
+            Some(&alice),
+            json!({ "title": "Too big", "content": "123456789" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    let mut uuids = Vec::new();
+    for content in ["12345", "1234"] {
+        let response = app
+            .post(
+                "/documents/create",
+                Some(&alice),
+                json!({ "title": "Notes", "content": content }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+        uuids.push(response.body["uuid"].as_str().unwrap().to_string());
+    }
+
+    let response = app
+        .post(
+            "/documents/create",
+            Some(&alice),
+            json!({ "title": "One more", "content": "" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::INSUFFICIENT_STORAGE);
+    assert_eq!(response.body["code"], "quota_exceeded");
+
+    // 5 + 4 bytes are stored, growing the second document to 6 would make 11
+    let path = format!("/documents/update/{}", uuids[1]);
+    let response = app
+        .put(
+            &path,
+            Some(&alice),
+            json!({ "title": "Notes", "content": "123456" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::INSUFFICIENT_STORAGE);
+    let response = app
+        .put(
+            &path,
+            Some(&alice),
+            json!({ "title": "Notes", "content": "12345" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = app.get("/users/me/usage", Some(&alice)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.body,
+        json!({
+            "document_count": 2,
+            "storage_bytes": 10,
+            "largest_document_bytes": 5,
+            "max_documents": 2,
+            "max_storage_bytes": 10,
+            "max_document_bytes": 8
+        })
+    );
+
+    app.finish().await;
+}
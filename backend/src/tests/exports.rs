@@ -0,0 +1,218 @@
+use std::io::{Cursor, Read};
+use std::time::Duration;
+
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use chrono::Utc;
+use serde_json::{json, Value};
+use uuid::Uuid;
+use zip::ZipArchive;
+
+use super::harness::{TestApp, TestUser};
+use crate::db::organisation_queries;
+use crate::utils::constants::EXPORT_BUILD_DURATION;
+
+// Polls the export until the background build is done with it
+async fn wait_for_export(app: &TestApp, user: &TestUser, uuid: &str) -> Value {
+    for _ in 0..50 {
+        let response = app
+            .get(&format!("/users/me/export/{uuid}"), Some(user))
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+        if response.body["status"] != "pending" {
+            return response.body;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    panic!("export {uuid} was never built");
+}
+
+#[tokio::test]
+async fn exports_hold_the_profile_documents_and_sessions() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    for title in ["Notes", "Notes", "a/b"] {
+        let response = app
+            .post(
+                "/documents/create",
+                Some(&alice),
+                json!({ "title": title, "content": "# Hello" }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+    }
+
+    let response = app.post("/users/me/export", Some(&alice), json!({})).await;
+    assert_eq!(response.status, StatusCode::ACCEPTED);
+    let uuid = response.body["uuid"].as_str().unwrap().to_string();
+
+    let export = wait_for_export(&app, &alice, &uuid).await;
+    assert_eq!(export["status"], "ready");
+    let download_url = export["download_url"].as_str().unwrap();
+    assert!(download_url.ends_with(&format!("/users/me/export/{uuid}/download")));
+
+    let response = app
+        .get(&format!("/users/me/export/{uuid}/download"), Some(&alice))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[CONTENT_TYPE], "application/zip");
+
+    let mut zip = ZipArchive::new(Cursor::new(response.bytes)).unwrap();
+    let mut names: Vec<&str> = zip.file_names().collect();
+    names.sort_unstable();
+    assert_eq!(
+        names,
+        [
+            "documents.json",
+            "documents/Notes (2).md",
+            "documents/Notes.md",
+            "documents/a_b.md",
+            "preferences.json",
+            "profile.json",
+            "sessions.json",
+        ]
+    );
+
+    let mut content = String::new();
+    zip.by_name("documents/Notes.md")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "# Hello");
+
+    // Sessions are listed without the identifiers that would sign someone in
+    let sessions: Value = serde_json::from_reader(zip.by_name("sessions.json").unwrap()).unwrap();
+    assert_eq!(sessions.as_array().unwrap().len(), 1);
+    assert!(sessions[0].get("uuid").is_none());
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn exports_are_only_visible_to_their_owner() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let bob = app.sign_in("bob").await;
+
+    let response = app.post("/users/me/export", Some(&alice), json!({})).await;
+    assert_eq!(response.status, StatusCode::ACCEPTED);
+    let uuid = response.body["uuid"].as_str().unwrap().to_string();
+    wait_for_export(&app, &alice, &uuid).await;
+
+    let response = app
+        .get(&format!("/users/me/export/{uuid}"), Some(&bob))
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app
+        .get(&format!("/users/me/export/{uuid}/download"), Some(&bob))
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app
+        .get(&format!("/users/me/export/{uuid}/download"), None)
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn exports_hold_organisation_documents_the_user_wrote() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let organisation = organisation_queries::create_organisation(
+        app.pool(),
+        Uuid::new_v4(),
+        "Acme",
+        alice.user.uuid,
+    )
+    .await
+    .unwrap();
+    app.state
+        .documents
+        .create_document(
+            Uuid::new_v4(),
+            alice.user.uuid,
+            Some(organisation.uuid),
+            "Roadmap",
+            "# Plans",
+        )
+        .await
+        .unwrap();
+
+    let response = app.post("/users/me/export", Some(&alice), json!({})).await;
+    assert_eq!(response.status, StatusCode::ACCEPTED);
+    let uuid = response.body["uuid"].as_str().unwrap().to_string();
+    assert_eq!(
+        wait_for_export(&app, &alice, &uuid).await["status"],
+        "ready"
+    );
+
+    let response = app
+        .get(&format!("/users/me/export/{uuid}/download"), Some(&alice))
+        .await;
+    let mut zip = ZipArchive::new(Cursor::new(response.bytes)).unwrap();
+    let documents: Value = serde_json::from_reader(zip.by_name("documents.json").unwrap()).unwrap();
+    assert_eq!(documents[0]["file"], "documents/Roadmap.md");
+    assert_eq!(
+        documents[0]["organisation_uuid"],
+        organisation.uuid.to_string()
+    );
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn concurrent_export_requests_build_one_archive() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    let (first, second) = tokio::join!(
+        app.post("/users/me/export", Some(&alice), json!({})),
+        app.post("/users/me/export", Some(&alice), json!({})),
+    );
+    let mut statuses = [first.status, second.status];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::ACCEPTED, StatusCode::CONFLICT]);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn only_one_export_is_built_at_a_time() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    // A build that is still running
+    let pending = app
+        .state
+        .exports
+        .create_export(
+            Uuid::new_v4(),
+            alice.user.uuid,
+            Utc::now() + EXPORT_BUILD_DURATION,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let response = app.post("/users/me/export", Some(&alice), json!({})).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+
+    // Once it's done the user can ask again
+    app.state.exports.fail_export(pending.uuid).await.unwrap();
+    let response = app.post("/users/me/export", Some(&alice), json!({})).await;
+    assert_eq!(response.status, StatusCode::ACCEPTED);
+
+    app.finish().await;
+}
@@ -0,0 +1,303 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::body::{to_bytes, Body};
+use axum::extract::ConnectInfo;
+use axum::http::header::{CONTENT_TYPE, COOKIE, SET_COOKIE};
+use axum::http::{HeaderMap, Method, Request, StatusCode};
+use axum::Router;
+use serde_json::Value;
+use sqlx::PgPool;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::app;
+use crate::config::Config;
+use crate::db::test_support::PostgresSchema;
+use crate::mailer::{Email, Mailer};
+use crate::models::user::User;
+use crate::state::AppState;
+use crate::utils::constants::COOKIE_AUTH_SESSION;
+use crate::utils::rate_limit::RateLimiter;
+
+// The whole application, as main.rs serves it, on a freshly migrated PostgreSQL schema.
+// Requests go straight to the router, so no port is opened
+pub struct TestApp {
+    pub state: AppState,
+    pub outbox: Arc<Outbox>,
+    router: Router,
+    schema: PostgresSchema,
+}
+
+// The mailer of the test application. It keeps what would have been sent so tests can
+// follow the links in it, and can be told to fail like a mail server that's down
+#[derive(Default)]
+pub struct Outbox {
+    emails: Mutex<Vec<Email>>,
+    failing: AtomicBool,
+}
+
+// A user signed in without going through Google, along with the session cookie to send
+pub struct TestUser {
+    pub user: User,
+    pub session_uuid: Uuid,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    // Null when the response has no body or it isn't JSON
+    pub body: Value,
+    pub bytes: Vec<u8>,
+}
+
+impl TestApp {
+    // None when TEST_DATABASE_URL isn't set, the caller skips the test then
+    pub async fn spawn() -> Option<Self> {
+        Self::spawn_with(|_| {}).await
+    }
+
+    // Same as spawn, with the configuration changed first
+    pub async fn spawn_with(configure: impl FnOnce(&mut Config)) -> Option<Self> {
+        let schema = PostgresSchema::create().await?;
+        let mut state = AppState::for_tests(schema.database.clone());
+        let outbox = Arc::new(Outbox::default());
+        state.mailer = outbox.clone();
+        let mut config = (*state.config).clone();
+        configure(&mut config);
+        state.rate_limiter = RateLimiter::new(config.rate_limit, config.trust_forwarded_for);
+        state.config = Arc::new(config);
+        let router = app::router(state.clone());
+
+        Some(TestApp {
+            state,
+            outbox,
+            router,
+            schema,
+        })
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        self.schema
+            .database
+            .postgres()
+            .expect("the harness runs on PostgreSQL")
+    }
+
+    // Creates a user with a session that's valid for as long as a real sign in would be
+    pub async fn sign_in(&self, username: &str) -> TestUser {
+        let user = self
+            .state
+            .users
+            .create_user(Uuid::new_v4(), username, &format!("{username}@example.com"))
+            .await
+            .expect("test user is created");
+
+        self.new_session(user).await
+    }
+
+    // Another session for a user that's already signed in, like a second device
+    pub async fn new_session(&self, user: User) -> TestUser {
+        let user_session = self
+            .state
+            .users
+            .create_user_session(user.uuid, self.state.config.session.idle_timeout)
+            .await
+            .expect("test session is created");
+
+        TestUser {
+            user,
+            session_uuid: user_session.uuid,
+        }
+    }
+
+    // Moves the session's expiry into the past, as if the user had been away too long
+    pub async fn expire_session(&self, session_uuid: Uuid) {
+        sqlx::query(
+            "
+            UPDATE UserSessions
+            SET expires_at = NOW() - INTERVAL '1 second'
+            WHERE uuid = $1
+            ",
+        )
+        .bind(session_uuid)
+        .execute(self.pool())
+        .await
+        .expect("test session is expired");
+    }
+
+    pub async fn get(&self, path: &str, user: Option<&TestUser>) -> TestResponse {
+        self.request(Method::GET, path, user, None).await
+    }
+
+    pub async fn post(&self, path: &str, user: Option<&TestUser>, body: Value) -> TestResponse {
+        self.request(Method::POST, path, user, Some(body)).await
+    }
+
+    pub async fn put(&self, path: &str, user: Option<&TestUser>, body: Value) -> TestResponse {
+        self.request(Method::PUT, path, user, Some(body)).await
+    }
+
+    pub async fn delete(&self, path: &str, user: Option<&TestUser>) -> TestResponse {
+        self.request(Method::DELETE, path, user, None).await
+    }
+
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        user: Option<&TestUser>,
+        body: Option<Value>,
+    ) -> TestResponse {
+        let cookies = user
+            .map(|user| vec![(COOKIE_AUTH_SESSION, user.session_uuid.to_string())])
+            .unwrap_or_default();
+        self.request_with_cookies(method, path, &cookies, body)
+            .await
+    }
+
+    // Same as request, with extra headers such as X-Workspace-Id
+    pub async fn request_with_headers(
+        &self,
+        method: Method,
+        path: &str,
+        user: Option<&TestUser>,
+        headers: &[(&str, String)],
+        body: Option<Value>,
+    ) -> TestResponse {
+        let cookies = user
+            .map(|user| vec![(COOKIE_AUTH_SESSION, user.session_uuid.to_string())])
+            .unwrap_or_default();
+        self.send(method, path, &cookies, headers, body).await
+    }
+
+    // Same as request, sending whichever cookies are given instead of a session
+    pub async fn request_with_cookies(
+        &self,
+        method: Method,
+        path: &str,
+        cookies: &[(&str, String)],
+        body: Option<Value>,
+    ) -> TestResponse {
+        self.send(method, path, cookies, &[], body).await
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        cookies: &[(&str, String)],
+        headers: &[(&str, String)],
+        body: Option<Value>,
+    ) -> TestResponse {
+        let mut request = Request::builder().method(method).uri(path);
+        for (name, value) in headers {
+            request = request.header(*name, value);
+        }
+        if !cookies.is_empty() {
+            let cookies: Vec<String> = cookies
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            request = request.header(COOKIE, cookies.join("; "));
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header(CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let mut request = request.body(body).expect("test request is valid");
+        // What axum::serve would attach, so handlers see a client address
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+
+        let response = self
+            .router
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("the router always responds");
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("response body is readable");
+        let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+        TestResponse {
+            status,
+            headers,
+            body,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    // Drops the schema. Tests call this last, so a failed test leaves its data to look at
+    pub async fn finish(self) {
+        self.schema.drop().await;
+    }
+}
+
+impl Outbox {
+    // Every send fails from now on
+    pub fn fail_sends(&self) {
+        self.failing.store(true, Ordering::SeqCst);
+    }
+
+    pub fn sent(&self) -> Vec<Email> {
+        self.emails.lock().unwrap().clone()
+    }
+
+    // Takes the oldest email to the address out of the outbox. Some are sent in the
+    // background after the response, so this waits a little for them
+    pub async fn wait_for(&self, to: &str) -> Email {
+        for _ in 0..50 {
+            let email = {
+                let mut emails = self.emails.lock().unwrap();
+                let position = emails.iter().position(|email| email.to == to);
+                position.map(|position| emails.remove(position))
+            };
+            if let Some(email) = email {
+                return email;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        panic!("no email was sent to {to}");
+    }
+}
+
+#[async_trait::async_trait]
+impl Mailer for Outbox {
+    async fn send(&self, email: Email) -> Result<(), anyhow::Error> {
+        if self.failing.load(Ordering::SeqCst) {
+            anyhow::bail!("The mail server is down");
+        }
+
+        self.emails.lock().unwrap().push(email);
+        Ok(())
+    }
+}
+
+impl TestResponse {
+    // The session cookie the response hands back, if it sets one
+    pub fn session_cookie(&self) -> Option<String> {
+        self.cookie(COOKIE_AUTH_SESSION)
+    }
+
+    // The value of a cookie the response sets, removals included as an empty value
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find_map(|value| value.strip_prefix(&format!("{name}=")))
+            .map(|value| value.split(';').next().unwrap_or_default().to_string())
+    }
+}
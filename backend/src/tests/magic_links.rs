@@ -0,0 +1,87 @@
+use axum::http::header::LOCATION;
+use axum::http::StatusCode;
+use serde_json::json;
+
+use super::harness::TestApp;
+
+// The path of the sign in link in the email, without the base URL
+pub(super) fn link_path(body: &str) -> String {
+    let start = body
+        .find("/auth/magic-link/verify?token=")
+        .expect("the email has a sign in link");
+
+    body[start..].split_whitespace().next().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn magic_links_sign_in_once_until_they_expire() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let email = &alice.user.email;
+
+    let requested = app
+        .post("/auth/magic-link", None, json!({ "email": email }))
+        .await;
+    assert_eq!(requested.status, StatusCode::ACCEPTED);
+    let link = link_path(&app.outbox.wait_for(email).await.body);
+
+    let signed_in = app.get(&link, None).await;
+    assert_eq!(signed_in.status, StatusCode::SEE_OTHER);
+    assert_eq!(signed_in.headers[LOCATION], "http://localhost:5173");
+    assert!(signed_in
+        .session_cookie()
+        .is_some_and(|cookie| !cookie.is_empty()));
+
+    let reused = app.get(&link, None).await;
+    assert_eq!(reused.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(reused.session_cookie(), None);
+
+    // A link that's past its expiry is turned down even though it was never used
+    let requested = app
+        .post("/auth/magic-link", None, json!({ "email": email }))
+        .await;
+    assert_eq!(requested.status, StatusCode::ACCEPTED);
+    let expiring_link = link_path(&app.outbox.wait_for(email).await.body);
+    sqlx::query("UPDATE MagicLinks SET expires_at = NOW() - INTERVAL '1 minute'")
+        .execute(app.pool())
+        .await
+        .unwrap();
+    let expired = app.get(&expiring_link, None).await;
+    assert_eq!(expired.status, StatusCode::UNAUTHORIZED);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn magic_link_requests_dont_reveal_accounts() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    let unknown = app
+        .post(
+            "/auth/magic-link",
+            None,
+            json!({ "email": "nobody@example.com" }),
+        )
+        .await;
+    assert_eq!(unknown.status, StatusCode::ACCEPTED);
+
+    // A mail server that's down is only logged, the answer is the same
+    app.outbox.fail_sends();
+    let failing = app
+        .post(
+            "/auth/magic-link",
+            None,
+            json!({ "email": alice.user.email }),
+        )
+        .await;
+    assert_eq!(failing.status, StatusCode::ACCEPTED);
+    assert_eq!(failing.bytes, unknown.bytes);
+    assert!(app.outbox.sent().is_empty());
+
+    app.finish().await;
+}
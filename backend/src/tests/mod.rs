@@ -0,0 +1,15 @@
+// End to end tests of the HTTP API. They need TEST_DATABASE_URL, each test runs the full
+// router against a PostgreSQL schema of its own
+mod harness;
+
+mod accounts;
+mod admin;
+mod audit;
+mod documents;
+mod exports;
+mod magic_links;
+mod organisations;
+mod profiles;
+mod rate_limits;
+mod sessions;
+mod two_factor;
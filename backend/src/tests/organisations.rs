@@ -0,0 +1,313 @@
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::harness::{TestApp, TestResponse, TestUser};
+use crate::utils::constants::HEADER_WORKSPACE;
+
+// Creates an organisation owned by the user and returns its uuid
+async fn create_organisation(app: &TestApp, owner: &TestUser) -> String {
+    let created = app
+        .post("/organisations", Some(owner), json!({ "name": "Acme" }))
+        .await;
+    assert_eq!(created.status, StatusCode::OK);
+
+    created.body["uuid"].as_str().unwrap().to_string()
+}
+
+// Invites the email address and returns the token from the invite email
+async fn invite(app: &TestApp, admin: &TestUser, organisation: &str, email: &str) -> String {
+    let invite = app
+        .post(
+            &format!("/organisations/{organisation}/invites"),
+            Some(admin),
+            json!({ "email": email }),
+        )
+        .await;
+    assert_eq!(invite.status, StatusCode::OK);
+
+    invite.body["uuid"].as_str().unwrap().to_string()
+}
+
+// Signs the user up and has them accept an invite to the organisation
+async fn join(app: &TestApp, admin: &TestUser, organisation: &str, username: &str) -> TestUser {
+    let token = invite(app, admin, organisation, &format!("{username}@example.com")).await;
+    let member = app.sign_in(username).await;
+
+    let accepted = app
+        .post(
+            "/organisations/invites/accept",
+            Some(&member),
+            json!({ "token": token }),
+        )
+        .await;
+    assert_eq!(accepted.status, StatusCode::OK);
+
+    member
+}
+
+// A document request in the organisation's workspace
+async fn in_workspace(
+    app: &TestApp,
+    method: Method,
+    path: &str,
+    user: &TestUser,
+    organisation: &str,
+    body: Option<Value>,
+) -> TestResponse {
+    app.request_with_headers(
+        method,
+        path,
+        Some(user),
+        &[(HEADER_WORKSPACE, organisation.to_string())],
+        body,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn invites_are_accepted_once_signed_in() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let organisation = create_organisation(&app, &alice).await;
+    let token = invite(&app, &alice, &organisation, "bob@example.com").await;
+
+    // The invite page has the invitee sign in before it sends the token
+    let signed_out = app
+        .post(
+            "/organisations/invites/accept",
+            None,
+            json!({ "token": token }),
+        )
+        .await;
+    assert_eq!(signed_out.status, StatusCode::UNAUTHORIZED);
+
+    let bob = app.sign_in("bob").await;
+    let accepted = app
+        .post(
+            "/organisations/invites/accept",
+            Some(&bob),
+            json!({ "token": token }),
+        )
+        .await;
+    assert_eq!(accepted.status, StatusCode::OK);
+    assert_eq!(accepted.body["organisation_uuid"], organisation);
+
+    let details = app
+        .get(&format!("/organisations/{organisation}"), Some(&bob))
+        .await;
+    assert_eq!(details.status, StatusCode::OK);
+    assert_eq!(details.body["role"], "member");
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn invalid_organisation_requests_list_the_fields() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let organisation = create_organisation(&app, &alice).await;
+
+    let blank = app
+        .post("/organisations", Some(&alice), json!({ "name": "   " }))
+        .await;
+    assert_eq!(blank.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(blank.body["errors"][0]["field"], "name");
+    assert_eq!(blank.body["errors"][0]["code"], "blank");
+
+    let invite = app
+        .post(
+            &format!("/organisations/{organisation}/invites"),
+            Some(&alice),
+            json!({ "email": "bob", "role": "owner" }),
+        )
+        .await;
+    assert_eq!(invite.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let fields: Vec<&str> = invite.body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["email", "role"]);
+
+    let role = app
+        .put(
+            &format!("/organisations/{organisation}/members/{}", alice.user.uuid),
+            Some(&alice),
+            json!({}),
+        )
+        .await;
+    assert_eq!(role.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(role.body["errors"][0]["field"], "role");
+
+    let accept = app
+        .post(
+            "/organisations/invites/accept",
+            Some(&alice),
+            json!({ "token": "not-a-uuid" }),
+        )
+        .await;
+    assert_eq!(accept.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(accept.body["errors"][0]["field"], "token");
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn non_members_cant_use_the_workspace() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let organisation = create_organisation(&app, &alice).await;
+    let mallory = app.sign_in("mallory").await;
+
+    let documents = in_workspace(
+        &app,
+        Method::GET,
+        "/documents/all",
+        &mallory,
+        &organisation,
+        None,
+    )
+    .await;
+    assert_eq!(documents.status, StatusCode::FORBIDDEN);
+
+    let created = in_workspace(
+        &app,
+        Method::POST,
+        "/documents/create",
+        &mallory,
+        &organisation,
+        Some(json!({ "title": "Notes", "content": "" })),
+    )
+    .await;
+    assert_eq!(created.status, StatusCode::FORBIDDEN);
+
+    // Organisations other users belong to look like they don't exist
+    let details = app
+        .get(&format!("/organisations/{organisation}"), Some(&mallory))
+        .await;
+    assert_eq!(details.status, StatusCode::NOT_FOUND);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn members_only_delete_their_own_documents() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let organisation = create_organisation(&app, &alice).await;
+    let bob = join(&app, &alice, &organisation, "bob").await;
+    let carol = join(&app, &alice, &organisation, "carol").await;
+
+    let created = in_workspace(
+        &app,
+        Method::POST,
+        "/documents/create",
+        &carol,
+        &organisation,
+        Some(json!({ "title": "Plans", "content": "# Plans" })),
+    )
+    .await;
+    assert_eq!(created.status, StatusCode::OK);
+    let uuid = created.body["uuid"].as_str().unwrap().to_string();
+    let path = format!("/documents/delete/{uuid}");
+
+    let refused = in_workspace(&app, Method::DELETE, &path, &bob, &organisation, None).await;
+    assert_eq!(refused.status, StatusCode::NOT_FOUND);
+    let kept = in_workspace(
+        &app,
+        Method::GET,
+        &format!("/documents/{uuid}"),
+        &bob,
+        &organisation,
+        None,
+    )
+    .await;
+    assert_eq!(kept.status, StatusCode::OK);
+
+    // Owners and admins manage every document in the workspace
+    let deleted = in_workspace(&app, Method::DELETE, &path, &alice, &organisation, None).await;
+    assert_eq!(deleted.status, StatusCode::OK);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn invites_for_another_email_are_refused() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let organisation = create_organisation(&app, &alice).await;
+    let token = invite(&app, &alice, &organisation, "bob@example.com").await;
+    let mallory = app.sign_in("mallory").await;
+
+    let refused = app
+        .post(
+            "/organisations/invites/accept",
+            Some(&mallory),
+            json!({ "token": token }),
+        )
+        .await;
+    assert_eq!(refused.status, StatusCode::NOT_FOUND);
+    let details = app
+        .get(&format!("/organisations/{organisation}"), Some(&mallory))
+        .await;
+    assert_eq!(details.status, StatusCode::NOT_FOUND);
+
+    // The invite is still there for the person it was sent to
+    let invites = app
+        .get(
+            &format!("/organisations/{organisation}/invites"),
+            Some(&alice),
+        )
+        .await;
+    assert_eq!(invites.body.as_array().unwrap().len(), 1);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn the_last_owner_cant_be_removed() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let organisation = create_organisation(&app, &alice).await;
+    let bob = join(&app, &alice, &organisation, "bob").await;
+    let alice_path = format!("/organisations/{organisation}/members/{}", alice.user.uuid);
+
+    let left = app.delete(&alice_path, Some(&alice)).await;
+    assert_eq!(left.status, StatusCode::CONFLICT);
+    let demoted = app
+        .put(&alice_path, Some(&alice), json!({ "role": "member" }))
+        .await;
+    assert_eq!(demoted.status, StatusCode::CONFLICT);
+
+    // With a second owner the first one can leave
+    let promoted = app
+        .put(
+            &format!("/organisations/{organisation}/members/{}", bob.user.uuid),
+            Some(&alice),
+            json!({ "role": "owner" }),
+        )
+        .await;
+    assert_eq!(promoted.status, StatusCode::OK);
+    let left = app.delete(&alice_path, Some(&alice)).await;
+    assert_eq!(left.status, StatusCode::OK);
+
+    let details = app
+        .get(&format!("/organisations/{organisation}"), Some(&bob))
+        .await;
+    assert_eq!(details.body["members"].as_array().unwrap().len(), 1);
+
+    app.finish().await;
+}
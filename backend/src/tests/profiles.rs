@@ -0,0 +1,101 @@
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+use super::harness::TestApp;
+
+#[tokio::test]
+async fn profiles_change_only_the_fields_sent() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    let response = app
+        .request(
+            Method::PATCH,
+            "/users/me",
+            Some(&alice),
+            Some(json!({
+                "display_name": "Alice",
+                "avatar_url": "https://example.com/alice.png"
+            })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["display_name"], "Alice");
+
+    let response = app
+        .request(
+            Method::PATCH,
+            "/users/me",
+            Some(&alice),
+            Some(json!({ "avatar_url": null })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["display_name"], "Alice");
+    assert!(response.body["avatar_url"].is_null());
+    // The name from Google is kept either way
+    assert_eq!(response.body["username"], "alice");
+
+    let response = app
+        .request(
+            Method::PATCH,
+            "/users/me",
+            Some(&alice),
+            Some(json!({ "display_name": "", "avatar_url": "javascript:alert(1)" })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let fields: Vec<&str> = response.body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["avatar_url", "display_name"]);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn preferences_follow_the_user_across_sessions() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let laptop = app.sign_in("alice").await;
+    let phone = app.new_session(laptop.user.clone()).await;
+
+    let response = app.get("/users/me/preferences", Some(&phone)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["theme"], "system");
+    assert_eq!(response.body["editor_font_size"], 14);
+
+    let preferences = json!({
+        "theme": "dark",
+        "editor_font_size": 16,
+        "keybinding_mode": "vim",
+        "default_export_format": "html",
+        "preview_layout": "editor"
+    });
+    let response = app
+        .put("/users/me/preferences", Some(&laptop), preferences)
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = app.get("/users/me/preferences", Some(&phone)).await;
+    assert_eq!(response.body["theme"], "dark");
+    assert_eq!(response.body["keybinding_mode"], "vim");
+
+    let response = app
+        .put(
+            "/users/me/preferences",
+            Some(&laptop),
+            json!({ "theme": "blue", "editor_font_size": 100 }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.body["errors"].as_array().unwrap().len(), 5);
+
+    app.finish().await;
+}
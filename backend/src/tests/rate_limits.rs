@@ -0,0 +1,128 @@
+use axum::http::header::RETRY_AFTER;
+use axum::http::StatusCode;
+use serde_json::json;
+
+use super::harness::TestApp;
+
+#[tokio::test]
+async fn sign_in_attempts_are_limited_per_address() {
+    let Some(app) = TestApp::spawn_with(|config| config.rate_limit.auth_per_minute = 2).await
+    else {
+        return;
+    };
+
+    for _ in 0..2 {
+        let attempt = app.get("/auth/google/login", None).await;
+        assert_ne!(attempt.status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    let limited = app.get("/auth/google/login", None).await;
+    assert_eq!(limited.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(limited.body["code"], "rate_limited");
+    // Two a minute refills one every 30 seconds
+    assert_eq!(limited.headers[RETRY_AFTER], "30");
+
+    // The other routes have budgets of their own
+    let alice = app.sign_in("alice").await;
+    let documents = app.get("/documents/all", Some(&alice)).await;
+    assert_eq!(documents.status, StatusCode::OK);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn writes_are_limited_per_user() {
+    let Some(app) = TestApp::spawn_with(|config| config.rate_limit.write_per_minute = 1).await
+    else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let bob = app.sign_in("bob").await;
+    let document = json!({ "title": "Notes", "content": "# Notes" });
+
+    let created = app
+        .post("/documents/create", Some(&alice), document.clone())
+        .await;
+    assert_eq!(created.status, StatusCode::OK);
+
+    let limited = app
+        .post("/documents/create", Some(&alice), document.clone())
+        .await;
+    assert_eq!(limited.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(limited.headers[RETRY_AFTER], "60");
+
+    // Reading still works, and other users aren't affected even from the same address
+    let all = app.get("/documents/all", Some(&alice)).await;
+    assert_eq!(all.status, StatusCode::OK);
+    assert_eq!(all.body.as_array().unwrap().len(), 1);
+    let created = app.post("/documents/create", Some(&bob), document).await;
+    assert_eq!(created.status, StatusCode::OK);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn requests_without_a_session_are_limited_per_address() {
+    let Some(app) = TestApp::spawn_with(|config| {
+        config.rate_limit.unauthenticated_per_minute = 2;
+    })
+    .await
+    else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    for _ in 0..2 {
+        let attempt = app.get("/documents/all", None).await;
+        assert_eq!(attempt.status, StatusCode::UNAUTHORIZED);
+    }
+    let limited = app.get("/documents/all", None).await;
+    assert_eq!(limited.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(limited.headers[RETRY_AFTER], "30");
+
+    // A valid session from the same address never reaches the budget
+    let documents = app.get("/documents/all", Some(&alice)).await;
+    assert_eq!(documents.status, StatusCode::OK);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn signed_in_requests_leave_the_address_budget_alone() {
+    let Some(app) = TestApp::spawn_with(|config| {
+        config.rate_limit.unauthenticated_per_minute = 1;
+    })
+    .await
+    else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    for _ in 0..5 {
+        let documents = app.get("/documents/all", Some(&alice)).await;
+        assert_eq!(documents.status, StatusCode::OK);
+    }
+    let attempt = app.get("/documents/all", None).await;
+    assert_eq!(attempt.status, StatusCode::UNAUTHORIZED);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn sign_in_attempts_leave_the_api_alone() {
+    let Some(app) = TestApp::spawn_with(|config| config.rate_limit.auth_per_minute = 1).await
+    else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    for _ in 0..3 {
+        app.get("/auth/google/login", None).await;
+    }
+    let documents = app.get("/documents/all", Some(&alice)).await;
+    assert_eq!(documents.status, StatusCode::OK);
+    let attempt = app.get("/documents/all", None).await;
+    assert_eq!(attempt.status, StatusCode::UNAUTHORIZED);
+
+    app.finish().await;
+}
@@ -0,0 +1,125 @@
+use axum::http::StatusCode;
+
+use super::harness::{TestApp, TestUser};
+
+#[tokio::test]
+async fn requests_without_a_session_are_rejected() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    for path in ["/users/me", "/documents/all"] {
+        let response = app.get(path, None).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED, "{path}");
+        assert_eq!(response.body["code"], "unauthenticated", "{path}");
+    }
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn active_sessions_are_kept_alive() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    let response = app.get("/users/me", Some(&alice)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["uuid"], alice.user.uuid.to_string());
+    assert_eq!(response.body["email"], "alice@example.com");
+    // The session was just issued, so it's extended rather than rotated
+    assert_eq!(
+        response.session_cookie(),
+        Some(alice.session_uuid.to_string())
+    );
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn expired_sessions_are_rejected() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    app.expire_session(alice.session_uuid).await;
+
+    let response = app.get("/users/me", Some(&alice)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.session_cookie(), None);
+
+    let response = app.get("/documents/all", Some(&alice)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn unknown_sessions_are_rejected() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let forged = TestUser {
+        user: alice.user,
+        session_uuid: uuid::Uuid::new_v4(),
+    };
+
+    let response = app.get("/users/me", Some(&forged)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn logging_out_ends_only_that_session() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let laptop = app.sign_in("alice").await;
+    let phone = app.new_session(laptop.user.clone()).await;
+
+    let response = app.get("/auth/logout", Some(&laptop)).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert_eq!(response.session_cookie(), Some(String::new()));
+
+    let response = app.get("/users/me", Some(&laptop)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.get("/users/me", Some(&phone)).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn sessions_of_disabled_accounts_are_not_refreshed() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    sqlx::query("UPDATE Users SET disabled_at = NOW() WHERE uuid = $1")
+        .bind(alice.user.uuid)
+        .execute(app.pool())
+        .await
+        .unwrap();
+    let expires_at = |pool| async move {
+        sqlx::query_scalar::<_, chrono::DateTime<chrono::Utc>>(
+            "SELECT expires_at FROM UserSessions WHERE uuid = $1",
+        )
+        .bind(alice.session_uuid)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    };
+    let before = expires_at(app.pool()).await;
+
+    let response = app.get("/users/me", Some(&alice)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(response.body["code"], "account_disabled");
+    assert_eq!(response.session_cookie(), None);
+    assert_eq!(expires_at(app.pool()).await, before);
+
+    app.finish().await;
+}
@@ -0,0 +1,251 @@
+use axum::http::header::LOCATION;
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+use totp_rs::TOTP;
+
+use super::harness::{TestApp, TestResponse, TestUser};
+use crate::db::magic_link_queries;
+use crate::utils::constants::{
+    COOKIE_AUTH_2FA_CHALLENGE, LOGIN_CHALLENGE_MAX_ATTEMPTS, MAGIC_LINK_DURATION,
+};
+use crate::utils::totp;
+
+// Signs in through a magic link, which asks for the second factor once it's enabled
+async fn start_login(app: &TestApp, user_uuid: uuid::Uuid) -> String {
+    let magic_link =
+        magic_link_queries::create_magic_link(app.pool(), user_uuid, MAGIC_LINK_DURATION)
+            .await
+            .expect("magic link is created");
+    let token = app.state.magic_link_signer.sign(magic_link.uuid);
+
+    let response = app
+        .get(&format!("/auth/magic-link/verify?token={token}"), None)
+        .await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert!(response.headers[LOCATION]
+        .to_str()
+        .unwrap()
+        .ends_with("/2fa"));
+    assert_eq!(response.session_cookie(), None);
+
+    response
+        .cookie(COOKIE_AUTH_2FA_CHALLENGE)
+        .expect("a challenge is issued")
+}
+
+// Enrolls an authenticator and confirms it. The code used to confirm is already spent, so
+// the returned one is from the next time step
+async fn enable_two_factor(app: &TestApp, user: &TestUser) -> (TOTP, String, Vec<String>) {
+    let enrollment = app.post("/users/me/2fa/totp", Some(user), json!({})).await;
+    assert_eq!(enrollment.status, StatusCode::OK);
+    let authenticator = totp::build_totp(
+        enrollment.body["secret"].as_str().unwrap(),
+        &user.user.email,
+    )
+    .unwrap();
+
+    let confirmed = app
+        .post(
+            "/users/me/2fa/totp/verify",
+            Some(user),
+            json!({ "code": authenticator.generate_current().unwrap() }),
+        )
+        .await;
+    assert_eq!(confirmed.status, StatusCode::OK);
+    let recovery_codes = serde_json::from_value(confirmed.body["recovery_codes"].clone()).unwrap();
+    let next_code = authenticator.generate(chrono::Utc::now().timestamp() as u64 + 30);
+
+    (authenticator, next_code, recovery_codes)
+}
+
+async fn answer_challenge(app: &TestApp, challenge: &str, code: &str) -> TestResponse {
+    app.request_with_cookies(
+        Method::POST,
+        "/auth/2fa/verify",
+        &[(COOKIE_AUTH_2FA_CHALLENGE, challenge.to_string())],
+        Some(json!({ "code": code })),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn two_factor_protects_sign_in() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+
+    let enrollment = app
+        .post("/users/me/2fa/totp", Some(&alice), json!({}))
+        .await;
+    assert_eq!(enrollment.status, StatusCode::OK);
+    let authenticator = totp::build_totp(
+        enrollment.body["secret"].as_str().unwrap(),
+        &alice.user.email,
+    )
+    .unwrap();
+    let enrollment_code = authenticator.generate_current().unwrap();
+
+    let confirmed = app
+        .post(
+            "/users/me/2fa/totp/verify",
+            Some(&alice),
+            json!({ "code": enrollment_code }),
+        )
+        .await;
+    assert_eq!(confirmed.status, StatusCode::OK);
+    let recovery_codes: Vec<String> =
+        serde_json::from_value(confirmed.body["recovery_codes"].clone()).unwrap();
+    assert!(!recovery_codes.is_empty());
+
+    // The code used to enroll can't be replayed, the next one from the app works
+    let challenge = start_login(&app, alice.user.uuid).await;
+    let replayed = answer_challenge(&app, &challenge, &enrollment_code).await;
+    assert_eq!(replayed.status, StatusCode::UNAUTHORIZED);
+    let next_code = authenticator.generate(chrono::Utc::now().timestamp() as u64 + 30);
+    let signed_in = answer_challenge(&app, &challenge, &next_code).await;
+    assert_eq!(signed_in.status, StatusCode::NO_CONTENT);
+    assert!(signed_in
+        .session_cookie()
+        .is_some_and(|cookie| !cookie.is_empty()));
+
+    // A challenge is answered once
+    let reused = answer_challenge(&app, &challenge, &recovery_codes[0]).await;
+    assert_eq!(reused.status, StatusCode::UNAUTHORIZED);
+
+    // An expired challenge is turned down even with a good code
+    let challenge = start_login(&app, alice.user.uuid).await;
+    sqlx::query("UPDATE LoginChallenges SET expires_at = NOW() - INTERVAL '1 minute'")
+        .execute(app.pool())
+        .await
+        .unwrap();
+    let expired = answer_challenge(&app, &challenge, &recovery_codes[0]).await;
+    assert_eq!(expired.status, StatusCode::UNAUTHORIZED);
+
+    // Recovery codes work once each
+    let challenge = start_login(&app, alice.user.uuid).await;
+    let recovered = answer_challenge(&app, &challenge, &recovery_codes[0]).await;
+    assert_eq!(recovered.status, StatusCode::NO_CONTENT);
+    assert!(recovered.session_cookie().is_some());
+    let challenge = start_login(&app, alice.user.uuid).await;
+    let reused = answer_challenge(&app, &challenge, &recovery_codes[0]).await;
+    assert_eq!(reused.status, StatusCode::UNAUTHORIZED);
+
+    let status = app.get("/users/me/2fa", Some(&alice)).await;
+    assert_eq!(status.body["enabled"], true);
+    assert_eq!(
+        status.body["recovery_codes_remaining"],
+        recovery_codes.len() - 1
+    );
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn codes_are_used_once() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let (_, code, _) = enable_two_factor(&app, &alice).await;
+
+    let challenge = start_login(&app, alice.user.uuid).await;
+    let signed_in = answer_challenge(&app, &challenge, &code).await;
+    assert_eq!(signed_in.status, StatusCode::NO_CONTENT);
+
+    // The same code again, still within its time step
+    let challenge = start_login(&app, alice.user.uuid).await;
+    let replayed = answer_challenge(&app, &challenge, &code).await;
+    assert_eq!(replayed.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(replayed.session_cookie(), None);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn recovery_codes_are_used_once() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let (_, _, recovery_codes) = enable_two_factor(&app, &alice).await;
+
+    let challenge = start_login(&app, alice.user.uuid).await;
+    let recovered = answer_challenge(&app, &challenge, &recovery_codes[1]).await;
+    assert_eq!(recovered.status, StatusCode::NO_CONTENT);
+
+    let challenge = start_login(&app, alice.user.uuid).await;
+    let reused = answer_challenge(&app, &challenge, &recovery_codes[1]).await;
+    assert_eq!(reused.status, StatusCode::UNAUTHORIZED);
+
+    // The others still work
+    let another = answer_challenge(&app, &challenge, &recovery_codes[2]).await;
+    assert_eq!(another.status, StatusCode::NO_CONTENT);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn challenges_take_a_limited_number_of_attempts() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let (_, code, _) = enable_two_factor(&app, &alice).await;
+
+    let challenge = start_login(&app, alice.user.uuid).await;
+    for _ in 0..LOGIN_CHALLENGE_MAX_ATTEMPTS {
+        let wrong = answer_challenge(&app, &challenge, "000000").await;
+        assert_eq!(wrong.status, StatusCode::UNAUTHORIZED);
+    }
+
+    // Once they're used up even the right code is turned down
+    let locked = answer_challenge(&app, &challenge, &code).await;
+    assert_eq!(locked.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(locked.session_cookie(), None);
+    let gone = answer_challenge(&app, &challenge, &code).await;
+    assert_eq!(gone.status, StatusCode::UNAUTHORIZED);
+
+    // Signing in again starts over with a fresh challenge
+    let challenge = start_login(&app, alice.user.uuid).await;
+    let signed_in = answer_challenge(&app, &challenge, &code).await;
+    assert_eq!(signed_in.status, StatusCode::NO_CONTENT);
+
+    app.finish().await;
+}
+
+#[tokio::test]
+async fn disabling_two_factor_takes_a_valid_code() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.sign_in("alice").await;
+    let (_, code, _) = enable_two_factor(&app, &alice).await;
+
+    let refused = app
+        .request(
+            Method::DELETE,
+            "/users/me/2fa/totp",
+            Some(&alice),
+            Some(json!({ "code": "000000" })),
+        )
+        .await;
+    assert_eq!(refused.status, StatusCode::UNAUTHORIZED);
+    let status = app.get("/users/me/2fa", Some(&alice)).await;
+    assert_eq!(status.body["enabled"], true);
+
+    let disabled = app
+        .request(
+            Method::DELETE,
+            "/users/me/2fa/totp",
+            Some(&alice),
+            Some(json!({ "code": code })),
+        )
+        .await;
+    assert_eq!(disabled.status, StatusCode::OK);
+    let status = app.get("/users/me/2fa", Some(&alice)).await;
+    assert_eq!(status.body["enabled"], false);
+
+    app.finish().await;
+}
@@ -0,0 +1,33 @@
+use serde_json::json;
+
+use crate::db::connection::Database;
+use crate::models::audit_event::AuditEventType;
+use crate::utils::audit::{self, AuditContext, AuditRecord};
+
+// Deletes the accounts whose grace period is over. An account that fails to delete is
+// logged and tried again on the next run, it doesn't hold up the others. Returns how many
+// accounts were deleted
+pub async fn delete_scheduled_accounts(database: &Database) -> Result<u64, sqlx::Error> {
+    let users = database.users();
+    let due = users.fetch_users_due_for_deletion().await?;
+
+    let mut deleted = 0;
+    for user_uuid in due {
+        if let Err(err) = users.delete_user(user_uuid).await {
+            tracing::error!(error = %err, %user_uuid, "Failed to delete scheduled account");
+            continue;
+        }
+        deleted += 1;
+
+        audit::record(
+            database.postgres(),
+            &AuditContext::default(),
+            AuditRecord::new(AuditEventType::AccountDeleted, Some(user_uuid))
+                .target("user", user_uuid)
+                .metadata(json!({ "source": "scheduled" })),
+        )
+        .await;
+    }
+
+    Ok(deleted)
+}
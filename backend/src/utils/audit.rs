@@ -0,0 +1,123 @@
+use axum::async_trait;
+use axum::extract::{FromRef, FromRequestParts};
+use axum::http::header::USER_AGENT;
+use axum::http::request::Parts;
+use sqlx::PgPool;
+use std::convert::Infallible;
+use uuid::Uuid;
+
+use crate::config::SharedConfig;
+use crate::db::audit_queries;
+use crate::models::audit_event::AuditEventType;
+use crate::utils::helpers::client_ip;
+
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+// Where a request came from, attached to every audit event it records
+#[derive(Debug, Clone, Default)]
+pub struct AuditContext {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuditContext
+where
+    SharedConfig: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = SharedConfig::from_ref(state);
+        let ip_address =
+            client_ip(parts, config.trust_forwarded_for).map(|ip_address| ip_address.to_string());
+
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect());
+
+        Ok(AuditContext {
+            ip_address,
+            user_agent,
+        })
+    }
+}
+
+// A single event waiting to be written to the audit log
+pub struct AuditRecord {
+    event_type: AuditEventType,
+    user_uuid: Option<Uuid>,
+    target_type: Option<&'static str>,
+    target_uuid: Option<Uuid>,
+    organisation_uuid: Option<Uuid>,
+    metadata: serde_json::Value,
+}
+
+impl AuditRecord {
+    pub fn new(event_type: AuditEventType, user_uuid: Option<Uuid>) -> Self {
+        AuditRecord {
+            event_type,
+            user_uuid,
+            target_type: None,
+            target_uuid: None,
+            organisation_uuid: None,
+            metadata: serde_json::json!({}),
+        }
+    }
+
+    pub fn target(
+        mut self,
+        target_type: &'static str,
+        target_uuid: impl Into<Option<Uuid>>,
+    ) -> Self {
+        self.target_type = Some(target_type);
+        self.target_uuid = target_uuid.into();
+        self
+    }
+
+    pub fn organisation(mut self, organisation_uuid: Option<Uuid>) -> Self {
+        self.organisation_uuid = organisation_uuid;
+        self
+    }
+
+    pub fn metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+// Writes an event to the audit log. Failing to record an event is logged but never fails
+// the request that triggered it. Session identifiers are bearer secrets and must never be
+// put in the metadata. The audit log lives in PostgreSQL, without it nothing is recorded
+pub async fn record<'a>(
+    pool: impl Into<Option<&'a PgPool>>,
+    context: &AuditContext,
+    record: AuditRecord,
+) {
+    let Some(pool) = pool.into() else {
+        return;
+    };
+
+    if let Err(err) = audit_queries::insert_audit_event(
+        pool,
+        record.user_uuid,
+        record.event_type.as_str(),
+        record.target_type,
+        record.target_uuid,
+        record.organisation_uuid,
+        &record.metadata,
+        context.ip_address.as_deref(),
+        context.user_agent.as_deref(),
+    )
+    .await
+    {
+        tracing::error!(
+            error = %err,
+            event_type = record.event_type.as_str(),
+            "Failed to record audit event"
+        );
+    }
+}
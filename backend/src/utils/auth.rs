@@ -0,0 +1,73 @@
+use axum::async_trait;
+use axum::extract::{FromRef, FromRequestParts, Request, State};
+use axum::http::request::Parts;
+use axum::middleware::Next;
+use axum::response::Response;
+use axum_extra::extract::CookieJar;
+
+use crate::db::repository::SharedUserRepository;
+use crate::error::AppError;
+use crate::models::user::User;
+use crate::utils::helpers::check_user_session;
+use crate::utils::rate_limit::RateLimiter;
+
+// The signed in user. Behind require_user the session was already checked and this only
+// reads the result, elsewhere it checks the session itself
+#[derive(Debug, Clone)]
+pub struct AuthUser(pub User);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    SharedUserRepository: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(user) = parts.extensions.get::<User>() {
+            return Ok(AuthUser(user.clone()));
+        }
+
+        let cookies = CookieJar::from_headers(&parts.headers);
+        let users = SharedUserRepository::from_ref(state);
+        let user = check_user_session(cookies, users.as_ref()).await?;
+
+        // Later extractors and middleware reuse the user instead of querying again
+        parts.extensions.insert(user.clone());
+
+        Ok(AuthUser(user))
+    }
+}
+
+// Middleware for routers that only signed in users may reach. Layering it over a router
+// covers every route in it, including ones added later. Addresses that keep sending
+// requests without a valid session are rate limited, signed in users never are
+pub async fn require_user(
+    State(users): State<SharedUserRepository>,
+    State(limiter): State<RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (mut parts, body) = request.into_parts();
+    if let Err(err) = AuthUser::from_request_parts(&mut parts, &users).await {
+        return Err(limiter.reject_unauthenticated(&parts, err));
+    }
+
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
+
+// Middleware that only lets administrators through
+pub async fn require_admin(
+    AuthUser(user): AuthUser,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if !user.is_admin() {
+        return Err(AppError::Forbidden(
+            "Only administrators can do this".to_string(),
+        ));
+    }
+
+    Ok(next.run(request).await)
+}
@@ -1,7 +1,109 @@
 use std::time::Duration;
 
+use tracing::Level;
+
 pub const COOKIE_AUTH_SESSION: &str = "auth_session";
 pub const COOKIE_AUTH_CSRF_STATE: &str = "auth_csrf_state";
 pub const COOKIE_AUTH_CODE_VERIFIER: &str = "auth_code_verifier";
-pub const SESSION_DURATION: Duration = Duration::from_millis(1000 * 60 * 60 * 24); // 24 hours
-// pub const SESSION_DURATION: Duration = Duration::from_millis(1000 * 60 * 1); // 1 minute
+
+// Defaults for the session lifetimes, each can be overridden through the configuration
+pub const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60 * 24); // 24 hours
+pub const DEFAULT_SESSION_MAX_LIFETIME: Duration = Duration::from_secs(60 * 60 * 24 * 30); // 30 days
+pub const DEFAULT_SESSION_ROTATION_INTERVAL: Duration = Duration::from_secs(60 * 60); // 1 hour
+
+// How long a rotated session identifier keeps working so in-flight requests don't fail
+pub const SESSION_ROTATION_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+// Deleted accounts are removed right away unless a grace period is configured
+pub const DEFAULT_ACCOUNT_DELETION_GRACE_DAYS: u64 = 0;
+pub const MAX_ACCOUNT_DELETION_GRACE_DAYS: u64 = 365;
+
+// Two-factor authentication
+pub const COOKIE_AUTH_2FA_CHALLENGE: &str = "auth_2fa_challenge";
+pub const TOTP_ISSUER: &str = "MarkdownEdit";
+pub const LOGIN_CHALLENGE_DURATION: Duration = Duration::from_secs(60 * 5); // 5 minutes
+pub const LOGIN_CHALLENGE_MAX_ATTEMPTS: i32 = 5;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+pub const RECOVERY_CODE_LENGTH: usize = 10;
+
+// Magic link sign in
+pub const MAGIC_LINK_DURATION: Duration = Duration::from_secs(60 * 15); // 15 minutes
+
+// User roles
+pub const ROLE_USER: &str = "user";
+pub const ROLE_ADMIN: &str = "admin";
+
+// Organisation workspaces
+pub const HEADER_WORKSPACE: &str = "x-workspace-id";
+pub const ORG_ROLE_OWNER: &str = "owner";
+pub const ORG_ROLE_ADMIN: &str = "admin";
+pub const ORG_ROLE_MEMBER: &str = "member";
+pub const ORG_ROLES: &[&str] = &[ORG_ROLE_OWNER, ORG_ROLE_ADMIN, ORG_ROLE_MEMBER];
+// Ownership can only be handed out to existing members
+pub const ORG_INVITE_ROLES: &[&str] = &[ORG_ROLE_ADMIN, ORG_ROLE_MEMBER];
+// The name and invite email match their VARCHAR(255) columns
+pub const ORGANISATION_NAME_MAX_LENGTH: u64 = 255;
+pub const INVITE_EMAIL_MAX_LENGTH: u64 = 255;
+pub const ORGANISATION_INVITE_DURATION: Duration = Duration::from_secs(60 * 60 * 24 * 7); // 7 days
+
+// Account data exports, the download link works for this long once the archive is built
+pub const EXPORT_DOWNLOAD_DURATION: Duration = Duration::from_secs(60 * 60 * 24); // 24 hours
+
+// A build that never finishes, e.g. because the server crashed, stops blocking a new export
+// after this long
+pub const EXPORT_BUILD_DURATION: Duration = Duration::from_secs(60 * 60); // 1 hour
+pub const EXPORT_STATUS_PENDING: &str = "pending";
+pub const EXPORT_STATUS_READY: &str = "ready";
+pub const EXPORT_STATUS_FAILED: &str = "failed";
+
+// Profiles and editor preferences, the limits match the columns and their checks
+pub const DISPLAY_NAME_MAX_LENGTH: u64 = 64;
+pub const AVATAR_URL_MAX_LENGTH: u64 = 2048;
+pub const PREFERENCE_THEMES: &[&str] = &["system", "light", "dark"];
+pub const PREFERENCE_KEYBINDING_MODES: &[&str] = &["default", "vim", "emacs"];
+pub const PREFERENCE_EXPORT_FORMATS: &[&str] = &["markdown", "html", "pdf"];
+pub const PREFERENCE_PREVIEW_LAYOUTS: &[&str] = &["split", "editor", "preview"];
+pub const EDITOR_FONT_SIZE_MIN: i32 = 8;
+pub const EDITOR_FONT_SIZE_MAX: i32 = 32;
+
+// Documents, the title matches the VARCHAR(255) column
+pub const DOCUMENT_TITLE_MAX_LENGTH: u64 = 255;
+pub const DOCUMENT_CONTENT_MAX_BYTES: usize = 1024 * 1024; // 1 MiB
+
+// Default per user quotas, each can be overridden through the configuration
+pub const DEFAULT_QUOTA_MAX_DOCUMENTS: u64 = 1000;
+pub const DEFAULT_QUOTA_MAX_STORAGE_BYTES: u64 = 100 * 1024 * 1024; // 100 MiB
+pub const DEFAULT_QUOTA_MAX_DOCUMENT_BYTES: u64 = DOCUMENT_CONTENT_MAX_BYTES as u64;
+
+// Default rate limits in requests a minute, each can be overridden through the configuration
+pub const DEFAULT_RATE_LIMIT_AUTH_PER_MINUTE: u32 = 10;
+pub const DEFAULT_RATE_LIMIT_UNAUTHENTICATED_PER_MINUTE: u32 = 30;
+pub const DEFAULT_RATE_LIMIT_READ_PER_MINUTE: u32 = 300;
+pub const DEFAULT_RATE_LIMIT_WRITE_PER_MINUTE: u32 = 60;
+// Most buckets kept at once. Reaching it drops the ones that refilled, then the ones left
+// alone the longest
+pub const RATE_LIMIT_MAX_TRACKED_CLIENTS: usize = 10_000;
+
+// Audit log
+pub const AUDIT_EVENTS_DEFAULT_LIMIT: i64 = 100;
+pub const AUDIT_EVENTS_MAX_LIMIT: i64 = 1000;
+
+// Server defaults used when the CLI, environment and config file leave a setting out
+pub const DEFAULT_CONFIG_FILE: &str = "markdown-edit.toml";
+pub const DEFAULT_BIND_ADDRESS: &str = "0.0.0.0";
+pub const DEFAULT_PORT: u16 = 8080;
+pub const DEFAULT_DATABASE_MAX_CONNECTIONS: u32 = 5;
+pub const DEFAULT_TRACING_LEVEL: Level = Level::INFO;
+
+// Header carrying the identifier of each request, generated when the client sends none
+pub const HEADER_REQUEST_ID: &str = "x-request-id";
+
+// How long a readiness check may wait on the database before reporting it as down
+pub const READINESS_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// How often the gauges read from the database are refreshed, /metrics shows the last values
+pub const METRICS_GAUGE_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+// Graceful shutdown defaults
+pub const DEFAULT_SHUTDOWN_READINESS_DELAY: Duration = Duration::from_secs(5);
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
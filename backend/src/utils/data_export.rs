@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+use std::time::Instant;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::db::repository::{
+    SharedDocumentRepository, SharedExportRepository, SharedUserRepository,
+};
+use crate::models::document::Document;
+use crate::models::user::User;
+use crate::models::user_preferences::UserPreferences;
+use crate::models::user_session::UserSession;
+use crate::utils::constants::EXPORT_DOWNLOAD_DURATION;
+use crate::utils::monitoring;
+
+// Longest title kept in a file name, the rest of the title is in documents.json
+const MAX_FILE_NAME_LENGTH: usize = 80;
+
+// What documents.json lists for every document, next to the Markdown file holding it
+#[derive(Debug, Serialize)]
+struct DocumentMetadata {
+    uuid: Option<Uuid>,
+    // Set for documents the user wrote in an organisation's workspace
+    organisation_uuid: Option<Uuid>,
+    title: Option<String>,
+    file: String,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+// Sessions without their identifiers, which would sign anyone holding the archive in
+#[derive(Debug, Serialize)]
+struct SessionMetadata {
+    created_at: Option<DateTime<Utc>>,
+    issued_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+// Builds the archive for an export the user asked for and stores it for download. Runs in
+// the background, a failure or the server shutting down marks the export as failed so the
+// user can ask again
+pub async fn build_export(
+    users: SharedUserRepository,
+    documents: SharedDocumentRepository,
+    exports: SharedExportRepository,
+    user: User,
+    export_uuid: Uuid,
+    cancel: CancellationToken,
+) {
+    let started = Instant::now();
+
+    let build = async {
+        // Documents the user wrote in organisations are theirs too
+        let documents = documents.fetch_documents_by_author(user.uuid).await?;
+        let sessions = users.fetch_active_user_sessions(user.uuid).await?;
+        let preferences = users.fetch_user_preferences(user.uuid).await?;
+
+        // Compressing is CPU bound, so it stays off the async workers
+        let archive = tokio::task::spawn_blocking(move || {
+            write_archive(&user, &preferences, &documents, &sessions)
+        })
+        .await??;
+
+        exports
+            .complete_export(export_uuid, &archive, Utc::now() + EXPORT_DOWNLOAD_DURATION)
+            .await?;
+
+        Ok::<(), anyhow::Error>(())
+    };
+    let result = tokio::select! {
+        result = build => result,
+        _ = cancel.cancelled() => Err(anyhow::anyhow!("The server shut down before the export was built")),
+    };
+
+    monitoring::record_background_task("build_data_export", result.is_ok(), started.elapsed());
+
+    if let Err(err) = result {
+        tracing::error!(error = format!("{err:#}"), %export_uuid, "Failed to build data export");
+        if let Err(err) = exports.fail_export(export_uuid).await {
+            tracing::error!(error = %err, %export_uuid, "Failed to mark data export as failed");
+        }
+    }
+}
+
+// The archive holds profile.json, preferences.json, sessions.json, documents.json and every
+// document as a Markdown file in documents/
+fn write_archive(
+    user: &User,
+    preferences: &UserPreferences,
+    documents: &[Document],
+    sessions: &[UserSession],
+) -> Result<Vec<u8>, anyhow::Error> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("profile.json", options)?;
+    serde_json::to_writer_pretty(&mut zip, user)?;
+
+    zip.start_file("preferences.json", options)?;
+    serde_json::to_writer_pretty(&mut zip, preferences)?;
+
+    let sessions: Vec<SessionMetadata> = sessions
+        .iter()
+        .map(|session| SessionMetadata {
+            created_at: session.created_at,
+            issued_at: session.issued_at,
+            expires_at: session.expires_at,
+        })
+        .collect();
+    zip.start_file("sessions.json", options)?;
+    serde_json::to_writer_pretty(&mut zip, &sessions)?;
+
+    let mut file_names = HashSet::new();
+    let mut metadata = Vec::with_capacity(documents.len());
+    for document in documents {
+        let file = unique_file_name(document.title.as_deref(), &mut file_names);
+        zip.start_file(file.as_str(), options)?;
+        zip.write_all(document.content.as_deref().unwrap_or_default().as_bytes())?;
+
+        metadata.push(DocumentMetadata {
+            uuid: document.uuid,
+            organisation_uuid: document.organisation_uuid,
+            title: document.title.clone(),
+            file,
+            created_at: document.created_at,
+            updated_at: document.updated_at,
+        });
+    }
+    zip.start_file("documents.json", options)?;
+    serde_json::to_writer_pretty(&mut zip, &metadata)?;
+
+    let archive = zip.finish().context("Failed to finish the archive")?;
+
+    Ok(archive.into_inner())
+}
+
+// Names the file after the document's title, with a number added when two titles clash
+fn unique_file_name(title: Option<&str>, taken: &mut HashSet<String>) -> String {
+    let stem: String = title
+        .unwrap_or_default()
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' => c,
+            _ => '_',
+        })
+        .take(MAX_FILE_NAME_LENGTH)
+        .collect();
+    let stem = match stem.trim() {
+        "" => "Untitled",
+        stem => stem,
+    };
+
+    let mut file = format!("documents/{stem}.md");
+    let mut number = 2;
+    while !taken.insert(file.to_lowercase()) {
+        file = format!("documents/{stem} ({number}).md");
+        number += 1;
+    }
+
+    file
+}
@@ -1,40 +1,111 @@
-use axum::http::StatusCode;
-use axum::response::ErrorResponse;
+use std::net::{IpAddr, SocketAddr};
+
+use axum::extract::ConnectInfo;
+use axum::http::request::Parts;
 use axum_extra::extract::CookieJar;
 use uuid::Uuid;
 
-use crate::db::user_queries;
+use crate::db::repository::UserRepository;
+use crate::db::two_factor_queries;
+use crate::error::AppError;
 use crate::models::user::User;
+use crate::models::user_totp::UserTotp;
 use crate::utils::constants::COOKIE_AUTH_SESSION;
+use crate::utils::{telemetry, totp};
 
 // Helper function to check if the user is logged in and fetch the user from the database if they are
 pub async fn check_user_session(
     cookies: CookieJar,
-    pool: sqlx::PgPool,
-) -> Result<User, ErrorResponse> {
+    users: &dyn UserRepository,
+) -> Result<User, AppError> {
     let session_cookie = cookies.get(COOKIE_AUTH_SESSION);
 
     // If the session cookie is not present, return an error
     let Some(session_cookie) = session_cookie else {
-        return Err(ErrorResponse::from(StatusCode::UNAUTHORIZED));
+        return Err(AppError::Unauthenticated);
     };
 
     // Parse the UUID from the session cookie
-    let session_uuid = match Uuid::parse_str(session_cookie.value()) {
-        Ok(uuid) => uuid,
-        Err(_) => {
-            return Err(ErrorResponse::from(StatusCode::BAD_REQUEST));
-        }
-    };
+    let session_uuid = Uuid::parse_str(session_cookie.value())
+        .map_err(|_| AppError::BadRequest("The session cookie isn't a valid UUID".to_string()))?;
 
     // Fetch the user from the database
-    let user = match user_queries::fetch_user_by_session_uuid(&pool, session_uuid).await {
+    let user = match users.fetch_user_by_session_uuid(session_uuid).await {
         Ok(user) => user,
-        Err(err) => {
-            eprintln!("Database error: {}", err);
-            return Err(ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR));
-        }
+        Err(sqlx::Error::RowNotFound) => return Err(AppError::Unauthenticated),
+        Err(err) => return Err(err.into()),
     };
 
+    // Tie the rest of the request's logs to the user making it
+    telemetry::record_user(user.uuid);
+
+    // Disabled accounts are locked out even if they still hold a valid session
+    if user.is_disabled() {
+        return Err(AppError::AccountDisabled);
+    }
+
     Ok(user)
 }
+
+// Helper function to check a second factor, either a TOTP code or an unused recovery code.
+// Successful codes are consumed so they can't be used again
+pub async fn verify_second_factor(
+    pool: &sqlx::PgPool,
+    user: &User,
+    user_totp: &UserTotp,
+    code: &str,
+) -> Result<bool, sqlx::Error> {
+    if let Some(step) = totp::verify_code(user_totp, &user.email, code) {
+        return two_factor_queries::mark_totp_step_used(pool, user.uuid, step).await;
+    }
+
+    two_factor_queries::use_recovery_code(pool, user.uuid, &totp::hash_recovery_code(code)).await
+}
+
+// The address a request came from. Behind a trusted proxy that's the last address in
+// X-Forwarded-For, the one the proxy added itself, anything before it came from the client.
+// Otherwise the header is ignored and the socket address is used
+pub fn client_ip(parts: &Parts, trust_forwarded_for: bool) -> Option<IpAddr> {
+    let forwarded_for = trust_forwarded_for
+        .then(|| parts.headers.get("x-forwarded-for"))
+        .flatten()
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|value| value.trim().parse().ok());
+
+    forwarded_for.or_else(|| {
+        parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(forwarded_for: &str) -> Parts {
+        let mut request = axum::http::Request::builder()
+            .header("x-forwarded-for", forwarded_for)
+            .body(())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 4000))));
+        request.into_parts().0
+    }
+
+    #[test]
+    fn forwarded_for_is_only_read_behind_a_proxy() {
+        let parts = parts("203.0.113.9, 198.51.100.7");
+
+        // Without a proxy the client could have written anything in the header
+        assert_eq!(client_ip(&parts, false), Some(IpAddr::from([10, 0, 0, 1])));
+        // The client controls every entry but the last, which the proxy added
+        assert_eq!(
+            client_ip(&parts, true),
+            Some(IpAddr::from([198, 51, 100, 7]))
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use std::env;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Signs magic link tokens so a link can't be forged from the identifiers in the database
+#[derive(Clone)]
+pub struct MagicLinkSigner {
+    key: Vec<u8>,
+}
+
+impl MagicLinkSigner {
+    // Uses MAGIC_LINK_SECRET when set. Otherwise a random key is generated, which means
+    // links stop working when the server restarts
+    pub fn from_env() -> Self {
+        let key = match env::var("MAGIC_LINK_SECRET") {
+            Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+            _ => {
+                tracing::warn!("MAGIC_LINK_SECRET is not set, magic links won't survive a restart");
+                let mut key = vec![0u8; 32];
+                rand::thread_rng().fill_bytes(&mut key);
+                key
+            }
+        };
+
+        MagicLinkSigner { key }
+    }
+
+    pub fn sign(&self, link_uuid: Uuid) -> String {
+        let signature = self.mac(link_uuid).finalize().into_bytes();
+        format!("{}.{}", link_uuid, URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    // Returns the link identifier if the token carries a valid signature
+    pub fn verify(&self, token: &str) -> Option<Uuid> {
+        let (link_uuid, signature) = token.split_once('.')?;
+        let link_uuid = Uuid::parse_str(link_uuid).ok()?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+        self.mac(link_uuid).verify_slice(&signature).ok()?;
+
+        Some(link_uuid)
+    }
+
+    fn mac(&self, link_uuid: Uuid) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(link_uuid.as_bytes());
+        mac
+    }
+}
@@ -1,2 +1,17 @@
+pub mod account_deletion;
+pub mod audit;
+pub mod auth;
 pub mod constants;
+pub mod data_export;
 pub mod helpers;
+pub mod magic_link;
+pub mod monitoring;
+pub mod postgres;
+pub mod quota;
+pub mod rate_limit;
+pub mod session;
+pub mod shutdown;
+pub mod telemetry;
+pub mod totp;
+pub mod validation;
+pub mod workspace;
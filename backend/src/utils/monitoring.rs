@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::db::connection::Database;
+
+// Buckets for request latencies, from a cached read up to a slow export
+const HTTP_LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// Installs the global metrics recorder. The returned handle renders everything recorded
+// so far in the Prometheus text format
+pub fn install_recorder() -> Result<PrometheusHandle, anyhow::Error> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full("http_request_duration_seconds".to_string()),
+            &HTTP_LATENCY_BUCKETS,
+        )
+        .context("Invalid latency buckets")?
+        .install_recorder()
+        .context("Failed to install the metrics recorder")
+}
+
+// Middleware counting requests and their latency per route. Requests that didn't match a
+// route share one label so random paths can't blow up the number of series
+pub async fn track_http_metrics(request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched_path| matched_path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16().to_string();
+    metrics::counter!(
+        "http_requests_total",
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "status" => status
+    )
+    .increment(1);
+    metrics::histogram!(
+        "http_request_duration_seconds",
+        "method" => method,
+        "route" => route
+    )
+    .record(started.elapsed().as_secs_f64());
+
+    response
+}
+
+// Records how a run of a background task went
+pub fn record_background_task(task: &'static str, succeeded: bool, duration: Duration) {
+    let outcome = if succeeded { "success" } else { "failure" };
+    metrics::counter!("background_task_runs_total", "task" => task, "outcome" => outcome)
+        .increment(1);
+    metrics::histogram!("background_task_duration_seconds", "task" => task)
+        .record(duration.as_secs_f64());
+
+    if succeeded {
+        metrics::gauge!("background_task_last_success_timestamp_seconds", "task" => task)
+            .set(chrono::offset::Utc::now().timestamp() as f64);
+    }
+}
+
+// Gauges that are read from the database and the pool. A background task refreshes them so
+// scrapes, which may come from anyone when no token is set, never query the database
+pub async fn record_database_gauges(database: &Database) {
+    let (size, idle, max_connections) = database.pool_status();
+    metrics::gauge!("db_pool_connections", "state" => "idle").set(idle as f64);
+    metrics::gauge!("db_pool_connections", "state" => "in_use").set(size as f64 - idle as f64);
+    metrics::gauge!("db_pool_max_connections").set(max_connections as f64);
+
+    let users = database.users();
+    match users.count_active_sessions().await {
+        Ok(active_sessions) => metrics::gauge!("sessions_active").set(active_sessions as f64),
+        Err(err) => tracing::warn!(error = %err, "Failed to count active sessions"),
+    }
+
+    match users.fetch_storage_usage().await {
+        Ok(storage_usage) => {
+            metrics::gauge!("users_total").set(storage_usage.user_count as f64);
+            metrics::gauge!("documents_total").set(storage_usage.document_count as f64);
+            metrics::gauge!("documents_storage_bytes").set(storage_usage.storage_bytes as f64);
+        }
+        Err(err) => tracing::warn!(error = %err, "Failed to fetch storage usage"),
+    }
+}
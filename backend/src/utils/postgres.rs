@@ -0,0 +1,30 @@
+use axum::async_trait;
+use axum::extract::{FromRef, FromRequestParts};
+use axum::http::request::Parts;
+
+use crate::db::connection::Database;
+use crate::error::AppError;
+
+// The PostgreSQL pool, for handlers of the features that only exist on PostgreSQL. On the
+// SQLite backend their requests fail with 501 Not Implemented
+pub struct Postgres(pub sqlx::PgPool);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Postgres
+where
+    Database: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Database::from_ref(state) {
+            Database::Postgres(pool) => Ok(Postgres(pool)),
+            Database::Sqlite(_) => Err(not_on_sqlite()),
+        }
+    }
+}
+
+pub fn not_on_sqlite() -> AppError {
+    AppError::NotImplemented("This feature needs the PostgreSQL database backend".to_string())
+}
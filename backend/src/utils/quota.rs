@@ -0,0 +1,138 @@
+use anyhow::bail;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::error::AppError;
+use crate::models::user_usage::UserUsage;
+use crate::utils::constants::DOCUMENT_CONTENT_MAX_BYTES;
+
+// A write the user's quota has no room for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaError {
+    DocumentTooLarge { max_document_bytes: u64 },
+    TooManyDocuments { max_documents: u64 },
+    StorageFull { max_storage_bytes: u64 },
+}
+
+impl From<QuotaError> for AppError {
+    fn from(err: QuotaError) -> Self {
+        match err {
+            QuotaError::DocumentTooLarge { max_document_bytes } => AppError::PayloadTooLarge(
+                format!("Documents can't be larger than {max_document_bytes} bytes"),
+            ),
+            QuotaError::TooManyDocuments { max_documents } => AppError::QuotaExceeded(format!(
+                "You can't have more than {max_documents} documents"
+            )),
+            QuotaError::StorageFull { max_storage_bytes } => AppError::QuotaExceeded(format!(
+                "Your documents can't take up more than {max_storage_bytes} bytes"
+            )),
+        }
+    }
+}
+
+// How much each user may store
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct QuotaConfig {
+    pub max_documents: u64,
+    // Summed over all of the user's documents
+    pub max_storage_bytes: u64,
+    pub max_document_bytes: u64,
+}
+
+impl QuotaConfig {
+    pub fn new(
+        max_documents: u64,
+        max_storage_bytes: u64,
+        max_document_bytes: u64,
+    ) -> Result<Self, anyhow::Error> {
+        if max_documents == 0 || max_storage_bytes == 0 || max_document_bytes == 0 {
+            bail!("Quotas must be greater than zero");
+        }
+        // Larger documents are rejected by validation before the quota is checked
+        if max_document_bytes > DOCUMENT_CONTENT_MAX_BYTES as u64 {
+            bail!("The document size quota can't be more than {DOCUMENT_CONTENT_MAX_BYTES} bytes");
+        }
+
+        Ok(QuotaConfig {
+            max_documents,
+            max_storage_bytes,
+            max_document_bytes,
+        })
+    }
+
+    // Checks a write against what the user already stores. A new document adds one to the
+    // count, an update replaces replaced_bytes of content with content_bytes
+    pub fn check(
+        &self,
+        usage: &UserUsage,
+        new_document: bool,
+        replaced_bytes: u64,
+        content_bytes: u64,
+    ) -> Result<(), QuotaError> {
+        if content_bytes > self.max_document_bytes {
+            return Err(QuotaError::DocumentTooLarge {
+                max_document_bytes: self.max_document_bytes,
+            });
+        }
+
+        let document_count = usage.document_count as u64 + u64::from(new_document);
+        if new_document && document_count > self.max_documents {
+            return Err(QuotaError::TooManyDocuments {
+                max_documents: self.max_documents,
+            });
+        }
+
+        // Shrinking a document is always allowed, even by someone already over the quota
+        let storage_bytes = (usage.storage_bytes as u64).saturating_sub(replaced_bytes);
+        if content_bytes > replaced_bytes && storage_bytes + content_bytes > self.max_storage_bytes
+        {
+            return Err(QuotaError::StorageFull {
+                max_storage_bytes: self.max_storage_bytes,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(document_count: i64, storage_bytes: i64) -> UserUsage {
+        UserUsage {
+            document_count,
+            storage_bytes,
+            largest_document_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn writes_within_the_quota_pass() {
+        let quota = QuotaConfig::new(2, 100, 50).unwrap();
+
+        assert!(quota.check(&usage(1, 50), true, 0, 50).is_ok());
+        // Replacing a document only counts the difference
+        assert!(quota.check(&usage(2, 100), false, 40, 40).is_ok());
+        // Over the storage quota, but the write makes it smaller
+        assert!(quota.check(&usage(2, 150), false, 40, 10).is_ok());
+    }
+
+    #[test]
+    fn writes_past_the_quota_fail() {
+        let quota = QuotaConfig::new(2, 100, 50).unwrap();
+
+        assert!(matches!(
+            quota.check(&usage(0, 0), true, 0, 51),
+            Err(QuotaError::DocumentTooLarge { .. })
+        ));
+        assert!(matches!(
+            quota.check(&usage(2, 0), true, 0, 1),
+            Err(QuotaError::TooManyDocuments { .. })
+        ));
+        assert!(matches!(
+            quota.check(&usage(2, 90), false, 10, 21),
+            Err(QuotaError::StorageFull { .. })
+        ));
+    }
+}
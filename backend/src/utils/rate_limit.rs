@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::{Request, State};
+use axum::http::request::Parts;
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::Response;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::user::User;
+use crate::utils::constants::RATE_LIMIT_MAX_TRACKED_CLIENTS;
+use crate::utils::helpers::client_ip;
+
+// Requests are limited per class, each with a budget of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitClass {
+    // Signing in, where every attempt is worth slowing down
+    Auth,
+    // Requests to the API that came without a valid session
+    Unauthenticated,
+    Read,
+    Write,
+}
+
+impl RateLimitClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitClass::Auth => "auth",
+            RateLimitClass::Unauthenticated => "unauthenticated",
+            RateLimitClass::Read => "read",
+            RateLimitClass::Write => "write",
+        }
+    }
+}
+
+// Requests a minute each class allows a client, a client can use a whole minute's worth in
+// one burst. Zero turns the limit off
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitConfig {
+    pub auth_per_minute: u32,
+    pub unauthenticated_per_minute: u32,
+    pub read_per_minute: u32,
+    pub write_per_minute: u32,
+}
+
+impl RateLimitConfig {
+    fn per_minute(&self, class: RateLimitClass) -> u32 {
+        match class {
+            RateLimitClass::Auth => self.auth_per_minute,
+            RateLimitClass::Unauthenticated => self.unauthenticated_per_minute,
+            RateLimitClass::Read => self.read_per_minute,
+            RateLimitClass::Write => self.write_per_minute,
+        }
+    }
+}
+
+// Who a budget belongs to. Signed in users are counted on their own, so people sharing an
+// address don't use up each other's budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ClientKey {
+    User(Uuid),
+    Ip(IpAddr),
+}
+
+impl ClientKey {
+    // An IPv6 client usually has a whole /64 to pick addresses from, so it counts as one
+    fn for_address(ip: IpAddr) -> Self {
+        match ip.to_canonical() {
+            IpAddr::V6(ip) => {
+                let prefix = u128::from(ip) & !u128::from(u64::MAX);
+                ClientKey::Ip(IpAddr::V6(Ipv6Addr::from(prefix)))
+            }
+            ip => ClientKey::Ip(ip),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+// Token buckets kept in memory, so every server instance limits on its own
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    trust_forwarded_for: bool,
+    max_clients: usize,
+    buckets: Arc<Mutex<HashMap<(RateLimitClass, ClientKey), Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig, trust_forwarded_for: bool) -> Self {
+        RateLimiter {
+            config,
+            trust_forwarded_for,
+            max_clients: RATE_LIMIT_MAX_TRACKED_CLIENTS,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Takes a token from the client's bucket, or returns how long until one is available
+    fn acquire(&self, class: RateLimitClass, key: ClientKey, now: Instant) -> Result<(), Duration> {
+        let per_minute = self.config.per_minute(class);
+        if per_minute == 0 {
+            return Ok(());
+        }
+        let capacity = f64::from(per_minute);
+        let tokens_per_second = capacity / 60.0;
+
+        let mut buckets = self
+            .buckets
+            .lock()
+            .expect("rate limiter lock is never poisoned");
+
+        if buckets.len() >= self.max_clients && !buckets.contains_key(&(class, key)) {
+            self.evict(&mut buckets, now);
+        }
+
+        let bucket = buckets.entry((class, key)).or_insert(Bucket {
+            tokens: capacity,
+            refilled_at: now,
+        });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * tokens_per_second).min(capacity);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+
+        Err(Duration::from_secs_f64(
+            (1.0 - bucket.tokens) / tokens_per_second,
+        ))
+    }
+
+    // Makes room for new clients. Buckets that filled up again are the same as no bucket, so
+    // they go first. If that isn't enough the tenth left alone the longest goes too, so the
+    // next clients find room without another scan. With room for one there is nothing to keep
+    fn evict(&self, buckets: &mut HashMap<(RateLimitClass, ClientKey), Bucket>, now: Instant) {
+        let config = self.config;
+        buckets.retain(|(class, _), bucket| {
+            let capacity = f64::from(config.per_minute(*class));
+            let refilled = now.duration_since(bucket.refilled_at).as_secs_f64() * capacity / 60.0;
+            bucket.tokens + refilled < capacity
+        });
+
+        let keep = self.max_clients - self.max_clients.div_ceil(10);
+        if keep == 0 {
+            buckets.clear();
+        } else if buckets.len() > keep {
+            let mut refilled_at: Vec<Instant> =
+                buckets.values().map(|bucket| bucket.refilled_at).collect();
+            let oldest_kept = buckets.len() - keep;
+            let (_, cutoff, _) = refilled_at.select_nth_unstable(oldest_kept);
+            let cutoff = *cutoff;
+            buckets.retain(|_, bucket| bucket.refilled_at >= cutoff);
+        }
+    }
+
+    async fn limit(
+        &self,
+        class: RateLimitClass,
+        request: Request,
+        next: Next,
+    ) -> Result<Response, AppError> {
+        let (parts, body) = request.into_parts();
+        let key = match parts.extensions.get::<User>() {
+            Some(user) => Some(ClientKey::User(user.uuid)),
+            None => client_ip(&parts, self.trust_forwarded_for).map(ClientKey::for_address),
+        };
+
+        // Without an address there is nothing to key the budget on, which only happens when
+        // the server isn't started through main.rs
+        if let Some(key) = key {
+            if let Err(retry_after) = self.acquire(class, key, Instant::now()) {
+                metrics::counter!("rate_limited_requests_total", "class" => class.as_str())
+                    .increment(1);
+                return Err(AppError::RateLimited(retry_after));
+            }
+        }
+
+        Ok(next.run(Request::from_parts(parts, body)).await)
+    }
+
+    // Called once the session check turned a request away. Each failure draws from the
+    // address's budget, and once it's used up the address gets told to slow down instead
+    pub fn reject_unauthenticated(&self, parts: &Parts, err: AppError) -> AppError {
+        let Some(ip) = client_ip(parts, self.trust_forwarded_for) else {
+            return err;
+        };
+
+        let class = RateLimitClass::Unauthenticated;
+        match self.acquire(class, ClientKey::for_address(ip), Instant::now()) {
+            Ok(()) => err,
+            Err(retry_after) => {
+                metrics::counter!("rate_limited_requests_total", "class" => class.as_str())
+                    .increment(1);
+                AppError::RateLimited(retry_after)
+            }
+        }
+    }
+}
+
+// Middleware for the sign in routes, keyed by address since nobody is signed in yet
+pub async fn limit_auth(
+    State(limiter): State<RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    limiter.limit(RateLimitClass::Auth, request, next).await
+}
+
+// Middleware for everything else in the API. Requests that don't change anything draw from
+// the read budget, the rest from the write budget
+pub async fn limit_requests(
+    State(limiter): State<RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let class = match *request.method() {
+        Method::GET | Method::HEAD | Method::OPTIONS => RateLimitClass::Read,
+        _ => RateLimitClass::Write,
+    };
+
+    limiter.limit(class, request, next).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(per_minute: u32) -> RateLimiter {
+        RateLimiter::new(
+            RateLimitConfig {
+                auth_per_minute: per_minute,
+                unauthenticated_per_minute: per_minute,
+                read_per_minute: per_minute,
+                write_per_minute: 0,
+            },
+            false,
+        )
+    }
+
+    #[test]
+    fn buckets_refill_over_time() {
+        let limiter = limiter(2);
+        let key = ClientKey::Ip(IpAddr::from([127, 0, 0, 1]));
+        let now = Instant::now();
+
+        assert!(limiter.acquire(RateLimitClass::Auth, key, now).is_ok());
+        assert!(limiter.acquire(RateLimitClass::Auth, key, now).is_ok());
+        // Two a minute is one token every 30 seconds
+        let retry_after = limiter.acquire(RateLimitClass::Auth, key, now).unwrap_err();
+        assert_eq!(retry_after.as_secs(), 30);
+
+        assert!(limiter
+            .acquire(RateLimitClass::Auth, key, now + Duration::from_secs(30))
+            .is_ok());
+        assert!(limiter
+            .acquire(RateLimitClass::Auth, key, now + Duration::from_secs(30))
+            .is_err());
+    }
+
+    #[test]
+    fn budgets_are_separate_per_class_and_client() {
+        let limiter = limiter(1);
+        let alice = ClientKey::User(Uuid::new_v4());
+        let bob = ClientKey::User(Uuid::new_v4());
+        let now = Instant::now();
+
+        assert!(limiter.acquire(RateLimitClass::Auth, alice, now).is_ok());
+        assert!(limiter.acquire(RateLimitClass::Auth, alice, now).is_err());
+        assert!(limiter.acquire(RateLimitClass::Read, alice, now).is_ok());
+        assert!(limiter.acquire(RateLimitClass::Auth, bob, now).is_ok());
+
+        // Writes are unlimited
+        for _ in 0..100 {
+            assert!(limiter.acquire(RateLimitClass::Write, alice, now).is_ok());
+        }
+    }
+
+    #[test]
+    fn tracked_clients_are_capped() {
+        let mut limiter = limiter(2);
+        limiter.max_clients = 10;
+        let now = Instant::now();
+
+        // Every client leaves its bucket partly drained, so none of them refilled
+        for n in 0..100u32 {
+            let key = ClientKey::Ip(IpAddr::from(n.to_be_bytes()));
+            let at = now + Duration::from_millis(u64::from(n));
+            assert!(limiter.acquire(RateLimitClass::Auth, key, at).is_ok());
+            assert!(limiter.buckets.lock().unwrap().len() <= 10);
+        }
+
+        // The latest clients are still tracked, the earliest were dropped
+        let buckets = limiter.buckets.lock().unwrap();
+        let latest = ClientKey::Ip(IpAddr::from(99u32.to_be_bytes()));
+        let earliest = ClientKey::Ip(IpAddr::from(0u32.to_be_bytes()));
+        assert!(buckets.contains_key(&(RateLimitClass::Auth, latest)));
+        assert!(!buckets.contains_key(&(RateLimitClass::Auth, earliest)));
+    }
+
+    #[test]
+    fn a_single_tracked_client_is_replaced() {
+        let mut limiter = limiter(2);
+        limiter.max_clients = 1;
+        let now = Instant::now();
+
+        for n in 0..3u32 {
+            let key = ClientKey::Ip(IpAddr::from(n.to_be_bytes()));
+            assert!(limiter.acquire(RateLimitClass::Auth, key, now).is_ok());
+            assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn ipv6_clients_are_counted_per_prefix() {
+        let limiter = limiter(1);
+        let now = Instant::now();
+        let address = |ip: &str| ClientKey::for_address(ip.parse().unwrap());
+
+        assert!(limiter
+            .acquire(RateLimitClass::Auth, address("2001:db8::1"), now)
+            .is_ok());
+        assert!(limiter
+            .acquire(RateLimitClass::Auth, address("2001:db8::ffff:2"), now)
+            .is_err());
+        assert!(limiter
+            .acquire(RateLimitClass::Auth, address("2001:db8:0:1::1"), now)
+            .is_ok());
+        assert_eq!(address("::ffff:10.0.0.1"), address("10.0.0.1"));
+    }
+}
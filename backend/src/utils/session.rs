@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use anyhow::bail;
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::db::repository::{SharedUserRepository, UserRepository};
+use crate::models::user_session::UserSession;
+use crate::utils::constants::{COOKIE_AUTH_SESSION, SESSION_ROTATION_GRACE_PERIOD};
+
+// How long sessions live and how often their identifiers are rotated
+#[derive(Debug, Clone, Copy)]
+pub struct SessionConfig {
+    // A session expires after this long without any activity
+    pub idle_timeout: Duration,
+    // A session can never be extended past this long after the user logged in
+    pub max_lifetime: Duration,
+    // A session identifier is replaced with a new one after being in use this long
+    pub rotation_interval: Duration,
+}
+
+impl SessionConfig {
+    pub fn new(
+        idle_timeout: Duration,
+        max_lifetime: Duration,
+        rotation_interval: Duration,
+    ) -> Result<Self, anyhow::Error> {
+        if idle_timeout > max_lifetime {
+            bail!("The session idle timeout must not be greater than the session max lifetime");
+        }
+
+        Ok(SessionConfig {
+            idle_timeout,
+            max_lifetime,
+            rotation_interval,
+        })
+    }
+}
+
+// Builds the cookie holding the session identifier
+pub fn session_cookie(session_uuid: Uuid, max_age: Duration) -> Cookie<'static> {
+    Cookie::build((COOKIE_AUTH_SESSION, session_uuid.to_string()))
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .path("/")
+        .max_age(cookie::time::Duration::seconds(max_age.as_secs() as i64))
+        .into()
+}
+
+// Middleware that slides the expiry of the session used for a request and rotates its
+// identifier when due, sending the refreshed cookie back with the response
+pub async fn refresh_session(
+    State(users): State<SharedUserRepository>,
+    State(config): State<SessionConfig>,
+    cookies: CookieJar,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+
+    let Some(session_cookie_value) = cookies.get(COOKIE_AUTH_SESSION) else {
+        return response;
+    };
+
+    let Ok(session_uuid) = Uuid::parse_str(session_cookie_value.value()) else {
+        return response;
+    };
+
+    match slide_user_session(users.as_ref(), session_uuid, &config).await {
+        Ok(Some((user_session, max_age))) => {
+            let cookies = CookieJar::new().add(session_cookie(user_session.uuid, max_age));
+            (cookies, response).into_response()
+        }
+        Ok(None) => response,
+        Err(err) => {
+            tracing::error!(error = %err, "Failed to refresh user session");
+            response
+        }
+    }
+}
+
+// Extends an active session by the idle timeout, capped by its maximum lifetime, and swaps
+// it for a new identifier once the rotation interval has passed. Returns the session to
+// hand back to the client along with how long it remains valid
+async fn slide_user_session(
+    users: &dyn UserRepository,
+    session_uuid: Uuid,
+    config: &SessionConfig,
+) -> Result<Option<(UserSession, Duration)>, sqlx::Error> {
+    let now = Utc::now();
+
+    let Some(user_session) = users.fetch_active_user_session(session_uuid).await? else {
+        return Ok(None);
+    };
+
+    // Sessions that were already rotated only live out their grace period
+    if user_session.replaced_by.is_some() {
+        return Ok(None);
+    }
+
+    let created_at = user_session.created_at.unwrap_or(now);
+    let issued_at = user_session.issued_at.unwrap_or(created_at);
+
+    let expires_at = (now + config.idle_timeout).min(created_at + config.max_lifetime);
+    // to_std fails for negative durations, i.e. a session past its maximum lifetime
+    let max_age = (expires_at - now).to_std().unwrap_or_default();
+    if max_age.is_zero() {
+        return Ok(None);
+    }
+
+    let user_session = if (now - issued_at).to_std().unwrap_or_default() >= config.rotation_interval
+    {
+        let grace_expires_at = (now + SESSION_ROTATION_GRACE_PERIOD).min(expires_at);
+        users
+            .rotate_user_session(user_session.uuid, now, expires_at, grace_expires_at)
+            .await?
+    } else {
+        users
+            .extend_user_session(user_session.uuid, expires_at)
+            .await?
+    };
+
+    Ok(Some((user_session, max_age)))
+}
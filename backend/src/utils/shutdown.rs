@@ -0,0 +1,99 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+// Resolves once the process is asked to stop with Ctrl+C or SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!(error = %err, "Failed to listen for Ctrl+C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                tracing::error!(error = %err, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+// Waits for a shutdown signal, then fails readiness straight away and gives load balancers
+// the readiness delay to stop sending traffic before the token tells everything to stop.
+// A second signal skips the delay
+pub async fn watch_for_shutdown(
+    shutting_down: Arc<AtomicBool>,
+    shutdown: CancellationToken,
+    readiness_delay: Duration,
+) {
+    shutdown_signal().await;
+    tracing::info!(
+        readiness_delay_secs = readiness_delay.as_secs(),
+        "Shutdown requested"
+    );
+    shutting_down.store(true, Ordering::SeqCst);
+
+    tokio::select! {
+        _ = time::sleep(readiness_delay) => {},
+        _ = shutdown_signal() => {
+            tracing::info!("Shutdown requested again, skipping the readiness delay");
+        },
+    }
+
+    shutdown.cancel();
+}
+
+// Work a request starts that outlives it, e.g. building an account export. Once requests are
+// drained on shutdown, whatever is still running is cancelled and gets the time left to
+// record that it didn't finish
+#[derive(Clone, Default)]
+pub struct BackgroundTasks {
+    tracker: TaskTracker,
+    cancel: CancellationToken,
+}
+
+impl BackgroundTasks {
+    pub fn new() -> Self {
+        BackgroundTasks::default()
+    }
+
+    // The task gets a token that is cancelled on shutdown
+    pub fn spawn<F>(&self, task: impl FnOnce(CancellationToken) -> F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tracker.spawn(task(self.cancel.child_token()));
+    }
+
+    pub async fn shutdown(&self, timeout: Duration) {
+        self.tracker.close();
+        self.cancel.cancel();
+
+        if time::timeout(timeout, self.tracker.wait()).await.is_err() {
+            tracing::warn!(
+                tasks = self.tracker.len(),
+                "Timed out waiting for background tasks to stop"
+            );
+        }
+    }
+}
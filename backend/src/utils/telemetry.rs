@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::MatchedPath;
+use axum::http::{Request, Response};
+use tower_http::request_id::RequestId;
+use tracing::{field, Level, Span};
+use tracing_subscriber::filter::EnvFilter;
+use uuid::Uuid;
+
+use crate::config::LogFormat;
+
+// Installs the global subscriber. RUST_LOG directives, when set, refine the level given
+// on the command line, e.g. to quieten a noisy dependency
+pub fn init(level: Level, format: LogFormat) -> Result<(), anyhow::Error> {
+    let filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy();
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match format {
+        LogFormat::Pretty => subscriber.try_init(),
+        LogFormat::Json => subscriber
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .try_init(),
+    }
+    .map_err(|err| anyhow::anyhow!(err))
+}
+
+// Opens the span every log line of a request is recorded in. The status, latency and user
+// are filled in once they are known
+pub fn request_span(request: &Request<Body>) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str)
+        .unwrap_or_else(|| request.uri().path());
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|request_id| request_id.header_value().to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        route,
+        request_id,
+        status = field::Empty,
+        latency_ms = field::Empty,
+        user_uuid = field::Empty,
+    )
+}
+
+pub fn record_response(response: &Response<Body>, latency: Duration, span: &Span) {
+    span.record("status", response.status().as_u16());
+    span.record("latency_ms", latency.as_millis() as u64);
+
+    if response.status().is_server_error() {
+        tracing::error!("Request failed");
+    } else {
+        tracing::info!("Request finished");
+    }
+}
+
+// Attaches the authenticated user to the current request span
+pub fn record_user(user_uuid: Uuid) {
+    Span::current().record("user_uuid", field::display(user_uuid));
+}
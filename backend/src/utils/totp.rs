@@ -0,0 +1,79 @@
+use anyhow::Context;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use rand::distributions::{Alphanumeric, DistString};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::models::user_totp::UserTotp;
+use crate::utils::constants::{RECOVERY_CODE_COUNT, RECOVERY_CODE_LENGTH, TOTP_ISSUER};
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+// Accept codes from one step before and after the current one to allow for clock drift
+const TOTP_SKEW: u64 = 1;
+
+// Generates a new base32 encoded secret for TOTP enrollment
+pub fn generate_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+pub fn build_totp(secret: &str, account_name: &str) -> Result<TOTP, anyhow::Error> {
+    let secret_bytes = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .context("Invalid TOTP secret")?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        TOTP_SKEW as u8,
+        TOTP_STEP,
+        secret_bytes,
+        Some(TOTP_ISSUER.to_string()),
+        account_name.to_string(),
+    )
+    .context("Failed to build TOTP")
+}
+
+// Renders the otpauth URI as an SVG QR code that authenticator apps can scan
+pub fn qr_code_svg(otpauth_uri: &str) -> Result<String, anyhow::Error> {
+    let code = QrCode::new(otpauth_uri.as_bytes()).context("Failed to encode QR code")?;
+
+    Ok(code.render::<svg::Color>().min_dimensions(200, 200).build())
+}
+
+// Checks a code against the user's secret and returns the time step it belongs to. Codes
+// from a step at or before the last one used are rejected so a code can't be replayed.
+// Codes are compared in constant time so timing doesn't tell how many digits were right
+pub fn verify_code(user_totp: &UserTotp, account_name: &str, code: &str) -> Option<i64> {
+    let totp = build_totp(&user_totp.secret, account_name).ok()?;
+    let now = chrono::offset::Utc::now().naive_utc().timestamp() as u64;
+    let current_step = now / TOTP_STEP;
+
+    (current_step.saturating_sub(TOTP_SKEW)..=current_step + TOTP_SKEW)
+        .map(|step| step as i64)
+        .filter(|step| user_totp.last_used_step.is_none_or(|last| *step > last))
+        .find(|step| {
+            let expected = totp.generate(*step as u64 * TOTP_STEP);
+            expected.as_bytes().ct_eq(code.trim().as_bytes()).into()
+        })
+}
+
+// Generates a fresh set of single use recovery codes, only their hashes are ever stored
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            Alphanumeric
+                .sample_string(&mut rng, RECOVERY_CODE_LENGTH)
+                .to_lowercase()
+        })
+        .collect()
+}
+
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized = code.trim().replace('-', "").to_lowercase();
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
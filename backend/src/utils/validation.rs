@@ -0,0 +1,162 @@
+use std::borrow::Cow;
+
+use axum::async_trait;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{FromRequest, Request};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use uuid::Uuid;
+use validator::{Validate, ValidateUrl, ValidationError, ValidationErrors};
+
+use crate::error::{AppError, FieldError};
+use crate::utils::constants::{
+    DOCUMENT_CONTENT_MAX_BYTES, ORG_INVITE_ROLES, ORG_ROLES, PREFERENCE_EXPORT_FORMATS,
+    PREFERENCE_KEYBINDING_MODES, PREFERENCE_PREVIEW_LAYOUTS, PREFERENCE_THEMES,
+};
+
+// JSON body extractor that runs the type's validation rules before the handler sees it.
+// Malformed bodies and invalid fields both come back as problem responses instead of
+// reaching the handler
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(request, state)
+            .await
+            .map_err(json_rejection)?;
+
+        value.validate()?;
+
+        Ok(ValidatedJson(value))
+    }
+}
+
+fn json_rejection(rejection: JsonRejection) -> AppError {
+    match rejection.status() {
+        StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge(rejection.body_text()),
+        StatusCode::UNPROCESSABLE_ENTITY => AppError::Validation(rejection.body_text()),
+        _ => AppError::BadRequest(rejection.body_text()),
+    }
+}
+
+// Every failed rule becomes its own entry, sorted so responses are stable
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut field_errors: Vec<FieldError> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| FieldError {
+                    field: field.to_string(),
+                    code: error.code.to_string(),
+                    message: error
+                        .message
+                        .as_ref()
+                        .map(|message| message.to_string())
+                        .unwrap_or_else(|| format!("{field} isn't valid")),
+                })
+            })
+            .collect();
+        field_errors.sort_by(|a, b| a.field.cmp(&b.field).then(a.code.cmp(&b.code)));
+
+        AppError::InvalidFields(field_errors)
+    }
+}
+
+pub fn validate_uuid(value: &str) -> Result<(), ValidationError> {
+    match Uuid::parse_str(value) {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            Err(ValidationError::new("uuid").with_message(Cow::Borrowed("Has to be a valid UUID")))
+        }
+    }
+}
+
+// Limits the size in bytes rather than characters, since that's what ends up stored
+pub fn validate_document_content(content: &str) -> Result<(), ValidationError> {
+    if content.len() > DOCUMENT_CONTENT_MAX_BYTES {
+        return Err(
+            ValidationError::new("too_large").with_message(Cow::Owned(format!(
+                "Can't be larger than {} bytes",
+                DOCUMENT_CONTENT_MAX_BYTES
+            ))),
+        );
+    }
+
+    Ok(())
+}
+
+// Tells a field set to null apart from a missing one, for PATCH bodies where null clears the
+// field and leaving it out keeps it. Use with #[serde(default)]
+pub fn deserialize_nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// Avatars are shown as images by other users' browsers, so only web URLs are accepted
+pub fn validate_avatar_url(url: &str) -> Result<(), ValidationError> {
+    if !(url.starts_with("https://") || url.starts_with("http://")) || !url.validate_url() {
+        return Err(ValidationError::new("url")
+            .with_message(Cow::Borrowed("Has to be an http or https URL")));
+    }
+
+    Ok(())
+}
+
+pub fn validate_theme(value: &str) -> Result<(), ValidationError> {
+    validate_one_of(value, PREFERENCE_THEMES)
+}
+
+pub fn validate_keybinding_mode(value: &str) -> Result<(), ValidationError> {
+    validate_one_of(value, PREFERENCE_KEYBINDING_MODES)
+}
+
+pub fn validate_export_format(value: &str) -> Result<(), ValidationError> {
+    validate_one_of(value, PREFERENCE_EXPORT_FORMATS)
+}
+
+pub fn validate_preview_layout(value: &str) -> Result<(), ValidationError> {
+    validate_one_of(value, PREFERENCE_PREVIEW_LAYOUTS)
+}
+
+pub fn validate_organisation_role(value: &str) -> Result<(), ValidationError> {
+    validate_one_of(value, ORG_ROLES)
+}
+
+pub fn validate_invite_role(value: &str) -> Result<(), ValidationError> {
+    validate_one_of(value, ORG_INVITE_ROLES)
+}
+
+// Names are stored trimmed, so one made of whitespace would end up empty
+pub fn validate_not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(ValidationError::new("blank").with_message(Cow::Borrowed("Can't be blank")));
+    }
+
+    Ok(())
+}
+
+fn validate_one_of(value: &str, allowed: &[&str]) -> Result<(), ValidationError> {
+    if !allowed.contains(&value) {
+        return Err(
+            ValidationError::new("one_of").with_message(Cow::Owned(format!(
+                "Has to be one of {}",
+                allowed.join(", ")
+            ))),
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use axum::http::HeaderMap;
+use uuid::Uuid;
+
+use crate::db::organisation_queries;
+use crate::error::AppError;
+use crate::models::user::User;
+use crate::utils::constants::{HEADER_WORKSPACE, ORG_ROLE_ADMIN, ORG_ROLE_OWNER};
+use crate::utils::postgres::not_on_sqlite;
+
+// The set of documents a request works on
+#[derive(Debug, Clone)]
+pub enum Workspace {
+    // The user's own documents
+    Personal,
+    // An organisation's shared documents, along with the user's role in it
+    Organisation { uuid: Uuid, role: String },
+}
+
+impl Workspace {
+    pub fn organisation_uuid(&self) -> Option<Uuid> {
+        match self {
+            Workspace::Personal => None,
+            Workspace::Organisation { uuid, .. } => Some(*uuid),
+        }
+    }
+
+    // Whether the user may delete any document in the workspace, not only their own
+    pub fn can_manage_documents(&self) -> bool {
+        match self {
+            Workspace::Personal => true,
+            Workspace::Organisation { role, .. } => is_organisation_admin(role),
+        }
+    }
+}
+
+pub fn is_organisation_admin(role: &str) -> bool {
+    role == ORG_ROLE_OWNER || role == ORG_ROLE_ADMIN
+}
+
+// Helper function to pick the workspace from the X-Workspace-Id header. A missing header or
+// "personal" means the user's own documents, otherwise the user has to be a member of the
+// organisation. Organisations need PostgreSQL, so without a pool there is only the personal
+// workspace
+pub async fn resolve_workspace(
+    headers: &HeaderMap,
+    pool: Option<&sqlx::PgPool>,
+    user: &User,
+) -> Result<Workspace, AppError> {
+    let Some(header) = headers.get(HEADER_WORKSPACE) else {
+        return Ok(Workspace::Personal);
+    };
+
+    let value = header
+        .to_str()
+        .map_err(|_| AppError::BadRequest("The workspace header isn't valid".to_string()))?;
+    if value.is_empty() || value == "personal" {
+        return Ok(Workspace::Personal);
+    }
+
+    let Ok(organisation_uuid) = Uuid::parse_str(value) else {
+        return Err(AppError::BadRequest(
+            "The workspace header isn't a valid organisation id".to_string(),
+        ));
+    };
+
+    let Some(pool) = pool else {
+        return Err(not_on_sqlite());
+    };
+
+    let role = organisation_queries::fetch_member_role(pool, organisation_uuid, user.uuid).await?;
+
+    match role {
+        Some(role) => Ok(Workspace::Organisation {
+            uuid: organisation_uuid,
+            role,
+        }),
+        None => Err(AppError::Forbidden(
+            "You aren't a member of this organisation".to_string(),
+        )),
+    }
+}
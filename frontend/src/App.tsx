@@ -2,6 +2,8 @@ import { BrowserRouter as Router, Routes, Route, Navigate } from "react-router-d
 import Home from "./pages/Home";
 import Editor from "./pages/Editor";
 import ErrorPage from "./pages/ErrorPage";
+import TwoFactor from "./pages/TwoFactor";
+import AcceptInvite, { PENDING_INVITE_KEY } from "./pages/AcceptInvite";
 import React, { useState, useEffect } from "react";
 import { DefaultSpinner } from "./components/DefaultSpinner";
 import "./input.css";
@@ -26,7 +28,18 @@ const App: React.FC = () => {
     return (
         <Router>
             <Routes>
-                <Route path="/" element={<Home userData={userData} />} />
+                <Route
+                    path="/"
+                    element={
+                        userData && localStorage.getItem(PENDING_INVITE_KEY) ? (
+                            <Navigate to="/invites/accept" />
+                        ) : (
+                            <Home userData={userData} />
+                        )
+                    }
+                />
+                <Route path="/2fa" element={userData ? <Navigate to="/" /> : <TwoFactor />} />
+                <Route path="/invites/accept" element={<AcceptInvite userData={userData} />} />
                 <Route path="/editor" element={userData ? <Editor /> : <Navigate to="/" />} />
                 <Route path="*" element={<ErrorPage />} />
             </Routes>
@@ -1,11 +1,35 @@
 import GoogleButton from "react-google-button";
+import { Button, Input, Typography } from "@material-tailwind/react";
+import React, { useState } from "react";
+import { requestMagicLink } from "../utils";
+
 export default function Login() {
     const serverUrl = import.meta.env.MODE === "production" ? "/auth/google/login" : "http://localhost:8080/auth/google/login"
+    const [email, setEmail] = useState("");
+    const [sent, setSent] = useState(false);
+
+    const handleMagicLink = async (e: React.FormEvent) => {
+        e.preventDefault();
+        setSent(await requestMagicLink(email));
+    };
+
     return (
-        <div className="flex justify-center items-center">
+        <div className="flex flex-col justify-center items-center gap-4">
             <a href={serverUrl}>
                 <GoogleButton />
             </a>
+            {sent ? (
+                <Typography placeholder="Check your email" variant="small">
+                    Check your email for a sign in link.
+                </Typography>
+            ) : (
+                <form onSubmit={handleMagicLink} className="flex flex-col gap-2 w-72">
+                    <Input crossOrigin="true" placeholder="Email" label="Email" type="email" value={email} onChange={(e) => setEmail(e.target.value)} />
+                    <Button placeholder="Email me a sign in link" type="submit" variant="outlined" size="sm">
+                        Email me a sign in link
+                    </Button>
+                </form>
+            )}
         </div>
     );
 }
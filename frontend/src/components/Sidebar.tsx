@@ -18,7 +18,7 @@ import {
 } from "@material-tailwind/react";
 import { DocumentIcon, DocumentPlusIcon, TrashIcon, PencilSquareIcon } from "@heroicons/react/24/solid";
 import { MagnifyingGlassIcon, Bars3Icon, XMarkIcon } from "@heroicons/react/24/outline";
-import { getDocuments, getDocument, deleteDocument, Document, createDocument, updateDocument } from "../utils";
+import { getDocuments, getDocument, deleteDocument, Document, createDocument, updateDocument, getUsage, Usage } from "../utils";
 import { useStore } from "../store";
 
 export default function Sidebar() {
@@ -30,6 +30,7 @@ export default function Sidebar() {
     const [action, setAction] = React.useState("");
     const [search, setSearch] = React.useState("");
     const [filteredDocs, setFilteredDocs] = React.useState<Document[]>([]);
+    const [usage, setUsage] = React.useState<Usage | null>(null);
 
     React.useEffect(() => {
         const fetchDocuments = async () => {
@@ -56,6 +57,7 @@ export default function Sidebar() {
             setDocuments(documents);
         };
         fetchDocuments();
+        getUsage().then(setUsage);
         setIsDrawerOpen(true);
     };
 
@@ -172,6 +174,20 @@ export default function Sidebar() {
                     <IconButton onClick={(e) => handleOpenDialog(e, "create", {} as Document)} variant="text" color="blue" placeholder="create">
                         <DocumentPlusIcon className="h-5 w-5" />
                     </IconButton>
+                    {usage && (
+                        <div className="p-2">
+                            <div className="h-2 w-full rounded bg-blue-gray-50">
+                                <div
+                                    className="h-2 rounded bg-blue-500"
+                                    style={{ width: `${Math.min(100, (usage.storage_bytes / usage.max_storage_bytes) * 100)}%` }}
+                                />
+                            </div>
+                            <Typography placeholder="usage" variant="small" className="mt-1 text-center">
+                                {usage.document_count} of {usage.max_documents} documents, {(usage.storage_bytes / 1024 / 1024).toFixed(1)} of{" "}
+                                {(usage.max_storage_bytes / 1024 / 1024).toFixed(0)} MiB used
+                            </Typography>
+                        </div>
+                    )}
                 </Card>
             </Drawer>
             <Dialog placeholder={"dialog"} open={open} handler={() => setOpen(!open)}>
@@ -0,0 +1,68 @@
+import { Button, Typography } from "@material-tailwind/react";
+import React, { useEffect, useState } from "react";
+import { Link, useSearchParams } from "react-router-dom";
+import Login from "../components/Login";
+import { acceptInvite, User } from "../utils";
+
+// The invite token is kept while the invitee signs in, App sends them back here afterwards
+export const PENDING_INVITE_KEY = "pending_invite";
+
+export default function AcceptInvite({ userData }: { userData: User | null }) {
+    const [searchParams] = useSearchParams();
+    const token = searchParams.get("token") || localStorage.getItem(PENDING_INVITE_KEY) || "";
+    const [status, setStatus] = useState<"pending" | "accepted" | "failed">("pending");
+
+    useEffect(() => {
+        if (!token) {
+            setStatus("failed");
+            return;
+        }
+        if (!userData) {
+            localStorage.setItem(PENDING_INVITE_KEY, token);
+            return;
+        }
+
+        localStorage.removeItem(PENDING_INVITE_KEY);
+        acceptInvite(token).then((accepted) => setStatus(accepted ? "accepted" : "failed"));
+    }, [token, userData]);
+
+    let message: React.ReactNode;
+    if (!userData && token) {
+        message = (
+            <>
+                <Typography placeholder="Sign in to accept" variant="lead" className="p-4">
+                    Sign in with the email address the invite was sent to and it will be accepted.
+                </Typography>
+                <Login />
+            </>
+        );
+    } else if (status === "accepted") {
+        message = (
+            <Typography placeholder="Invite accepted" variant="lead" className="p-4">
+                You've joined the organisation.
+            </Typography>
+        );
+    } else if (status === "failed") {
+        message = (
+            <Typography placeholder="Invite failed" variant="lead" color="red" className="p-4">
+                The invite doesn't exist, expired or is for another email address.
+            </Typography>
+        );
+    }
+
+    return (
+        <div className="flex flex-col justify-center items-center h-screen text-center p-4" id="home">
+            <Typography placeholder="Organisation invite" variant="h3" className="p-4">
+                Organisation invite
+            </Typography>
+            {message}
+            {userData && status !== "pending" && (
+                <Link to="/">
+                    <Button placeholder="Continue" color="blue" ripple>
+                        Continue
+                    </Button>
+                </Link>
+            )}
+        </div>
+    );
+}
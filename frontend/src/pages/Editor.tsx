@@ -7,7 +7,7 @@ import { languages } from "@codemirror/language-data";
 import ReactMarkdown from "react-markdown";
 import remarkGfm from "remark-gfm";
 import Tools from "../components/Tools";
-import { updateDocument } from "../utils";
+import { getPreferences, updateDocument, updatePreferences, UserPreferences } from "../utils";
 import "../input.css";
 import { Alert } from "@material-tailwind/react";
 import { useStore } from "../store";
@@ -20,8 +20,26 @@ export default function Editor() {
     const [hasDocument, setHasDocument] = React.useState(false);
     const [isTyping, setIsTyping] = React.useState(false);
 
+    const [preferences, setPreferences] = React.useState<UserPreferences | null>(null);
+
     const selectedDocRef = React.useRef(selectedDoc);
 
+    // the theme saved with the account wins over the one remembered by this browser
+    React.useEffect(() => {
+        getPreferences().then((saved) => {
+            if (!saved) {
+                return;
+            }
+            setPreferences(saved);
+            if (saved.theme !== "system") {
+                const isDarkMode = saved.theme === "dark";
+                setDarkMode(isDarkMode);
+                document.body.classList.toggle("dark-mode", isDarkMode);
+                localStorage.setItem("darkMode", JSON.stringify(isDarkMode));
+            }
+        });
+    }, []);
+
     // update selectedDocRef whenever selectedDoc changes
     React.useEffect(() => {
         selectedDocRef.current = selectedDoc;
@@ -76,6 +94,10 @@ export default function Editor() {
         setDarkMode(newDarkMode);
         document.body.classList.toggle("dark-mode", newDarkMode);
         localStorage.setItem("darkMode", JSON.stringify(newDarkMode));
+        if (preferences) {
+            const theme = newDarkMode ? "dark" : "light";
+            updatePreferences({ ...preferences, theme }).then((saved) => saved && setPreferences(saved));
+        }
     };
 
     const emptyDocuments = () => {
@@ -0,0 +1,47 @@
+import { Button, Input, Typography } from "@material-tailwind/react";
+import React, { useState } from "react";
+import { verifyTwoFactorLogin } from "../utils";
+
+export default function TwoFactor() {
+    const [code, setCode] = useState("");
+    const [error, setError] = useState("");
+
+    const handleSubmit = async (e: React.FormEvent) => {
+        e.preventDefault();
+        const verified = await verifyTwoFactorLogin(code);
+        if (verified) {
+            window.location.href = "/";
+        } else {
+            setError("That code didn't work, try again or sign in with Google again.");
+        }
+    };
+
+    return (
+        <div className="flex flex-col justify-center items-center h-screen text-center p-4" id="home">
+            <Typography placeholder="Two-factor authentication" variant="h3" className="p-4">
+                Two-factor authentication
+            </Typography>
+            <Typography placeholder="Enter code" variant="lead" className="p-4">
+                Enter the code from your authenticator app or one of your recovery codes.
+            </Typography>
+            <form onSubmit={handleSubmit} className="flex flex-col gap-4 w-72">
+                <Input
+                    crossOrigin="true"
+                    placeholder="123456"
+                    label="Code"
+                    value={code}
+                    autoComplete="one-time-code"
+                    onChange={(e) => setCode(e.target.value)}
+                />
+                {error && (
+                    <Typography placeholder="error" color="red" variant="small">
+                        {error}
+                    </Typography>
+                )}
+                <Button placeholder="Verify" type="submit" color="blue" ripple>
+                    Verify
+                </Button>
+            </form>
+        </div>
+    );
+}
@@ -4,6 +4,20 @@ export interface User {
     email: string;
     created_at: string;
     updated_at: string;
+    role: "user" | "admin";
+    disabled_at: string | null;
+    deletion_scheduled_at: string | null;
+    display_name: string | null;
+    avatar_url: string | null;
+}
+
+export interface UserPreferences {
+    theme: "system" | "light" | "dark";
+    editor_font_size: number;
+    keybinding_mode: "default" | "vim" | "emacs";
+    default_export_format: "markdown" | "html" | "pdf";
+    preview_layout: "split" | "editor" | "preview";
+    updated_at: string | null;
 }
 
 export interface Document {
@@ -13,10 +27,26 @@ export interface Document {
     user_uuid: string;
     created_at: string;
     updated_at: string;
+    organisation_uuid: string | null;
+}
+
+export interface Usage {
+    document_count: number;
+    storage_bytes: number;
+    largest_document_bytes: number;
+    max_documents: number;
+    max_storage_bytes: number;
+    max_document_bytes: number;
 }
 
 const serverUrl = import.meta.env.MODE === "production" ? "" : "http://localhost:8080"
 
+// Documents are read from and written to the selected organisation workspace, or the personal one when none is selected
+function workspaceHeaders(): Record<string, string> {
+    const workspaceId = localStorage.getItem("workspace_id");
+    return workspaceId ? { "X-Workspace-Id": workspaceId } : {};
+}
+
 export async function checkAuthentication(): Promise<User | null> {
     try {
         const response = await fetch(`${serverUrl}/users/me`, {
@@ -31,11 +61,118 @@ export async function checkAuthentication(): Promise<User | null> {
     }
 }
 
+export async function getPreferences(): Promise<UserPreferences | null> {
+    try {
+        const response = await fetch(`${serverUrl}/users/me/preferences`, {
+            method: "GET",
+            credentials: "include"
+        });
+        if (!response.ok) {
+            return null;
+        }
+        const data: UserPreferences = await response.json();
+        return data;
+    } catch (error) {
+        console.warn("Error fetching preferences: ", error);
+        return null;
+    }
+}
+
+export async function updatePreferences(preferences: UserPreferences): Promise<UserPreferences | null> {
+    try {
+        const response = await fetch(`${serverUrl}/users/me/preferences`, {
+            method: "PUT",
+            credentials: "include",
+            headers: {
+                "Content-Type": "application/json"
+            },
+            body: JSON.stringify(preferences)
+        });
+        if (!response.ok) {
+            return null;
+        }
+        const data: UserPreferences = await response.json();
+        return data;
+    } catch (error) {
+        console.warn("Error updating preferences: ", error);
+        return null;
+    }
+}
+
+export async function getUsage(): Promise<Usage | null> {
+    try {
+        const response = await fetch(`${serverUrl}/users/me/usage`, {
+            method: "GET",
+            credentials: "include"
+        });
+        if (!response.ok) {
+            return null;
+        }
+        const data: Usage = await response.json();
+        return data;
+    } catch (error) {
+        console.warn("Error fetching usage: ", error);
+        return null;
+    }
+}
+
+export async function requestMagicLink(email: string): Promise<boolean> {
+    try {
+        const response = await fetch(`${serverUrl}/auth/magic-link`, {
+            method: "POST",
+            credentials: "include",
+            headers: {
+                "Content-Type": "application/json"
+            },
+            body: JSON.stringify({ email })
+        });
+        return response.ok;
+    } catch (error) {
+        console.warn("Error requesting magic link: ", error);
+        return false;
+    }
+}
+
+export async function verifyTwoFactorLogin(code: string): Promise<boolean> {
+    try {
+        const response = await fetch(`${serverUrl}/auth/2fa/verify`, {
+            method: "POST",
+            credentials: "include",
+            headers: {
+                "Content-Type": "application/json"
+            },
+            body: JSON.stringify({ code })
+        });
+        return response.ok;
+    } catch (error) {
+        console.warn("Error verifying two-factor code: ", error);
+        return false;
+    }
+}
+
+export async function acceptInvite(token: string): Promise<boolean> {
+    try {
+        const response = await fetch(`${serverUrl}/organisations/invites/accept`, {
+            method: "POST",
+            credentials: "include",
+            headers: {
+                "Content-Type": "application/json"
+            },
+            body: JSON.stringify({ token })
+        });
+        return response.ok;
+    } catch (error) {
+        console.warn("Error accepting invite: ", error);
+        return false;
+    }
+}
+
 export async function getDocuments(): Promise<Document[]> {
     try {
         const response = await fetch(`${serverUrl}/documents/all`, {
             method: "GET",
-            credentials: "include"
+            credentials: "include",
+            headers: workspaceHeaders()
         });
         const data: Document[] = await response.json();
         return data;
@@ -49,7 +186,8 @@ export async function getDocument(uuid: string): Promise<Document> {
     try {
         const response = await fetch(`${serverUrl}/documents/${uuid}`, {
             method: "GET",
-            credentials: "include"
+            credentials: "include",
+            headers: workspaceHeaders()
         });
         const data: Document = await response.json();
         return data;
@@ -65,15 +203,13 @@ export async function createDocument(title: string): Promise<Document> {
             method: "POST",
             credentials: "include",
             headers: {
-                "Content-Type": "application/json"
+                "Content-Type": "application/json",
+                ...workspaceHeaders()
             },
             body: JSON.stringify({
                 uuid: crypto.randomUUID(),
                 title,
-                content: "",
-                user_uuid: localStorage.getItem("user_uuid"),
-                created_at: new Date().toISOString(),
-                updated_at: new Date().toISOString()
+                content: ""
             })
         });
         const data: Document = await response.json();
@@ -88,7 +224,8 @@ export async function deleteDocument(uuid: string): Promise<Document> {
     try {
         const response = await fetch(`${serverUrl}/documents/delete/${uuid}`, {
             method: "DELETE",
-            credentials: "include"
+            credentials: "include",
+            headers: workspaceHeaders()
         });
         const data = await response.json();
         return data;
@@ -104,13 +241,12 @@ export async function updateDocument(uuid: string, title: string, content: strin
             method: "PUT",
             credentials: "include",
             headers: {
-                "Content-Type": "application/json"
+                "Content-Type": "application/json",
+                ...workspaceHeaders()
             },
             body: JSON.stringify({
-                uuid,
                 title,
-                content,
-                updated_at: new Date().toISOString()
+                content
             })
         });
         const data: Document = await response.json();